@@ -12,6 +12,7 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use crate::subcommands::SubCmd;
+use sn_client::NetworkConfigArgs;
 use sn_logging::{parse_log_format, LogFormat, LogOutputDest};
 use sn_peers_acquisition::PeersArgs;
 
@@ -60,6 +61,9 @@ pub(crate) struct Opt {
     #[command(flatten)]
     pub(crate) peers: PeersArgs,
 
+    #[command(flatten)]
+    pub(crate) network_config: NetworkConfigArgs,
+
     /// Available sub commands.
     #[clap(subcommand)]
     pub cmd: SubCmd,
@@ -55,7 +55,8 @@ async fn main() -> Result<()> {
     let client_data_dir_path = get_client_data_dir_path().await?;
     let secret_key = get_client_secret_key(&client_data_dir_path).await?;
 
-    if opt.peers.peers.is_empty() {
+    let peers = opt.peers.get_peers(Some(&client_data_dir_path)).await;
+    if peers.is_empty() {
         if !cfg!(feature = "local-discovery") {
             let log_str = "No peers given. As `local-discovery` feature is disabled, we will not be able to connect to the network.";
             warn!(log_str);
@@ -65,7 +66,15 @@ async fn main() -> Result<()> {
         }
     }
 
-    let client = Client::new(secret_key, Some(opt.peers.peers), opt.timeout).await?;
+    let network_config = opt.network_config.load()?;
+    let client = Client::new(
+        secret_key,
+        Some(peers),
+        opt.timeout,
+        Some(client_data_dir_path.clone()),
+        network_config,
+    )
+    .await?;
 
     // default to verifying storage
     let should_verify_store = !opt.no_verify;
@@ -83,7 +92,9 @@ async fn main() -> Result<()> {
             )
             .await?
         }
-        SubCmd::Register(cmds) => register_cmds(cmds, &client, should_verify_store).await?,
+        SubCmd::Register(cmds) => {
+            register_cmds(cmds, &client, &client_data_dir_path, should_verify_store).await?
+        }
     };
 
     Ok(())
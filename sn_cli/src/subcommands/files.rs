@@ -6,28 +6,84 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::wallet::{chunk_and_pay_for_storage, ChunkedFile};
+use super::{
+    upload_journal::UploadJournal,
+    wallet::{chunk_files, load_wallet_client, pay_for_chunks, ChunkedFile},
+};
 
 use bytes::Bytes;
 use clap::Parser;
-use color_eyre::Result;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
 use sn_client::{Client, Files};
-use sn_protocol::storage::{Chunk, ChunkAddress};
-use sn_transfers::wallet::PaymentProofsMap;
+use sn_protocol::storage::{ChunkAddress, RegisterAddress};
+use sn_transfers::wallet::{LocalWallet, PaymentProofsMap};
 
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
 use xor_name::XorName;
 
+// Number of chunks uploaded between each update of the upload journal.
+const UPLOAD_JOURNAL_BATCH_SIZE: usize = 16;
+
 #[derive(Parser, Debug)]
 pub enum FilesCmds {
     Upload {
         /// The location of the files to upload.
         #[clap(name = "path", value_name = "DIRECTORY")]
         path: PathBuf,
+        /// Resume a previously interrupted upload of the same path.
+        ///
+        /// Chunks already confirmed stored are skipped, and the payment proofs
+        /// recorded in the upload journal are reused instead of paying again.
+        #[clap(long, default_value = "false")]
+        resume: bool,
+        /// Upload the files privately.
+        ///
+        /// The data map of each file is encrypted to the client key,
+        /// so that only this client is able to download the files.
+        #[clap(long, default_value = "false")]
+        private: bool,
+    },
+    /// Upload a folder, along with a manifest of its files stored on the network.
+    ///
+    /// The whole tree of files can then be downloaded from the single address of the manifest.
+    UploadDir {
+        /// The location of the folder to upload.
+        #[clap(name = "path", value_name = "DIRECTORY")]
+        path: PathBuf,
+        /// The name of a register to point to the uploaded folder.
+        ///
+        /// The register is created if it does not exist yet, so that the latest version
+        /// of the folder can be shared through the address of the register.
+        #[clap(long)]
+        register: Option<String>,
+        /// Resume a previously interrupted upload of the same folder.
+        #[clap(long, default_value = "false")]
+        resume: bool,
+        /// Upload the folder privately, so that only this client is able to download it.
+        #[clap(long, default_value = "false")]
+        private: bool,
+    },
+    /// Download a folder, recreating the tree of files of its manifest.
+    DownloadDir {
+        /// Address of the folder manifest, in hex string.
+        #[clap(name = "address")]
+        address: String,
+        /// Use this flag if you are providing the address of a register pointing to the folder,
+        /// instead of the address of the folder manifest.
+        #[clap(long, default_value = "false")]
+        register: bool,
+        /// The directory to download the folder into.
+        /// Defaults to the `downloaded_files` dir of the client.
+        #[clap(long)]
+        dest: Option<PathBuf>,
     },
     Download {
         /// Name of the file to download.
@@ -46,7 +102,33 @@ pub(crate) async fn files_cmds(
     verify_store: bool,
 ) -> Result<()> {
     match cmds {
-        FilesCmds::Upload { path } => upload_files(path, client, root_dir, verify_store).await?,
+        FilesCmds::Upload {
+            path,
+            resume,
+            private,
+        } => upload_files(path, client, root_dir, resume, private, verify_store).await?,
+        FilesCmds::UploadDir {
+            path,
+            register,
+            resume,
+            private,
+        } => {
+            upload_folder(
+                path,
+                client,
+                root_dir,
+                register,
+                resume,
+                private,
+                verify_store,
+            )
+            .await?
+        }
+        FilesCmds::DownloadDir {
+            address,
+            register,
+            dest,
+        } => download_folder(address, register, dest, client, root_dir).await?,
         FilesCmds::Download {
             file_name,
             file_addr,
@@ -80,10 +162,13 @@ pub(crate) async fn files_cmds(
 
 /// Given a directory, upload all files contained
 /// Optionally verifies data was stored successfully
+/// Progress is recorded in an upload journal, so that an interrupted upload can be resumed.
 async fn upload_files(
     files_path: PathBuf,
     client: Client,
     root_dir: &Path,
+    resume: bool,
+    private: bool,
     verify_store: bool,
 ) -> Result<()> {
     let file_api: Files = Files::new(client.clone());
@@ -94,73 +179,291 @@ async fn upload_files(
     );
     // The input files_path has to be a dir
     let file_names_path = root_dir.join("uploaded_files");
+    let journal_dir = UploadJournal::dir_for(root_dir, &files_path);
+
+    let mut journal = match existing_journal(&journal_dir, &files_path, resume).await? {
+        Some(journal) => journal,
+        None => {
+            let chunks_dir = UploadJournal::chunks_dir(&journal_dir);
+            tokio::fs::create_dir_all(&chunks_dir).await?;
+
+            let chunks_to_upload = chunk_files(&client, &files_path, &chunks_dir, private).await?;
+            UploadJournal::create(&journal_dir, &files_path, chunks_to_upload).await?
+        }
+    };
+    pay_for_journaled_chunks(&client, root_dir, &mut journal).await?;
+
+    let chunks_to_fetch = store_journaled_files(&file_api, &mut journal, verify_store).await?;
+    finish_journal(journal).await?;
+
+    let content = bincode::serialize(&chunks_to_fetch)?;
+    tokio::fs::create_dir_all(file_names_path.as_path()).await?;
+    let date_time = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let file_names_path = file_names_path.join(format!("file_names_{date_time}"));
+    println!("Writing {} bytes to {file_names_path:?}", content.len());
+    fs::write(file_names_path, content)?;
+
+    Ok(())
+}
+
+/// Upload all files under a folder, together with a manifest of them.
+/// Optionally points a register to the manifest once everything was stored.
+async fn upload_folder(
+    folder_path: PathBuf,
+    client: Client,
+    root_dir: &Path,
+    register: Option<String>,
+    resume: bool,
+    private: bool,
+    verify_store: bool,
+) -> Result<()> {
+    let file_api: Files = if private {
+        Files::new_private(client.clone())
+    } else {
+        Files::new(client.clone())
+    };
+
+    debug!("Uploading folder {folder_path:?}, will verify?: {verify_store}");
+    let journal_dir = UploadJournal::folder_dir_for(root_dir, &folder_path);
+
+    let mut journal = match existing_journal(&journal_dir, &folder_path, resume).await? {
+        Some(journal) => journal,
+        None => {
+            let chunks_dir = UploadJournal::chunks_dir(&journal_dir);
+            tokio::fs::create_dir_all(&chunks_dir).await?;
+
+            println!(
+                "Preparing (chunking) folder at '{}'...",
+                folder_path.display()
+            );
+            let (manifest_addr, manifest, chunks) =
+                file_api.chunk_folder(&folder_path, &chunks_dir).await?;
+            if manifest.entries().is_empty() {
+                bail!("The provided path does not contain any file. Please check your path!\nExiting...");
+            }
+
+            println!(
+                "Making payment for {} Chunks that belong to a folder of {} file/s.",
+                chunks.len(),
+                manifest.entries().len()
+            );
+
+            // The whole folder is journaled as a single file, stored under the address of its manifest.
+            let folder = ChunkedFile {
+                file_name: folder_path.display().to_string(),
+                size: manifest
+                    .entries()
+                    .iter()
+                    .map(|entry| entry.size as usize)
+                    .sum(),
+                chunks,
+            };
+            let files = BTreeMap::from([(manifest_addr, folder)]);
+            UploadJournal::create(&journal_dir, &folder_path, files).await?
+        }
+    };
+    pay_for_journaled_chunks(&client, root_dir, &mut journal).await?;
+
+    let manifest_addr = match journal.files().keys().next() {
+        Some(manifest_addr) => *manifest_addr,
+        None => bail!("The upload journal of {folder_path:?} does not hold any folder."),
+    };
+    let _ = store_journaled_files(&file_api, &mut journal, verify_store).await?;
+    let complete = journal.is_complete();
+    finish_journal(journal).await?;
+    if !complete {
+        return Ok(());
+    }
+
+    println!("Successfully stored the folder manifest to {manifest_addr:64x}");
+    if let Some(name) = register {
+        let meta = XorName::from_content(name.as_bytes());
+        let mut wallet_client = load_wallet_client(&client, root_dir).await?;
+        let register_addr = file_api
+            .publish_folder_to_register(
+                meta,
+                ChunkAddress::new(manifest_addr),
+                &mut wallet_client,
+                verify_store,
+            )
+            .await?;
+        println!("Successfully pointed register '{name}' at {register_addr} to the folder.");
+        wallet_client.into_wallet().store().await?;
+    }
+
+    Ok(())
+}
+
+/// Download the folder at the given address, which is either the one of the folder manifest,
+/// or the one of a register pointing to it.
+async fn download_folder(
+    address: String,
+    register: bool,
+    dest: Option<PathBuf>,
+    client: Client,
+    root_dir: &Path,
+) -> Result<()> {
+    let file_api: Files = Files::new(client);
 
-    // Payment shall always be verified.
-    let (chunks_to_upload, payment_proofs) =
-        chunk_and_pay_for_storage(&client, root_dir, &files_path, true).await?;
+    let manifest_addr = if register {
+        let register_addr = RegisterAddress::from_hex(&address)?;
+        println!("Trying to retrieve the folder pointed to by Register at {register_addr}");
+        file_api.folder_address_from_register(register_addr).await?
+    } else {
+        let name = hex::decode(&address)?
+            .try_into()
+            .map_err(|_| eyre!("Failed to parse XorName from hex string {address}"))?;
+        ChunkAddress::new(XorName(name))
+    };
+
+    let download_path = dest.unwrap_or_else(|| root_dir.join("downloaded_files"));
+    println!(
+        "Downloading folder {:64x} to {download_path:?}",
+        manifest_addr.xorname()
+    );
+    let manifest = file_api
+        .download_folder(manifest_addr, &download_path)
+        .await?;
+    println!(
+        "Successfully downloaded {} file/s to {download_path:?}",
+        manifest.entries().len()
+    );
+
+    Ok(())
+}
+
+/// Returns the journal of an interrupted upload when resuming,
+/// otherwise clears any previous journal from `journal_dir`.
+async fn existing_journal(
+    journal_dir: &Path,
+    files_path: &Path,
+    resume: bool,
+) -> Result<Option<UploadJournal>> {
+    if resume {
+        match UploadJournal::load(journal_dir).await? {
+            Some(journal) => {
+                println!(
+                    "Resuming upload of {} file/s, reusing {} cached payment proofs.",
+                    journal.files().len(),
+                    journal.payment_proofs().len()
+                );
+                return Ok(Some(journal));
+            }
+            None => {
+                println!("No interrupted upload of {files_path:?} was found, starting a new one.")
+            }
+        }
+    }
+
+    if journal_dir.exists() {
+        tokio::fs::remove_dir_all(journal_dir).await?;
+    }
+    Ok(None)
+}
+
+/// Pays for the chunks of the journal we hold no payment proof for, recording the proofs in it.
+/// Those of an interrupted payment, which the wallet cached, are recovered rather than paid again.
+async fn pay_for_journaled_chunks(
+    client: &Client,
+    root_dir: &Path,
+    journal: &mut UploadJournal,
+) -> Result<()> {
+    let unpaid_chunks = journal.unpaid_chunks();
+    if unpaid_chunks.is_empty() {
+        return Ok(());
+    }
+
+    let wallet = LocalWallet::load_from(root_dir).await?;
+    let (cached, unpaid_chunks): (Vec<_>, Vec<_>) = unpaid_chunks
+        .into_iter()
+        .partition(|name| wallet.get_payment_proof(name).is_some());
+    let cached_proofs: PaymentProofsMap = cached
+        .into_iter()
+        .filter_map(|name| Some((name, wallet.get_payment_proof(&name)?.clone())))
+        .collect();
+    if !cached_proofs.is_empty() {
+        println!(
+            "Reusing {} payment proofs cached by the wallet.",
+            cached_proofs.len()
+        );
+        journal.add_payment_proofs(cached_proofs).await?;
+    }
+
+    if !unpaid_chunks.is_empty() {
+        // Payment shall always be verified.
+        let payment_proofs = pay_for_chunks(client, root_dir, unpaid_chunks.iter(), true).await?;
+        journal.add_payment_proofs(payment_proofs).await?;
+    }
+    Ok(())
+}
 
-    let mut chunks_to_fetch = Vec::new();
-    for (
-        file_addr,
-        ChunkedFile {
+/// Stores the pending chunks of all the files of the journal.
+/// Returns the address and name of each file.
+async fn store_journaled_files(
+    file_api: &Files,
+    journal: &mut UploadJournal,
+    verify_store: bool,
+) -> Result<Vec<(XorName, String)>> {
+    let mut stored_files = Vec::new();
+    let file_addrs: Vec<XorName> = journal.files().keys().copied().collect();
+    for file_addr in file_addrs {
+        let ChunkedFile {
             file_name,
             size,
             chunks,
-        },
-    ) in chunks_to_upload.into_iter()
-    {
+        } = &journal.files()[&file_addr];
+        let file_name = file_name.clone();
+        let pending_chunks = journal.pending_chunks(&file_addr);
+
         println!(
-            "Storing file '{file_name}' of {size} bytes ({} chunk/s)..",
-            chunks.len()
+            "Storing file '{file_name}' of {size} bytes ({} chunk/s, {} already stored)..",
+            chunks.len(),
+            chunks.len() - pending_chunks.len()
         );
 
-        if let Err(error) =
-            upload_chunks(&file_api, &file_name, chunks, &payment_proofs, verify_store).await
-        {
+        if let Err(error) = upload_chunks(file_api, journal, pending_chunks, verify_store).await {
             println!("Failed to store all chunks of file '{file_name}' to all nodes in the close group: {error}")
         } else {
             println!("Successfully stored '{file_name}' to {file_addr:64x}");
         }
 
-        chunks_to_fetch.push((file_addr, file_name));
+        stored_files.push((file_addr, file_name));
     }
 
-    let content = bincode::serialize(&chunks_to_fetch)?;
-    tokio::fs::create_dir_all(file_names_path.as_path()).await?;
-    let date_time = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-    let file_names_path = file_names_path.join(format!("file_names_{date_time}"));
-    println!("Writing {} bytes to {file_names_path:?}", content.len());
-    fs::write(file_names_path, content)?;
+    Ok(stored_files)
+}
 
+/// Removes the journal once all its chunks were stored,
+/// otherwise keeps it for the upload to be resumed.
+async fn finish_journal(journal: UploadJournal) -> Result<()> {
+    if journal.is_complete() {
+        journal.remove().await?;
+    } else {
+        println!("Not all chunks were stored. Run the upload again with `--resume` to retry only the missing ones.");
+    }
     Ok(())
 }
 
-/// Upload chunks of an individual file to the network.
+/// Upload chunks of an individual file to the network,
+/// recording each batch that was stored in the upload journal.
 async fn upload_chunks(
     file_api: &Files,
-    file_name: &str,
+    journal: &mut UploadJournal,
     chunks_paths: Vec<(XorName, PathBuf)>,
-    payment_proofs: &PaymentProofsMap,
     verify_store: bool,
 ) -> Result<()> {
-    let chunks_reader = chunks_paths
-        .into_iter()
-        .map(|(name, chunk_path)| (name, fs::read(chunk_path)))
-        .filter_map(|x| match x {
-            (name, Ok(file)) => Some(Chunk {
-                address: ChunkAddress::new(name),
-                value: Bytes::from(file),
-            }),
-            (_, Err(err)) => {
-                // FIXME: this error won't be seen/reported, thus assumed all chunks were read and stored.
-                println!("Could not upload generated chunk of file '{file_name}': {err}");
-                None
-            }
-        });
+    for batch in chunks_paths.chunks(UPLOAD_JOURNAL_BATCH_SIZE) {
+        file_api
+            .upload_chunks_from_paths(
+                batch.iter().cloned(),
+                journal.payment_proofs(),
+                verify_store,
+            )
+            .await?;
 
-    file_api
-        .upload_chunks_in_batches(chunks_reader, payment_proofs, verify_store)
-        .await?;
+        let names: Vec<XorName> = batch.iter().map(|(name, _)| *name).collect();
+        journal.mark_stored(&names).await?;
+    }
     Ok(())
 }
 
@@ -203,15 +506,15 @@ async fn download_file(
         xorname
     );
     debug!("Downloading file {file_name:?}");
-    match file_api.read_bytes(ChunkAddress::new(*xorname)).await {
-        Ok(bytes) => {
+    let file_name_path = download_path.join(file_name);
+    match file_api
+        .read_to_path(ChunkAddress::new(*xorname), &file_name_path)
+        .await
+    {
+        Ok(written) => {
             debug!("Successfully got file {file_name}!");
             println!("Successfully got file {file_name}!");
-            let file_name_path = download_path.join(file_name);
-            println!("Writing {} bytes to {file_name_path:?}", bytes.len());
-            if let Err(err) = fs::write(file_name_path, bytes) {
-                println!("Failed to create file {file_name:?} with error {err:?}");
-            }
+            println!("Wrote {written} bytes to {file_name_path:?}");
         }
         Err(error) => {
             error!("Did not get file {file_name:?} from the network! {error}");
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 pub(crate) mod files;
 pub(crate) mod register;
+mod upload_journal;
 pub(crate) mod wallet;
 
 use clap::Subcommand;
@@ -6,16 +6,19 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::wallet::load_wallet_client;
+
 use bls::PublicKey;
 use clap::Subcommand;
 use color_eyre::Result;
 use sn_client::{Client, ClientRegister, Error as ClientError};
 use sn_protocol::storage::RegisterAddress;
+use std::path::Path;
 use xor_name::XorName;
 
 #[derive(Subcommand, Debug)]
 pub enum RegisterCmds {
-    /// Create a new register with a name.
+    /// Create a new register with a name, paying for it with the local wallet.
     Create {
         /// The name of the register to create. This could be the app's name.
         /// This is used along with your public key to derive the address of the register
@@ -50,10 +53,13 @@ pub enum RegisterCmds {
 pub(crate) async fn register_cmds(
     cmds: RegisterCmds,
     client: &Client,
+    root_dir: &Path,
     verify_store: bool,
 ) -> Result<()> {
     match cmds {
-        RegisterCmds::Create { name } => create_register(name, client, verify_store).await?,
+        RegisterCmds::Create { name } => {
+            create_register(name, client, root_dir, verify_store).await?
+        }
         RegisterCmds::Edit {
             address,
             use_name,
@@ -67,13 +73,28 @@ pub(crate) async fn register_cmds(
     Ok(())
 }
 
-async fn create_register(name: String, client: &Client, verify_store: bool) -> Result<()> {
+async fn create_register(
+    name: String,
+    client: &Client,
+    root_dir: &Path,
+    verify_store: bool,
+) -> Result<()> {
     let meta = XorName::from_content(name.as_bytes());
-    let register = ClientRegister::create_online(client.clone(), meta, verify_store).await?;
+    let mut wallet_client = load_wallet_client(client, root_dir).await?;
+    let register =
+        ClientRegister::create_online(client.clone(), meta, &mut wallet_client, verify_store)
+            .await?;
     println!(
         "Successfully created register '{name}' at {}!",
         register.address()
     );
+
+    let mut wallet = wallet_client.into_wallet();
+    wallet.store().await?;
+    println!(
+        "Successfully stored wallet with new balance {}.",
+        wallet.balance()
+    );
     Ok(())
 }
 
@@ -0,0 +1,349 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::wallet::ChunkedFile;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sn_transfers::wallet::PaymentProofsMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+use xor_name::XorName;
+
+const UPLOAD_JOURNAL_DIR_NAME: &str = "upload_journal";
+const JOURNAL_FILE_NAME: &str = "journal";
+const STORED_CHUNKS_FILE_NAME: &str = "stored_chunks";
+const CHUNKS_DIR_NAME: &str = "chunks";
+
+/// The part of the journal which is written once the files were chunked,
+/// and rewritten as they are paid for.
+#[derive(Serialize, Deserialize)]
+struct JournalContent {
+    files_path: PathBuf,
+    files: BTreeMap<XorName, ChunkedFile>,
+    payment_proofs: PaymentProofsMap,
+}
+
+/// An on-disk record of an upload of all the files under a path.
+///
+/// It holds the chunk set of each file, together with the payment proofs for them,
+/// and an append-only list of the chunks that were confirmed stored.
+/// This allows an interrupted upload to be resumed without paying again,
+/// and without re-uploading the chunks already stored.
+pub(super) struct UploadJournal {
+    dir: PathBuf,
+    content: JournalContent,
+    stored: BTreeSet<XorName>,
+}
+
+impl UploadJournal {
+    /// Returns the journal dir for uploading the files at `files_path`.
+    pub(super) fn dir_for(root_dir: &Path, files_path: &Path) -> PathBuf {
+        let files_path = canonical_string(files_path);
+        let name = XorName::from_content(files_path.as_bytes());
+        root_dir
+            .join(UPLOAD_JOURNAL_DIR_NAME)
+            .join(hex::encode(name))
+    }
+
+    /// Returns the journal dir for uploading the folder at `folder_path`, along with its manifest.
+    /// It differs from the one for uploading the files at the same path.
+    pub(super) fn folder_dir_for(root_dir: &Path, folder_path: &Path) -> PathBuf {
+        let folder_path = canonical_string(folder_path);
+        let name = XorName::from_content_parts(&[b"folder", folder_path.as_bytes()]);
+        root_dir
+            .join(UPLOAD_JOURNAL_DIR_NAME)
+            .join(hex::encode(name))
+    }
+
+    /// Returns the dir where the chunks of a journal are kept until they have all been stored.
+    pub(super) fn chunks_dir(journal_dir: &Path) -> PathBuf {
+        journal_dir.join(CHUNKS_DIR_NAME)
+    }
+
+    /// Writes a new journal to `journal_dir`, replacing any previous one.
+    /// It is written before paying for the chunks, for the payment proofs to be recorded in it.
+    pub(super) async fn create(
+        journal_dir: &Path,
+        files_path: &Path,
+        files: BTreeMap<XorName, ChunkedFile>,
+    ) -> Result<Self> {
+        fs::create_dir_all(journal_dir).await?;
+        let journal = Self {
+            dir: journal_dir.to_path_buf(),
+            content: JournalContent {
+                files_path: files_path.to_path_buf(),
+                files,
+                payment_proofs: PaymentProofsMap::default(),
+            },
+            stored: BTreeSet::new(),
+        };
+
+        journal.write_content().await?;
+        fs::write(journal_dir.join(STORED_CHUNKS_FILE_NAME), b"").await?;
+
+        Ok(journal)
+    }
+
+    /// Returns `Some(UploadJournal)` or None if there is no journal in `journal_dir`.
+    pub(super) async fn load(journal_dir: &Path) -> Result<Option<Self>> {
+        let journal_path = journal_dir.join(JOURNAL_FILE_NAME);
+        if !journal_path.is_file() {
+            return Ok(None);
+        }
+
+        let bytes = fs::read(&journal_path).await?;
+        let content: JournalContent = bincode::deserialize(&bytes)?;
+
+        let stored_path = journal_dir.join(STORED_CHUNKS_FILE_NAME);
+        let mut stored = BTreeSet::new();
+        if stored_path.is_file() {
+            for line in fs::read_to_string(&stored_path).await?.lines() {
+                // A partially written line, from being interrupted, is simply not counted as stored.
+                if let Some(name) = hex::decode(line.trim())
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                {
+                    let _ = stored.insert(XorName(name));
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            dir: journal_dir.to_path_buf(),
+            content,
+            stored,
+        }))
+    }
+
+    /// The files being uploaded, by their data-map address.
+    pub(super) fn files(&self) -> &BTreeMap<XorName, ChunkedFile> {
+        &self.content.files
+    }
+
+    /// The payment proofs made for the chunks of all the files.
+    pub(super) fn payment_proofs(&self) -> &PaymentProofsMap {
+        &self.content.payment_proofs
+    }
+
+    /// Returns the chunks not yet stored which we hold no payment proof for.
+    pub(super) fn unpaid_chunks(&self) -> Vec<XorName> {
+        self.content
+            .files
+            .values()
+            .flat_map(|file| &file.chunks)
+            .map(|(name, _)| *name)
+            .filter(|name| {
+                !self.stored.contains(name) && !self.content.payment_proofs.contains_key(name)
+            })
+            .collect()
+    }
+
+    /// Records the payment proofs made for the chunks, syncing them to disk.
+    pub(super) async fn add_payment_proofs(&mut self, proofs: PaymentProofsMap) -> Result<()> {
+        self.content.payment_proofs.extend(proofs);
+        self.write_content().await
+    }
+
+    /// Returns the chunks of the file which have not yet been confirmed stored.
+    pub(super) fn pending_chunks(&self, file_addr: &XorName) -> Vec<(XorName, PathBuf)> {
+        self.content
+            .files
+            .get(file_addr)
+            .map(|file| {
+                file.chunks
+                    .iter()
+                    .filter(|(name, _)| !self.stored.contains(name))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns true if all chunks of all files have been confirmed stored.
+    pub(super) fn is_complete(&self) -> bool {
+        self.content
+            .files
+            .values()
+            .flat_map(|file| &file.chunks)
+            .all(|(name, _)| self.stored.contains(name))
+    }
+
+    /// Appends the given chunks to the list of those confirmed stored, syncing it to disk.
+    pub(super) async fn mark_stored(&mut self, names: &[XorName]) -> Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(STORED_CHUNKS_FILE_NAME))
+            .await?;
+
+        // Starting on a new line, in case the previous write was interrupted mid-line.
+        let mut lines = String::from("\n");
+        for name in names {
+            lines.push_str(&hex::encode(name));
+            lines.push('\n');
+        }
+        file.write_all(lines.as_bytes()).await?;
+        file.sync_data().await?;
+
+        self.stored.extend(names.iter().copied());
+        Ok(())
+    }
+
+    // Replaces the journal file, through a temp file so that it is never found partially written.
+    async fn write_content(&self) -> Result<()> {
+        let bytes = bincode::serialize(&self.content)?;
+        let tmp_path = self.dir.join(format!("{JOURNAL_FILE_NAME}.tmp"));
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(&bytes).await?;
+        file.sync_all().await?;
+        fs::rename(tmp_path, self.dir.join(JOURNAL_FILE_NAME)).await?;
+        Ok(())
+    }
+
+    /// Removes the journal, and the chunks kept with it, from disk.
+    pub(super) async fn remove(self) -> Result<()> {
+        fs::remove_dir_all(&self.dir).await?;
+        Ok(())
+    }
+}
+
+fn canonical_string(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_protocol::messages::PaymentProof;
+    use tempfile::TempDir;
+
+    fn chunked_file(names: &[XorName]) -> ChunkedFile {
+        ChunkedFile {
+            file_name: "file".to_string(),
+            size: names.len() * 1024,
+            chunks: names
+                .iter()
+                .map(|name| (*name, PathBuf::from(hex::encode(name))))
+                .collect(),
+        }
+    }
+
+    fn payment_proofs(names: &[XorName]) -> PaymentProofsMap {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    *name,
+                    PaymentProof {
+                        spent_ids: vec![],
+                        audit_trail: vec![],
+                        path: vec![],
+                        quotes: vec![],
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn names(count: usize) -> Vec<XorName> {
+        (0..count)
+            .map(|index| XorName::from_content(&index.to_le_bytes()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn journal_round_trips_through_disk() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let journal_dir = tmp_dir.path().join("journal");
+        assert!(UploadJournal::load(&journal_dir).await?.is_none());
+
+        let file_addr = XorName::from_content(b"file");
+        let names = names(4);
+        let files = BTreeMap::from([(file_addr, chunked_file(&names))]);
+        let mut journal = UploadJournal::create(&journal_dir, tmp_dir.path(), files).await?;
+
+        // Written before the payment, so that it can be resumed if interrupted while paying.
+        let loaded = UploadJournal::load(&journal_dir)
+            .await?
+            .expect("The journal was not written");
+        assert_eq!(loaded.unpaid_chunks().len(), 4);
+        assert_eq!(loaded.files().len(), 1);
+
+        journal.add_payment_proofs(payment_proofs(&names)).await?;
+        let loaded = UploadJournal::load(&journal_dir)
+            .await?
+            .expect("The journal was not written");
+        assert!(loaded.unpaid_chunks().is_empty());
+        assert_eq!(loaded.payment_proofs().len(), 4);
+        assert_eq!(
+            loaded.pending_chunks(&file_addr),
+            journal.pending_chunks(&file_addr)
+        );
+
+        journal.remove().await?;
+        assert!(!journal_dir.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partial_upload_resumes_with_the_pending_chunks() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let journal_dir = tmp_dir.path().join("journal");
+        let file_addr = XorName::from_content(b"file");
+        let names = names(6);
+        let files = BTreeMap::from([(file_addr, chunked_file(&names))]);
+        let mut journal = UploadJournal::create(&journal_dir, tmp_dir.path(), files).await?;
+
+        // Interrupted while paying, with only some of the proofs recorded.
+        journal
+            .add_payment_proofs(payment_proofs(&names[..2]))
+            .await?;
+        let mut journal = UploadJournal::load(&journal_dir)
+            .await?
+            .expect("The journal was not written");
+        assert_eq!(journal.unpaid_chunks().len(), 4);
+        journal
+            .add_payment_proofs(payment_proofs(&names[2..]))
+            .await?;
+
+        // Then while storing, with a partially written line.
+        journal.mark_stored(&names[..3]).await?;
+        let mut stored_file = fs::OpenOptions::new()
+            .append(true)
+            .open(journal_dir.join(STORED_CHUNKS_FILE_NAME))
+            .await?;
+        stored_file.write_all(b"0a1b").await?;
+        stored_file.sync_data().await?;
+
+        let mut journal = UploadJournal::load(&journal_dir)
+            .await?
+            .expect("The journal was not written");
+        let pending: Vec<_> = journal
+            .pending_chunks(&file_addr)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(pending, names[3..].to_vec());
+        assert!(!journal.is_complete());
+
+        journal.mark_stored(&pending).await?;
+        assert!(journal.is_complete());
+        let journal = UploadJournal::load(&journal_dir)
+            .await?
+            .expect("The journal was not written");
+        assert!(journal.is_complete());
+        Ok(())
+    }
+}
@@ -7,17 +7,24 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use sn_client::{Client, Files, WalletClient};
-use sn_dbc::Token;
-use sn_transfers::wallet::{parse_public_address, LocalWallet, PaymentProofsMap};
+use sn_dbc::{PublicAddress, Token};
+use sn_protocol::messages::Transfer;
+use sn_transfers::{
+    client_transfers::CoinSelection,
+    wallet::{
+        history_to_csv, history_to_json, parse_public_address, LocalWallet, PaymentProofsMap,
+    },
+};
 
-use bytes::Bytes;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use color_eyre::{eyre::bail, eyre::WrapErr, Result, Section};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fs,
     io::Read,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use url::Url;
 use walkdir::WalkDir;
@@ -56,14 +63,56 @@ pub enum WalletCmds {
         #[clap(name = "url")]
         url: String,
     },
-    /// Send a DBC.
+    /// Send tokens to a public address, or to each of the recipients listed in a file.
+    ///
+    /// Prints the transfer to hand over to each recipient, for them to receive the tokens.
     Send {
         /// The number of nanos to send.
-        #[clap(name = "amount")]
-        amount: String,
+        #[clap(name = "amount", required_unless_present = "recipients")]
+        amount: Option<String>,
         /// Hex-encoded public address of the recipient.
-        #[clap(name = "to")]
-        to: String,
+        #[clap(name = "to", required_unless_present = "recipients")]
+        to: Option<String>,
+        /// A file listing the recipients to send to in a single transaction.
+        ///
+        /// Either a CSV file with an `address,amount` line per recipient,
+        /// or a JSON file holding an array of `{"address": ..., "amount": ...}` objects.
+        #[clap(long, value_name = "path", conflicts_with_all = ["amount", "to"])]
+        recipients: Option<PathBuf>,
+        /// How the DBCs to spend are picked. Defaults to the largest first.
+        #[clap(long, value_enum)]
+        coin_selection: Option<CoinSelection>,
+        /// Print the DBCs the send would spend, and the change it would leave, without sending.
+        ///
+        /// With the random coin selection, the plan is only illustrative,
+        /// as the send picks its DBCs anew.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Receive the tokens of a transfer made to this wallet.
+    ///
+    /// The DBCs are rebuilt and verified out of the spends found in the network.
+    Receive {
+        /// The hex encoded transfer, as printed by the sender.
+        #[clap(name = "transfer")]
+        transfer: String,
+    },
+    /// Check the wallet's available DBCs are still unspent in the network.
+    ///
+    /// Those found spent, e.g. by a copy of the wallet on another machine, are moved to the spent ones,
+    /// and any double spend found is reported.
+    Audit,
+    /// Print the history of the wallet.
+    ///
+    /// It lists the deposits, sends, storage payments and change outputs,
+    /// along with whether the network has confirmed their transaction.
+    History {
+        /// Export the history in this format, rather than printing it.
+        #[clap(long, value_enum)]
+        export: Option<HistoryFormat>,
+        /// Write the export to this file, rather than to stdout.
+        #[clap(long, requires = "export")]
+        output: Option<PathBuf>,
     },
     /// Make a payment for chunk storage based on files to be stored.
     ///
@@ -75,6 +124,20 @@ pub enum WalletCmds {
     },
 }
 
+/// The formats the wallet history can be exported in.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+/// A recipient listed in a JSON recipients file.
+#[derive(Deserialize)]
+struct Recipient {
+    address: String,
+    amount: String,
+}
+
 pub(crate) async fn wallet_cmds(
     cmds: WalletCmds,
     client: &Client,
@@ -86,9 +149,42 @@ pub(crate) async fn wallet_cmds(
         WalletCmds::Balance => balance(root_dir).await?,
         WalletCmds::Deposit { stdin, dbc } => deposit(root_dir, stdin, dbc).await?,
         WalletCmds::GetFaucet { url } => get_faucet(root_dir, url).await?,
-        WalletCmds::Send { amount, to } => send(amount, to, client, root_dir, verify_store).await?,
+        WalletCmds::Send {
+            amount,
+            to,
+            recipients,
+            coin_selection,
+            dry_run,
+        } => {
+            let recipients = match (recipients, amount, to) {
+                (Some(path), ..) => read_recipients(&path)?,
+                (None, Some(amount), Some(to)) => vec![parse_recipient(&to, &amount)?],
+                _ => {
+                    bail!("Either an amount and a recipient, or a recipients file, must be given.")
+                }
+            };
+            let coin_selection = coin_selection.unwrap_or_default();
+            if dry_run {
+                plan_send(recipients, coin_selection, root_dir).await?
+            } else {
+                send(recipients, coin_selection, client, root_dir, verify_store).await?
+            }
+        }
+        WalletCmds::Receive { transfer } => receive(transfer, client, root_dir).await?,
+        WalletCmds::Audit => audit(client, root_dir).await?,
+        WalletCmds::History { export, output } => history(root_dir, export, output).await?,
         WalletCmds::Pay { path } => {
-            chunk_and_pay_for_storage(client, root_dir, &path, verify_store).await?;
+            // The chunks are only needed to pay for them, so they go to a dir removed once paid.
+            let chunks_dir = tempfile::tempdir()?;
+            chunk_and_pay_for_storage(
+                client,
+                root_dir,
+                &path,
+                chunks_dir.path(),
+                false,
+                verify_store,
+            )
+            .await?;
         }
     }
     Ok(())
@@ -108,6 +204,102 @@ async fn balance(root_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+async fn audit(client: &Client, root_dir: &Path) -> Result<()> {
+    let wallet = LocalWallet::load_from(root_dir).await?;
+    let old_balance = wallet.balance();
+    let mut wallet_client = new_wallet_client(client, wallet).await;
+
+    println!("Checking the available DBCs against the network...");
+    let audit = wallet_client.verify_wallet().await;
+
+    for (dbc_id, tx_hashes) in &audit.double_spent {
+        println!(
+            "Double spend found for DBC {}, spent in the transactions:",
+            hex::encode(dbc_id.to_bytes())
+        );
+        for tx_hash in tx_hashes {
+            println!("  {}", tx_hash.to_hex());
+        }
+    }
+    for (dbc_id, amount) in &audit.spent {
+        println!(
+            "DBC {} of {amount} was already spent.",
+            hex::encode(dbc_id.to_bytes())
+        );
+    }
+    for (dbc_id, err) in &audit.failed {
+        println!(
+            "Could not check DBC {}: {err}",
+            hex::encode(dbc_id.to_bytes())
+        );
+    }
+
+    let mut wallet = wallet_client.into_wallet();
+    let new_balance = wallet.balance();
+    wallet.store().await?;
+
+    println!(
+        "Checked {} DBC(s): {} spent, {} double spent, {} unchecked.\nOld balance: {old_balance}\nNew balance: {new_balance}",
+        audit.checked,
+        audit.spent.len(),
+        audit.double_spent.len(),
+        audit.failed.len()
+    );
+    Ok(())
+}
+
+async fn history(
+    root_dir: &Path,
+    export: Option<HistoryFormat>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let wallet = LocalWallet::load_from(root_dir).await?;
+    let records = wallet.history().await?;
+
+    let exported = match export {
+        Some(HistoryFormat::Csv) => history_to_csv(&records),
+        Some(HistoryFormat::Json) => history_to_json(&records)?,
+        None => {
+            if records.is_empty() {
+                println!("No history yet.");
+            }
+            for record in records {
+                let date_time =
+                    chrono::NaiveDateTime::from_timestamp_opt(record.timestamp as i64, 0)
+                        .map(|date_time| date_time.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default();
+                let mut details = String::new();
+                if let Some(recipient) = &record.recipient {
+                    details.push_str(&format!(" to {recipient}"));
+                }
+                if let (Some(root_hash), Some(records_count)) =
+                    (&record.root_hash, record.records_count)
+                {
+                    details.push_str(&format!(" for {records_count} records of {root_hash}"));
+                }
+                println!(
+                    "{date_time} UTC  {:<15} {:>20}  {:<9}  tx {}{details}",
+                    record.kind,
+                    Token::from_nano(record.amount).to_string(),
+                    record.state,
+                    record.tx_hash,
+                );
+            }
+            return Ok(());
+        }
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(&path, exported)
+                .wrap_err_with(|| format!("Failed to write the history to {path:?}"))?;
+            println!("Exported the wallet history to {path:?}.");
+        }
+        None => print!("{exported}"),
+    }
+    Ok(())
+}
+
 async fn get_faucet(root_dir: &Path, url: String) -> Result<()> {
     let wallet = LocalWallet::load_from(root_dir).await?;
     let address_hex = hex::encode(wallet.address().to_bytes());
@@ -183,28 +375,88 @@ async fn deposit_from_dbc_hex(root_dir: &Path, input: String) -> Result<()> {
     Ok(())
 }
 
-async fn send(
-    amount: String,
-    to: String,
-    client: &Client,
+// Parses the recipients file, as JSON if it has the extension, as CSV otherwise.
+fn read_recipients(path: &Path) -> Result<Vec<(Token, PublicAddress)>> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read the recipients file {path:?}"))?;
+
+    if path.extension().map_or(false, |ext| ext == "json") {
+        let recipients: Vec<Recipient> =
+            serde_json::from_str(&contents).wrap_err("Failed to parse the recipients file")?;
+        return recipients
+            .iter()
+            .map(|recipient| parse_recipient(&recipient.address, &recipient.amount))
+            .collect();
+    }
+
+    let mut recipients = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        // The header line is optional.
+        if line.is_empty() || (index == 0 && line == "address,amount") {
+            continue;
+        }
+        let Some((address, amount)) = line.split_once(',') else {
+            bail!("Line {} of the recipients file is not `address,amount`", index + 1);
+        };
+        recipients.push(
+            parse_recipient(address.trim(), amount.trim())
+                .wrap_err_with(|| format!("Invalid line {} of the recipients file", index + 1))?,
+        );
+    }
+    Ok(recipients)
+}
+
+fn parse_recipient(address: &str, amount: &str) -> Result<(Token, PublicAddress)> {
+    let address = parse_public_address(address)?;
+    let amount = Token::from_str(amount)?;
+    if amount.as_nano() == 0 {
+        bail!("Invalid format or zero amount passed in for {address:?}.");
+    }
+    Ok((amount, address))
+}
+
+async fn plan_send(
+    recipients: Vec<(Token, PublicAddress)>,
+    coin_selection: CoinSelection,
     root_dir: &Path,
-    verify_store: bool,
 ) -> Result<()> {
-    let address = parse_public_address(to)?;
+    let mut wallet = LocalWallet::load_from(root_dir).await?;
+    wallet.set_coin_selection(coin_selection);
+    let plan = wallet.plan_send(recipients)?;
 
-    use std::str::FromStr;
-    let amount = Token::from_str(&amount)?;
-    if amount.as_nano() == 0 {
-        println!("Invalid format or zero amount passed in. Nothing sent.");
-        return Ok(());
+    println!("Inputs:");
+    for (dbc_id, amount) in &plan.inputs {
+        println!("  {} {amount}", hex::encode(dbc_id.to_bytes()));
+    }
+    println!("Outputs:");
+    for (amount, address) in &plan.outputs {
+        println!("  {} {amount}", hex::encode(address.to_bytes()));
     }
+    println!("Change: {}", plan.change);
+    println!("Nothing was sent.");
+    Ok(())
+}
 
-    let wallet = LocalWallet::load_from(root_dir).await?;
-    let mut wallet_client = WalletClient::new(client.clone(), wallet);
+async fn send(
+    recipients: Vec<(Token, PublicAddress)>,
+    coin_selection: CoinSelection,
+    client: &Client,
+    root_dir: &Path,
+    verify_store: bool,
+) -> Result<()> {
+    let mut wallet = LocalWallet::load_from(root_dir).await?;
+    wallet.set_coin_selection(coin_selection);
+    let mut wallet_client = new_wallet_client(client, wallet).await;
 
-    match wallet_client.send(amount, address, verify_store).await {
-        Ok(new_dbc) => {
-            println!("Sent {amount:?} to {address:?}");
+    match wallet_client
+        .send_to_many(recipients.clone(), verify_store)
+        .await
+    {
+        Ok(transfers) => {
+            for (amount, address) in &recipients {
+                println!("Sent {amount:?} to {address:?}");
+            }
             let mut wallet = wallet_client.into_wallet();
             let new_balance = wallet.balance();
 
@@ -214,17 +466,51 @@ async fn send(
                 println!("Successfully stored wallet with new balance {new_balance}.");
             }
 
-            wallet.store_created_dbc(new_dbc).await?;
-            println!("Successfully stored new dbc to wallet dir. It can now be sent to the recipient, using any channel of choice.");
+            println!("The transfers below can now be sent to their recipient, using any channel of choice. Only they can receive the tokens out of them.");
+            for (address, transfer) in transfers {
+                println!(
+                    "{}: {}",
+                    hex::encode(address.to_bytes()),
+                    transfer.to_hex()?
+                );
+            }
         }
         Err(err) => {
-            println!("Failed to send {amount:?} to {address:?} due to {err:?}.");
+            println!(
+                "Failed to send to {} recipient(s) due to {err:?}.",
+                recipients.len()
+            );
         }
     }
 
     Ok(())
 }
 
+async fn receive(transfer: String, client: &Client, root_dir: &Path) -> Result<()> {
+    let transfer = Transfer::from_hex(&transfer).wrap_err("Failed to parse the transfer")?;
+
+    let wallet = LocalWallet::load_from(root_dir).await?;
+    let old_balance = wallet.balance();
+    let mut wallet_client = new_wallet_client(client, wallet).await;
+
+    let dbcs = wallet_client
+        .receive(&transfer)
+        .await
+        .wrap_err("Failed to receive the transfer")
+        .suggestion(
+            "The spends of the transfer may not have reached the network yet. Please retry later.",
+        )?;
+
+    let mut wallet = wallet_client.into_wallet();
+    let new_balance = wallet.balance();
+    wallet.store().await?;
+
+    println!("Successfully received {} dbc(s) to the wallet. \nOld balance: {old_balance}\nNew balance: {new_balance}", dbcs.len());
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
 pub(super) struct ChunkedFile {
     pub file_name: String,
     pub size: usize,
@@ -235,23 +521,44 @@ pub(super) async fn chunk_and_pay_for_storage(
     client: &Client,
     root_dir: &Path,
     files_path: &Path,
+    chunks_dir: &Path,
+    private: bool,
     verify_store: bool,
 ) -> Result<(BTreeMap<XorName, ChunkedFile>, PaymentProofsMap)> {
-    let wallet = LocalWallet::load_from(root_dir)
-        .await
-        .wrap_err("Unable to read wallet file in {path:?}")
-        .suggestion(
-            "If you have an old wallet file, it may no longer be compatible. Try removing it",
-        )?;
-    let mut wallet_client = WalletClient::new(client.clone(), wallet);
-    let file_api: Files = Files::new(client.clone());
+    let chunked_files = chunk_files(client, files_path, chunks_dir, private).await?;
+
+    let proofs = pay_for_chunks(
+        client,
+        root_dir,
+        chunked_files
+            .values()
+            .flat_map(|chunked_file| &chunked_file.chunks)
+            .map(|(name, _)| name),
+        verify_store,
+    )
+    .await?;
+
+    Ok((chunked_files, proofs))
+}
+
+/// Chunks the files found at `files_path` into `chunks_dir`, returning them by their data-map address.
+pub(super) async fn chunk_files(
+    client: &Client,
+    files_path: &Path,
+    chunks_dir: &Path,
+    private: bool,
+) -> Result<BTreeMap<XorName, ChunkedFile>> {
+    let file_api: Files = if private {
+        Files::new_private(client.clone())
+    } else {
+        Files::new(client.clone())
+    };
 
     // Get the list of Chunks addresses from the files found at 'files_path'
     println!(
         "Preparing (chunking) files at '{}'...",
         files_path.display()
     );
-    let chunks_dir = std::env::temp_dir();
     let mut num_of_chunks = 0;
     let mut chunked_files = BTreeMap::new();
     for entry in WalkDir::new(files_path).into_iter().flatten() {
@@ -266,27 +573,19 @@ pub(super) async fn chunk_and_pay_for_storage(
                 continue;
             };
 
-            let file = fs::read(entry.path())?;
-            let bytes = Bytes::from(file);
-            // we need all chunks addresses not just the data-map addr
-            let (file_addr, chunks) = file_api.chunk_bytes(bytes.clone())?;
-            let mut chunks_paths = vec![];
-            for c in chunks.iter() {
-                num_of_chunks += 1;
-                let xorname = *c.name();
-                // let's store the chunk on temp file for the user
-                // to be able to upload it to the network after making the payment,
-                // without needing to chunk the files again.
-                let path = chunks_dir.join(hex::encode(xorname));
-                fs::write(&path, c.value())?;
-                chunks_paths.push((xorname, path));
-            }
+            let size = fs::metadata(entry.path())?.len() as usize;
+            // we need all chunks addresses not just the data-map addr.
+            // The chunks are streamed into temp files for the user
+            // to be able to upload them to the network after making the payment,
+            // without needing to chunk the files again, nor holding them in memory.
+            let (file_addr, chunks_paths) = file_api.chunk_file(entry.path(), chunks_dir).await?;
+            num_of_chunks += chunks_paths.len();
 
             chunked_files.insert(
                 file_addr,
                 ChunkedFile {
                     file_name,
-                    size: bytes.len(),
+                    size,
                     chunks: chunks_paths,
                 },
             );
@@ -302,15 +601,20 @@ pub(super) async fn chunk_and_pay_for_storage(
         chunked_files.len()
     );
 
-    let (proofs, cost) = wallet_client
-        .pay_for_storage(
-            chunked_files
-                .values()
-                .flat_map(|chunked_file| &chunked_file.chunks)
-                .map(|(name, _)| name),
-            verify_store,
-        )
-        .await?;
+    Ok(chunked_files)
+}
+
+/// Pays for the storage of the given chunks with the local wallet,
+/// storing the wallet with the resulting payment proofs.
+pub(super) async fn pay_for_chunks(
+    client: &Client,
+    root_dir: &Path,
+    chunks: impl Iterator<Item = &XorName>,
+    verify_store: bool,
+) -> Result<PaymentProofsMap> {
+    let mut wallet_client = load_wallet_client(client, root_dir).await?;
+
+    let (proofs, cost) = wallet_client.pay_for_storage(chunks, verify_store).await?;
 
     if let Some(cost) = cost {
         let total_cost = proofs.len() as u64 * cost.as_nano();
@@ -322,7 +626,7 @@ pub(super) async fn chunk_and_pay_for_storage(
         println!("No payment needed for {} records.", proofs.len(),);
     }
 
-    let wallet = wallet_client.into_wallet();
+    let mut wallet = wallet_client.into_wallet();
     if let Err(err) = wallet.store().await {
         println!("Failed to store wallet: {err:?}");
     } else {
@@ -333,5 +637,42 @@ pub(super) async fn chunk_and_pay_for_storage(
     }
 
     println!("Successfully paid for storage and generated the proofs. They can now be sent to the storage nodes when uploading paid chunks.");
-    Ok((chunked_files, proofs))
+    Ok(proofs)
+}
+
+/// Creates the wallet client, reporting what became of the transfers left unconfirmed
+/// by previous runs, which it reconciles with the network.
+async fn new_wallet_client(client: &Client, wallet: LocalWallet) -> WalletClient {
+    let wallet_client = WalletClient::new(client.clone(), wallet).await;
+    let reconciled = wallet_client.reconciled_txs();
+    for tx_hash in &reconciled.confirmed {
+        println!("Tx {} was confirmed by the network.", tx_hash.to_hex());
+    }
+    for tx_hash in &reconciled.republished {
+        println!("Tx {} was successfully republished!", tx_hash.to_hex());
+    }
+    for tx_hash in &reconciled.rolled_back {
+        println!(
+            "Tx {} can't be completed, as some of its inputs were spent in another tx. It was rolled back.",
+            tx_hash.to_hex()
+        );
+    }
+    for tx_hash in &reconciled.unconfirmed {
+        println!(
+            "Tx {} is still unconfirmed. It will be retried later.",
+            tx_hash.to_hex()
+        );
+    }
+    wallet_client
+}
+
+/// Loads the local wallet at `root_dir`, for the client to pay with.
+pub(super) async fn load_wallet_client(client: &Client, root_dir: &Path) -> Result<WalletClient> {
+    let wallet = LocalWallet::load_from(root_dir)
+        .await
+        .wrap_err("Unable to read wallet file in {path:?}")
+        .suggestion(
+            "If you have an old wallet file, it may no longer be compatible. Try removing it",
+        )?;
+    Ok(new_wallet_client(client, wallet).await)
 }
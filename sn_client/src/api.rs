@@ -8,26 +8,27 @@
 
 use super::{
     error::{Error, Result},
-    Client, ClientEvent, ClientEventsChannel, ClientEventsReceiver, ClientRegister,
+    Client, ClientEvent, ClientEventsChannel, ClientEventsReceiver, ClientRegister, WalletClient,
 };
 
 use bls::{PublicKey, SecretKey, Signature};
 use indicatif::ProgressBar;
 use libp2p::{kad::Record, Multiaddr};
 use sn_dbc::{DbcId, SignedSpend, Token};
-use sn_networking::{multiaddr_is_global, NetworkEvent, SwarmDriver, CLOSE_GROUP_SIZE};
+use sn_networking::{multiaddr_is_global, NetworkConfig, NetworkEvent, SwarmDriver};
+use sn_peers_acquisition::PeerCacheUpdater;
 use sn_protocol::{
     error::Error as ProtocolError,
-    messages::PaymentProof,
+    messages::{PaymentProof, StoreCostQuote},
     storage::{
         try_deserialize_record, try_serialize_record, Chunk, ChunkAddress, ChunkWithPayment,
-        DbcAddress, RecordHeader, RecordKind, RegisterAddress,
+        DbcAddress, RecordHeader, RecordKind, RegisterAddress, RegisterWithPayment,
     },
     NetworkAddress, PrettyPrintRecordKey,
 };
 use sn_registers::SignedRegister;
 use sn_transfers::client_transfers::SpendRequest;
-use std::time::Duration;
+use std::{collections::BTreeSet, path::PathBuf, time::Duration};
 use tokio::task::spawn;
 use tracing::trace;
 use xor_name::XorName;
@@ -37,10 +38,16 @@ const INACTIVITY_TIMEOUT: std::time::Duration = tokio::time::Duration::from_secs
 
 impl Client {
     /// Instantiate a new client.
+    ///
+    /// If a `peer_cache_dir` is provided, the peers added to the routing table are cached there,
+    /// for later sessions to bootstrap from. The `network_config` is to match the parameters
+    /// of the network joined, whose nodes refuse clients with other ones.
     pub async fn new(
         signer: SecretKey,
         peers: Option<Vec<Multiaddr>>,
         req_response_timeout: Option<Duration>,
+        peer_cache_dir: Option<PathBuf>,
+        network_config: NetworkConfig,
     ) -> Result<Self> {
         // If any of our contact peers has a global address, we'll assume we're in a global network.
         let local = match peers {
@@ -52,7 +59,7 @@ impl Client {
         info!("Starting Kad swarm in client mode...");
 
         let (network, mut network_event_receiver, swarm_driver) =
-            SwarmDriver::new_client(local, req_response_timeout)?;
+            SwarmDriver::new_client(local, req_response_timeout, network_config)?;
         info!("Client constructed network and swarm_driver");
         let events_channel = ClientEventsChannel::default();
 
@@ -91,6 +98,7 @@ impl Client {
 
         // spawn task to wait for NetworkEvent and check for inactivity
         let mut client_clone = client.clone();
+        let peer_cache = peer_cache_dir.map(|dir| PeerCacheUpdater::spawn(&dir));
         let _event_handler = spawn(async move {
             loop {
                 match tokio::time::timeout(INACTIVITY_TIMEOUT, network_event_receiver.recv()).await
@@ -103,6 +111,9 @@ impl Client {
                                 continue;
                             }
                         };
+                        if let Some(peer_cache) = &peer_cache {
+                            update_peer_cache(peer_cache, &the_event);
+                        }
                         if let Err(err) = client_clone.handle_network_event(the_event) {
                             warn!("Error handling network event: {err}");
                         }
@@ -172,7 +183,7 @@ impl Client {
     }
 
     fn handle_network_event(&mut self, event: NetworkEvent) -> Result<()> {
-        if let NetworkEvent::PeerAdded(peer_id) = event {
+        if let NetworkEvent::PeerAdded(peer_id, _) = event {
             self.peers_added += 1;
             debug!("PeerAdded: {peer_id}");
 
@@ -180,7 +191,8 @@ impl Client {
             // it may take some time to fill up the RT.
             // To avoid such delay may fail the query with RecordNotFound,
             // wait till certain amount of peers populated into RT
-            if self.peers_added >= CLOSE_GROUP_SIZE {
+            let close_group_size = self.network.network_config.close_group_size;
+            if self.peers_added >= close_group_size {
                 if let Some(progress) = &self.progress {
                     progress.finish_with_message("Connected to the Network");
                     // Remove the progress bar
@@ -191,14 +203,14 @@ impl Client {
                     .broadcast(ClientEvent::ConnectedToNetwork)?;
             } else {
                 debug!(
-                    "{}/{} initial peers found.",
-                    self.peers_added, CLOSE_GROUP_SIZE
+                    "{}/{close_group_size} initial peers found.",
+                    self.peers_added
                 );
 
                 if let Some(progress) = &self.progress {
                     progress.set_message(format!(
-                        "{}/{} initial peers found.",
-                        self.peers_added, CLOSE_GROUP_SIZE
+                        "{}/{close_group_size} initial peers found.",
+                        self.peers_added
                     ));
                 }
             }
@@ -227,6 +239,17 @@ impl Client {
         &self,
         address: RegisterAddress,
     ) -> Result<SignedRegister> {
+        Ok(self
+            .get_register_with_payment_from_network(address)
+            .await?
+            .register)
+    }
+
+    /// Get a register from network, along with the payment made for its creation
+    pub(crate) async fn get_register_with_payment_from_network(
+        &self,
+        address: RegisterAddress,
+    ) -> Result<RegisterWithPayment> {
         let key = NetworkAddress::from_register_address(address).to_record_key();
 
         let record = self
@@ -242,7 +265,7 @@ impl Client {
             .map_err(|_| ProtocolError::RegisterNotFound(Box::new(address)))?;
 
         if let RecordKind::Register = header.kind {
-            let register = try_deserialize_record::<SignedRegister>(&record)
+            let register = try_deserialize_record::<RegisterWithPayment>(&record)
                 .map_err(|_| ProtocolError::RegisterNotFound(Box::new(address)))?;
             Ok(register)
         } else {
@@ -259,14 +282,15 @@ impl Client {
         ClientRegister::retrieve(self.clone(), address).await
     }
 
-    /// Create a new Register on the Network.
+    /// Create a new Register on the Network, paying for it with the given wallet.
     pub async fn create_register(
         &self,
         meta: XorName,
+        wallet_client: &mut WalletClient,
         verify_store: bool,
     ) -> Result<ClientRegister> {
         info!("Instantiating a new Register replica with meta {meta:?}");
-        ClientRegister::create_online(self.clone(), meta, verify_store).await
+        ClientRegister::create_online(self.clone(), meta, wallet_client, verify_store).await
     }
 
     /// Store `Chunk` as a record.
@@ -329,25 +353,67 @@ impl Client {
 
     /// Get a dbc spend from network
     pub async fn get_spend_from_network(&self, dbc_id: &DbcId) -> Result<SignedSpend> {
+        self.get_spend_at_address(DbcAddress::from_dbc_id(dbc_id))
+            .await
+    }
+
+    /// Get all the spends of a dbc found in the network, to tell whether it has been spent.
+    /// None are found for an unspent dbc, while more than one are evidence of a double spend.
+    ///
+    /// Unlike `get_spend_from_network`, which fails on anything but a single valid spend,
+    /// this tells unspent, spent and double spent dbcs apart. The record is only looked up
+    /// once, as it is expected to be missing for unspent dbcs, while the holders returning
+    /// differing copies of it is what a double spend across them looks like.
+    /// Failing to reach the holders in time is an error, rather than the dbc being unspent.
+    pub async fn get_spends_from_network(&self, dbc_id: &DbcId) -> Result<Vec<SignedSpend>> {
         let address = DbcAddress::from_dbc_id(dbc_id);
         let key = NetworkAddress::from_dbc_address(address).to_record_key();
 
-        trace!("Getting spend {dbc_id:?} with record_key {key:?}");
+        trace!("Getting all spends of {dbc_id:?} with record_key {key:?}");
+        let records = self.network.get_record_copies_from_network(key).await?;
+
+        let mut signed_spends = BTreeSet::new();
+        for record in records {
+            let header = RecordHeader::from_record(&record)?;
+            if header.kind != RecordKind::DbcSpend {
+                error!("RecordKind mismatch while trying to retrieve the spends of {dbc_id:?}");
+                return Err(ProtocolError::RecordKindMismatch(RecordKind::DbcSpend).into());
+            }
+
+            // Only the valid spends of this very dbc are evidence of it being spent.
+            signed_spends.extend(
+                try_deserialize_record::<Vec<SignedSpend>>(&record)?
+                    .into_iter()
+                    .filter(|signed_spend| {
+                        signed_spend.dbc_id() == dbc_id
+                            && signed_spend.verify(signed_spend.spent_tx_hash()).is_ok()
+                    }),
+            );
+        }
+        Ok(signed_spends.into_iter().collect())
+    }
+
+    /// Get the dbc spend at the given address from network,
+    /// e.g. the parent spend of a utxo we received.
+    pub async fn get_spend_at_address(&self, address: DbcAddress) -> Result<SignedSpend> {
+        let key = NetworkAddress::from_dbc_address(address).to_record_key();
+
+        trace!("Getting spend at {address:?} with record_key {key:?}");
         let record = self
             .network
             .get_record_from_network(key.clone(), None, true)
             .await
             .map_err(|err| {
-                Error::CouldNotVerifyTransfer(format!("dbc_id {dbc_id:?} errored: {err:?}"))
+                Error::CouldNotVerifyTransfer(format!("dbc at {address:?} errored: {err:?}"))
             })?;
         debug!(
-            "For spend {dbc_id:?} got record from the network, {:?}",
+            "For spend at {address:?} got record from the network, {:?}",
             PrettyPrintRecordKey::from(record.key.clone())
         );
 
         let header = RecordHeader::from_record(&record).map_err(|err| {
             Error::CouldNotVerifyTransfer(format!(
-                "Can't parse RecordHeader for the dbc_id {dbc_id:?} with error {err:?}"
+                "Can't parse RecordHeader for the dbc at {address:?} with error {err:?}"
             ))
         })?;
 
@@ -355,7 +421,7 @@ impl Client {
             match try_deserialize_record::<Vec<SignedSpend>>(&record)
                 .map_err(|err| {
                     Error::CouldNotVerifyTransfer(format!(
-                        "Can't deserialize record for the dbc_id {dbc_id:?} with error {err:?}"
+                        "Can't deserialize record for the dbc at {address:?} with error {err:?}"
                     ))
                 })?
                 .as_slice()
@@ -363,34 +429,34 @@ impl Client {
                 [one, two, ..] => {
                     error!("Found double spend for {address:?}");
                     Err(Error::CouldNotVerifyTransfer(format!(
-                "Found double spend for the dbc_id {dbc_id:?} - {:?}: spend_one {:?} and spend_two {:?}",
+                "Found double spend for the dbc at {address:?} - {:?}: spend_one {:?} and spend_two {:?}",
                 PrettyPrintRecordKey::from(key), one.derived_key_sig, two.derived_key_sig
             )))
                 }
                 [signed_spend] => {
                     trace!("Spend get for address: {address:?} successful");
-                    if dbc_id == signed_spend.dbc_id() {
+                    if address == DbcAddress::from_dbc_id(signed_spend.dbc_id()) {
                         match signed_spend.verify(signed_spend.spent_tx_hash()) {
                             Ok(_) => {
-                                trace!("Verified signed spend got from networkfor {dbc_id:?}");
+                                trace!("Verified signed spend got from networkfor {address:?}");
                                 Ok(signed_spend.clone())
                             }
                             Err(err) => {
-                                warn!("Invalid signed spend got from network for {dbc_id:?}: {err:?}.");
+                                warn!("Invalid signed spend got from network for {address:?}: {err:?}.");
                                 Err(Error::CouldNotVerifyTransfer(format!(
-                                "Spend failed verifiation for the dbc_id {dbc_id:?} with error {err:?}")))
+                                "Spend failed verifiation for the dbc at {address:?} with error {err:?}")))
                             }
                         }
                     } else {
-                        warn!("Signed spend ({:?}) got from network mismatched the expected one {dbc_id:?}.", signed_spend.dbc_id());
+                        warn!("Signed spend ({:?}) got from network mismatched the expected one at {address:?}.", signed_spend.dbc_id());
                         Err(Error::CouldNotVerifyTransfer(format!(
-                                "Signed spend ({:?}) got from network mismatched the expected one {dbc_id:?}.", signed_spend.dbc_id())))
+                                "Signed spend ({:?}) got from network mismatched the expected one at {address:?}.", signed_spend.dbc_id())))
                     }
                 }
                 _ => {
                     trace!("Found no spend for {address:?}");
                     Err(Error::CouldNotVerifyTransfer(format!(
-                        "Fetched record shows no spend for dbc {dbc_id:?}."
+                        "Fetched record shows no spend for dbc at {address:?}."
                     )))
                 }
             }
@@ -416,6 +482,7 @@ impl Client {
             .network
             .get_store_cost_from_network(address.clone(), any_cost_will_do)
             .await?
+            .0
             .as_nano();
 
         if cost > self.network_store_cost {
@@ -430,4 +497,27 @@ impl Client {
 
         Ok(Token::from_nano(cost))
     }
+
+    /// Get the store cost at a given address, along with the quotes of it
+    /// signed by the nodes closest to the address.
+    pub async fn get_store_cost_quotes(
+        &self,
+        address: NetworkAddress,
+    ) -> Result<(Token, Vec<StoreCostQuote>)> {
+        trace!("Getting store cost quotes at {address:?}");
+        let (cost, quotes) = self
+            .network
+            .get_store_cost_from_network(address, false)
+            .await?;
+        Ok((cost, quotes))
+    }
+}
+
+// Keeps the peer cache up to date with the routing table.
+fn update_peer_cache(peer_cache: &PeerCacheUpdater, event: &NetworkEvent) {
+    match event {
+        NetworkEvent::PeerAdded(peer_id, addrs) => peer_cache.peer_added(*peer_id, addrs.clone()),
+        NetworkEvent::PeerRemoved(peer_id) => peer_cache.peer_removed(*peer_id),
+        _ => {}
+    }
 }
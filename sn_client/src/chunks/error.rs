@@ -55,6 +55,9 @@ pub enum Error {
         missing_chunks: Vec<XorName>,
     },
 
+    #[error("Could not decrypt the private data map with the client's key.")]
+    PrivateDataMapDecryption,
+
     #[error("Not all data was chunked, expected {expected}, but we have {chunked}.)")]
     NotAllDataWasChunked {
         /// Number of Chunks expected to be generated
@@ -10,7 +10,7 @@ mod error;
 mod pac_man;
 
 pub(crate) use self::error::{Error, Result};
-pub(crate) use pac_man::{encrypt_large, to_chunk, DataMapLevel};
+pub(crate) use pac_man::{encrypt_from_path, encrypt_large, seal, to_chunk, unseal, DataMapLevel};
 
 use bytes::Bytes;
 use self_encryption::MIN_ENCRYPTABLE_BYTES;
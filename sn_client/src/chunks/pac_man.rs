@@ -9,12 +9,16 @@
 use super::{Error, Result};
 
 use bincode::serialize;
+use bls::{Ciphertext, PublicKey, SecretKey};
 use bytes::Bytes;
 use rayon::prelude::*;
-use self_encryption::{DataMap, EncryptedChunk, MAX_CHUNK_SIZE};
+use self_encryption::{DataMap, EncryptedChunk, StreamSelfEncryptor, MAX_CHUNK_SIZE};
 use serde::{Deserialize, Serialize};
 use sn_protocol::storage::Chunk;
-use std::path::Path;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use xor_name::XorName;
 
 #[derive(Serialize, Deserialize)]
@@ -25,27 +29,89 @@ pub(crate) enum DataMapLevel {
     // resulting from chunking up a previous level data map.
     // This happens when that previous level data map was too big to fit in a chunk itself.
     Additional(DataMap),
+    // Holds the top-most `DataMapLevel` of private data (or the content of a private small file),
+    // encrypted to the public key of its owner, so that only the owner can find and decrypt the data.
+    Encrypted(Box<Ciphertext>),
 }
 
-#[allow(unused)]
-pub(crate) fn encrypt_from_path(path: &Path) -> Result<(XorName, Vec<Chunk>)> {
-    let (data_map, encrypted_chunks) = encrypt_file(path)?;
-    pack(data_map, encrypted_chunks)
+/// Self-encrypts the file at `path` one chunk at a time, writing every resulting chunk
+/// (including the `DataMapLevel` ones) into `output_dir` instead of keeping them in memory.
+/// Returns the top-most chunk address, and the names and paths of all the chunks written.
+/// If an owner is provided, the top-most `DataMapLevel` is encrypted to it.
+pub(crate) fn encrypt_from_path(
+    path: &Path,
+    output_dir: &Path,
+    owner: Option<PublicKey>,
+) -> Result<(XorName, Vec<(XorName, PathBuf)>)> {
+    let mut encryptor = StreamSelfEncryptor::encrypt_from_file(
+        Box::new(path.to_path_buf()),
+        Some(Box::new(output_dir.to_path_buf())),
+    )?;
+
+    let mut chunks_paths = vec![];
+    let data_map = loop {
+        match encryptor.next_encryption()? {
+            (Some(encrypted_chunk), _) => {
+                // The encryptor has already flushed the chunk into `output_dir`, named by its hex address.
+                let name = XorName::from_content(&encrypted_chunk.content);
+                chunks_paths.push((name, output_dir.join(hex::encode(name))));
+            }
+            (None, Some(data_map)) => break data_map,
+            (None, None) => return Err(Error::EmptyFileProvided),
+        }
+    };
+
+    let expected_total = data_map.infos().len();
+    if expected_total > chunks_paths.len() {
+        return Err(Error::NotAllDataWasChunked {
+            expected: expected_total,
+            chunked: chunks_paths.len(),
+        });
+    }
+
+    let (address, data_map_chunks) = pack(data_map, owner)?;
+    for chunk in data_map_chunks {
+        let name = *chunk.name();
+        let path = output_dir.join(hex::encode(name));
+        fs::write(&path, chunk.value())?;
+        chunks_paths.push((name, path));
+    }
+
+    Ok((address, chunks_paths))
 }
 
-pub(crate) fn encrypt_large(data: Bytes) -> Result<(XorName, Vec<Chunk>)> {
+pub(crate) fn encrypt_large(
+    data: Bytes,
+    owner: Option<PublicKey>,
+) -> Result<(XorName, Vec<Chunk>)> {
     let (data_map, encrypted_chunks) = encrypt_data(data)?;
-    pack(data_map, encrypted_chunks)
+    let (address, additional_chunks) = pack(data_map, owner)?;
+
+    let expected_total = encrypted_chunks.len() + additional_chunks.len();
+    let all_chunks: Vec<_> = encrypted_chunks
+        .par_iter()
+        .map(|c| to_chunk(c.content.clone())) // no need to encrypt what is self-encrypted
+        .chain(additional_chunks)
+        .collect();
+
+    if expected_total > all_chunks.len() {
+        // as we flatten above, we need to check outcome here
+        return Err(Error::NotAllDataWasChunked {
+            expected: expected_total,
+            chunked: all_chunks.len(),
+        });
+    }
+
+    Ok((address, all_chunks))
 }
 
 /// Returns the top-most chunk address through which the entire
-/// data tree can be accessed, and all the other encrypted chunks.
-/// If encryption is provided, the additional `DataMapLevel` chunks are encrypted with it.
+/// data tree can be accessed, and the `DataMapLevel` chunks leading to the given `DataMap`.
+/// The encrypted chunks the `DataMap` points to are not part of the returned chunks.
+/// If an owner is provided, the top-most `DataMapLevel` chunk is encrypted to it.
 /// This is necessary if the data is meant to be private, since a `DataMap` is used to find and decrypt the original file.
-pub(crate) fn pack(
-    data_map: DataMap,
-    encrypted_chunks: Vec<EncryptedChunk>,
-) -> Result<(XorName, Vec<Chunk>)> {
+/// The lower `DataMapLevel`s need no such encryption, as they are self-encrypted and only reachable from the top-most one.
+pub(crate) fn pack(data_map: DataMap, owner: Option<PublicKey>) -> Result<(XorName, Vec<Chunk>)> {
     // Produces a chunk out of the first `DataMap`, which is validated for its size.
     // If the chunk is too big, it is self-encrypted and the resulting (additional level) `DataMap` is put into a chunk.
     // The above step is repeated as many times as required until the chunk size is valid.
@@ -55,15 +121,19 @@ pub(crate) fn pack(
     let mut chunks = vec![];
     let mut chunk_content = pack_data_map(DataMapLevel::First(data_map))?;
 
-    let (address, additional_chunks) = loop {
+    loop {
         let chunk = to_chunk(chunk_content);
+        let head_chunk = match owner {
+            Some(owner) => to_chunk(seal(chunk.value(), owner)?),
+            None => chunk.clone(),
+        };
         // If datamap chunk is less than `MAX_CHUNK_SIZE` return it so it can be directly sent to the network.
-        if MAX_CHUNK_SIZE >= chunk.serialised_size() {
-            let name = *chunk.name();
+        if MAX_CHUNK_SIZE >= head_chunk.serialised_size() {
+            let name = *head_chunk.name();
             chunks.reverse();
-            chunks.push(chunk);
+            chunks.push(head_chunk);
             // Returns the address of the last datamap, and all the chunks produced.
-            break (name, chunks);
+            break Ok((name, chunks));
         } else {
             let serialized_chunk = Bytes::from(serialize(&chunk)?);
             let (data_map, next_encrypted_chunks) = self_encryption::encrypt(serialized_chunk)?;
@@ -74,41 +144,112 @@ pub(crate) fn pack(
                 .collect();
             chunk_content = pack_data_map(DataMapLevel::Additional(data_map))?;
         }
-    };
-
-    let expected_total = encrypted_chunks.len() + additional_chunks.len();
-    let all_chunks: Vec<_> = encrypted_chunks
-        .par_iter()
-        .map(|c| to_chunk(c.content.clone())) // no need to encrypt what is self-encrypted
-        .chain(additional_chunks)
-        .collect();
-
-    if expected_total > all_chunks.len() {
-        // as we flatten above, we need to check outcome here
-        return Err(Error::NotAllDataWasChunked {
-            expected: expected_total,
-            chunked: all_chunks.len(),
-        });
     }
-
-    Ok((address, all_chunks))
 }
 
 pub(crate) fn to_chunk(chunk_content: Bytes) -> Chunk {
     Chunk::new(chunk_content)
 }
 
+/// Encrypts the content of a chunk to the given owner, packed as a `DataMapLevel::Encrypted`.
+/// The content is prefixed with its hash, so that decrypting it with any other key can be detected.
+pub(crate) fn seal(content: &Bytes, owner: PublicKey) -> Result<Bytes> {
+    let mut plaintext = XorName::from_content(content).0.to_vec();
+    plaintext.extend_from_slice(content);
+    pack_data_map(DataMapLevel::Encrypted(Box::new(owner.encrypt(plaintext))))
+}
+
+/// Decrypts the content sealed to the owner of the given secret key.
+pub(crate) fn unseal(ciphertext: &Ciphertext, owner: &SecretKey) -> Result<Bytes> {
+    let mut plaintext = Bytes::from(
+        owner
+            .decrypt(ciphertext)
+            .ok_or(Error::PrivateDataMapDecryption)?,
+    );
+    if plaintext.len() < xor_name::XOR_NAME_LEN {
+        return Err(Error::PrivateDataMapDecryption);
+    }
+    let content = plaintext.split_off(xor_name::XOR_NAME_LEN);
+    if plaintext[..] != XorName::from_content(&content).0[..] {
+        return Err(Error::PrivateDataMapDecryption);
+    }
+    Ok(content)
+}
+
 fn pack_data_map(data_map: DataMapLevel) -> Result<Bytes> {
     Ok(Bytes::from(serialize(&data_map)?))
 }
 
-fn encrypt_file(file: &Path) -> Result<(DataMap, Vec<EncryptedChunk>)> {
-    let bytes = Bytes::from(std::fs::read(file)?);
+fn encrypt_data(bytes: Bytes) -> Result<(DataMap, Vec<EncryptedChunk>)> {
     let encrypted_chunk = self_encryption::encrypt(bytes)?;
     Ok(encrypted_chunk)
 }
 
-fn encrypt_data(bytes: Bytes) -> Result<(DataMap, Vec<EncryptedChunk>)> {
-    let encrypted_chunk = self_encryption::encrypt(bytes)?;
-    Ok(encrypted_chunk)
+#[cfg(test)]
+mod tests {
+    use super::{encrypt_data, encrypt_from_path, encrypt_large, pack, unseal, DataMapLevel};
+
+    use assert_fs::TempDir;
+    use bincode::deserialize;
+    use bls::SecretKey;
+    use bytes::Bytes;
+    use eyre::{eyre, Result};
+    use rand::{thread_rng, Rng};
+    use self_encryption::MAX_CHUNK_SIZE;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn encrypt_from_path_matches_in_memory_encryption() -> Result<()> {
+        let mut rng = thread_rng();
+        let data: Vec<u8> = (0..(5 * MAX_CHUNK_SIZE + 123)).map(|_| rng.gen()).collect();
+
+        let dir = TempDir::new()?;
+        let file_path = dir.path().join("file");
+        std::fs::write(&file_path, &data)?;
+        let chunks_dir = dir.path().join("chunks");
+        std::fs::create_dir(&chunks_dir)?;
+
+        let (streamed_address, chunks_paths) = encrypt_from_path(&file_path, &chunks_dir, None)?;
+        let (address, chunks) = encrypt_large(Bytes::from(data), None)?;
+
+        assert_eq!(streamed_address, address);
+        let streamed_names: BTreeSet<_> = chunks_paths.iter().map(|(name, _)| *name).collect();
+        let names: BTreeSet<_> = chunks.iter().map(|chunk| *chunk.name()).collect();
+        assert_eq!(streamed_names, names);
+
+        for (name, path) in chunks_paths {
+            let content = std::fs::read(path)?;
+            assert_eq!(xor_name::XorName::from_content(&content), name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn private_head_chunk_is_only_readable_by_owner() -> Result<()> {
+        let mut rng = thread_rng();
+        let data: Vec<u8> = (0..(3 * MAX_CHUNK_SIZE)).map(|_| rng.gen()).collect();
+        let (data_map, _) = encrypt_data(Bytes::from(data))?;
+
+        let owner = SecretKey::random();
+        let (address, chunks) = pack(data_map.clone(), Some(owner.public_key()))?;
+        let head_chunk = chunks
+            .iter()
+            .find(|chunk| *chunk.name() == address)
+            .ok_or_else(|| eyre!("The head chunk should be part of the packed chunks"))?;
+
+        let ciphertext = match deserialize(head_chunk.value())? {
+            DataMapLevel::Encrypted(ciphertext) => ciphertext,
+            _ => return Err(eyre!("The head chunk should be encrypted")),
+        };
+        assert!(unseal(&ciphertext, &SecretKey::random()).is_err());
+
+        let decrypted = unseal(&ciphertext, &owner)?;
+        match deserialize(&decrypted)? {
+            DataMapLevel::First(decrypted_data_map) => assert!(decrypted_data_map == data_map),
+            _ => return Err(eyre!("The head chunk should hold the first level data map")),
+        }
+
+        Ok(())
+    }
 }
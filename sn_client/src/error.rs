@@ -10,7 +10,7 @@ pub(crate) type Result<T> = std::result::Result<T, Error>;
 
 use super::ClientEvent;
 
-use sn_protocol::storage::ChunkAddress;
+use sn_protocol::storage::{ChunkAddress, RegisterAddress};
 use sn_registers::{Entry, EntryHash};
 
 use std::collections::BTreeSet;
@@ -60,6 +60,15 @@ pub enum Error {
     #[error("Missing a payment proof for address {0:?}")]
     MissingPaymentProof(ChunkAddress),
 
+    #[error("Missing a payment proof for creating the Register at {0}")]
+    MissingRegisterPaymentProof(Box<RegisterAddress>),
+
+    #[error("The folder manifest entry path {0:?} is not a valid path relative to the folder.")]
+    InvalidFolderEntryPath(String),
+
+    #[error("The Register at {0} does not point to a folder manifest.")]
+    InvalidFolderPointer(Box<RegisterAddress>),
+
     /// A general error when a transfer fails.
     #[error("Failed to send tokens due to {0}")]
     CouldNotSendTokens(String),
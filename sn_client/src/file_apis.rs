@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    chunks::{to_chunk, DataMapLevel, Error, SmallFile},
+    chunks::{encrypt_from_path, seal, to_chunk, unseal, DataMapLevel, Error, SmallFile},
     error::Result,
     Client,
 };
@@ -16,11 +16,20 @@ use sn_protocol::storage::{Chunk, ChunkAddress};
 use sn_transfers::wallet::PaymentProofsMap;
 
 use bincode::deserialize;
+use bls::PublicKey;
 use bytes::Bytes;
-use futures::future::join_all;
+use futures::{
+    future::join_all,
+    stream::{self, Stream, StreamExt, TryStreamExt},
+};
 use itertools::Itertools;
 use self_encryption::{self, ChunkInfo, DataMap, EncryptedChunk, MIN_ENCRYPTABLE_BYTES};
-use tokio::task::{self, JoinHandle};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncWriteExt},
+    task::{self, JoinHandle},
+};
 use tracing::trace;
 use xor_name::XorName;
 
@@ -28,14 +37,39 @@ use xor_name::XorName;
 const CHUNKS_BATCH_MAX_SIZE: usize = 4;
 
 /// File APIs.
+#[derive(Clone)]
 pub struct Files {
-    client: Client,
+    pub(crate) client: Client,
+    private: bool,
+}
+
+// The content of a file's head chunk, once unpacked.
+enum Unpacked {
+    // A LargeFile, to be read through its data map.
+    DataMap(DataMap),
+    // A SmallFile, with its content.
+    SmallFile(Bytes),
 }
 
 impl Files {
     /// Create file apis instance.
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            private: false,
+        }
+    }
+
+    /// Create file apis instance which chunks and uploads files privately.
+    ///
+    /// The top-most data map of each file (or the content of a small file) is encrypted
+    /// to the client's public key, so only this client can read the file back.
+    /// As the encryption is randomised, a private file gets a new address every time it is chunked.
+    pub fn new_private(client: Client) -> Self {
+        Self {
+            client,
+            private: true,
+        }
     }
 
     #[instrument(skip(self), level = "debug")]
@@ -43,12 +77,9 @@ impl Files {
     pub async fn read_bytes(&self, address: ChunkAddress) -> Result<Bytes> {
         let chunk = self.client.get_chunk(address).await?;
 
-        // first try to deserialize a LargeFile, if it works, we go and seek it
-        if let Ok(data_map) = self.unpack_chunk(chunk.clone()).await {
-            self.read_all(data_map).await
-        } else {
-            // if an error occurs, we assume it's a SmallFile
-            Ok(chunk.value().clone())
+        match self.unpack_head_chunk(chunk).await? {
+            Unpacked::DataMap(data_map) => self.read_all(data_map).await,
+            Unpacked::SmallFile(bytes) => Ok(bytes),
         }
     }
 
@@ -73,16 +104,10 @@ impl Files {
         trace!("Reading {length} bytes at: {address:?}, starting from position: {position}");
         let chunk = self.client.get_chunk(address).await?;
 
-        // First try to deserialize a LargeFile, if it works, we go and seek it.
-        // If an error occurs, we consider it to be a SmallFile.
-        if let Ok(data_map) = self.unpack_chunk(chunk.clone()).await {
-            return self.seek(data_map, position, length).await;
-        }
-
-        // The error above is ignored to avoid leaking the storage format detail of SmallFiles and LargeFiles.
-        // The basic idea is that we're trying to deserialize as one, and then the other.
-        // The cost of it is that some errors will not be seen without a refactor.
-        let mut bytes = chunk.value().clone();
+        let mut bytes = match self.unpack_head_chunk(chunk).await? {
+            Unpacked::DataMap(data_map) => return self.seek(data_map, position, length).await,
+            Unpacked::SmallFile(bytes) => bytes,
+        };
 
         let _ = bytes.split_to(position);
         bytes.truncate(length);
@@ -90,6 +115,65 @@ impl Files {
         Ok(bytes)
     }
 
+    /// Reads the file at `address` from the network as a stream of [`Bytes`].
+    ///
+    /// Unlike `read_bytes`, the chunks are fetched and decrypted one batch at a time,
+    /// in order, so only a few chunks are ever held in memory regardless of the file size.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn read_stream(
+        &self,
+        address: ChunkAddress,
+    ) -> Result<impl Stream<Item = Result<Bytes>>> {
+        let chunk = self.client.get_chunk(address).await?;
+
+        let (data_map, infos, small_file) = match self.unpack_head_chunk(chunk).await? {
+            Unpacked::DataMap(data_map) => {
+                let infos = data_map.infos();
+                (Some(data_map), infos, None)
+            }
+            Unpacked::SmallFile(bytes) => (None, vec![], Some(Ok(vec![bytes]))),
+        };
+
+        let batches = infos
+            .chunks(CHUNKS_BATCH_MAX_SIZE)
+            .map(|batch| batch.to_vec())
+            .collect_vec();
+        let files = self.clone();
+        let large_file = stream::iter(batches).then(move |batch| {
+            let files = files.clone();
+            let data_map = data_map.clone();
+            async move {
+                match data_map {
+                    Some(data_map) => files.read_batch(&data_map, batch).await,
+                    None => Ok(vec![]),
+                }
+            }
+        });
+
+        Ok(stream::iter(small_file)
+            .chain(large_file)
+            .map_ok(|batch| stream::iter(batch.into_iter().map(Ok)))
+            .try_flatten())
+    }
+
+    /// Reads the file at `address` from the network, writing it to `path` chunk by chunk.
+    /// Any existing file at `path` is overwritten. Returns the number of bytes written.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn read_to_path(&self, address: ChunkAddress, path: &Path) -> Result<u64> {
+        let stream = self.read_stream(address).await?;
+        futures::pin_mut!(stream);
+
+        let mut file = File::create(path).await.map_err(Error::Io)?;
+        let mut written = 0;
+        while let Some(bytes) = stream.try_next().await? {
+            file.write_all(&bytes).await.map_err(Error::Io)?;
+            written += bytes.len() as u64;
+        }
+        file.flush().await.map_err(Error::Io)?;
+
+        Ok(written)
+    }
+
     /// Directly writes [`Bytes`] to the network in the
     /// form of immutable chunks, without any batching.
     #[instrument(skip(self, bytes), level = "debug")]
@@ -128,13 +212,92 @@ impl Files {
     pub fn chunk_bytes(&self, bytes: Bytes) -> Result<(XorName, Vec<Chunk>)> {
         if bytes.len() < MIN_ENCRYPTABLE_BYTES {
             let file = SmallFile::new(bytes)?;
-            let chunk = package_small(file)?;
+            let chunk = package_small(file, self.owner())?;
             Ok((*chunk.name(), vec![chunk]))
         } else {
-            encrypt_large(bytes)
+            encrypt_large(bytes, self.owner())
         }
     }
 
+    /// Tries to chunk the file at `path`, writing the chunks into `chunks_dir` as they are produced,
+    /// without storing anything to network.
+    /// Returns the data-map address, and the names and paths of all the chunks.
+    #[instrument(skip_all, level = "trace")]
+    pub async fn chunk_file(
+        &self,
+        path: &Path,
+        chunks_dir: &Path,
+    ) -> Result<(XorName, Vec<(XorName, PathBuf)>)> {
+        let path = path.to_path_buf();
+        let chunks_dir = chunks_dir.to_path_buf();
+        let owner = self.owner();
+        // Reading, encrypting and writing out the chunks is blocking work.
+        task::spawn_blocking(move || chunk_file_blocking(&path, &chunks_dir, owner))
+            .await
+            .map_err(|err| Error::Io(err.into()))?
+    }
+
+    /// Tries to chunk the contents of `reader`, writing the chunks into `chunks_dir`,
+    /// without storing anything to network.
+    ///
+    /// The contents are first spooled into `chunks_dir`, as self-encryption needs to seek
+    /// back over the data. The spooled copy is removed once chunked.
+    #[instrument(skip_all, level = "trace")]
+    pub async fn chunk_reader<R: AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+        chunks_dir: &Path,
+    ) -> Result<(XorName, Vec<(XorName, PathBuf)>)> {
+        let spool_path =
+            chunks_dir.join(format!("spool_{}", hex::encode(rand::random::<[u8; 8]>())));
+        let mut spool = File::create(&spool_path).await.map_err(Error::Io)?;
+        let _ = tokio::io::copy(reader, &mut spool)
+            .await
+            .map_err(Error::Io)?;
+        spool.flush().await.map_err(Error::Io)?;
+        drop(spool);
+
+        let chunked = self.chunk_file(&spool_path, chunks_dir).await;
+        if let Err(err) = tokio::fs::remove_file(&spool_path).await {
+            warn!("Failed to remove spooled file {spool_path:?}: {err}");
+        }
+        chunked
+    }
+
+    /// Chunks the file at `path` into `chunks_dir` and uploads the chunks,
+    /// reading each one back from disk only as it is being sent.
+    #[instrument(skip(self, payment_proofs), level = "debug")]
+    pub async fn upload_from_path(
+        &self,
+        path: &Path,
+        chunks_dir: &Path,
+        payment_proofs: &PaymentProofsMap,
+        verify_store: bool,
+    ) -> Result<ChunkAddress> {
+        let (head_address, chunks_paths) = self.chunk_file(path, chunks_dir).await?;
+        self.upload_chunks_from_paths(chunks_paths.into_iter(), payment_proofs, verify_store)
+            .await?;
+        Ok(ChunkAddress::new(head_address))
+    }
+
+    /// Directly writes the chunks stored at the given paths to the network,
+    /// reading each chunk from disk just before its batch is uploaded.
+    /// Fails on the first chunk that cannot be read.
+    #[instrument(skip_all, level = "trace")]
+    pub async fn upload_chunks_from_paths(
+        &self,
+        chunks_paths: impl Iterator<Item = (XorName, PathBuf)>,
+        payment_proofs: &PaymentProofsMap,
+        verify_store: bool,
+    ) -> Result<()> {
+        let chunks = stream::iter(chunks_paths).then(|(_, path)| async move {
+            let bytes = tokio::fs::read(path).await.map_err(Error::Io)?;
+            Ok(to_chunk(Bytes::from(bytes)))
+        });
+        self.upload_in_batches(chunks, payment_proofs, verify_store)
+            .await
+    }
+
     /// Directly writes Chunks to the network in the
     /// form of immutable self encrypted chunks.
     #[instrument(skip_all, level = "trace")]
@@ -143,11 +306,34 @@ impl Files {
         chunks: impl Iterator<Item = Chunk>,
         payment_proofs: &PaymentProofsMap,
         verify_store: bool,
+    ) -> Result<()> {
+        self.upload_in_batches(stream::iter(chunks.map(Ok)), payment_proofs, verify_store)
+            .await
+    }
+
+    // --------------------------------------------
+    // ---------- Private helpers -----------------
+    // --------------------------------------------
+
+    // The key private files are encrypted to, if this instance uploads privately.
+    fn owner(&self) -> Option<PublicKey> {
+        self.private.then(|| self.client.signer_pk())
+    }
+
+    // Uploads chunks in batches of `CHUNKS_BATCH_MAX_SIZE`.
+    // Chunks are only pulled from the stream as the previous batch completes.
+    async fn upload_in_batches(
+        &self,
+        chunks: impl Stream<Item = Result<Chunk>>,
+        payment_proofs: &PaymentProofsMap,
+        verify_store: bool,
     ) -> Result<()> {
         trace!("Client upload in batches started");
+        let mut chunks = std::pin::pin!(chunks);
         let mut tasks = vec![];
         let mut next_batch_size = 0;
-        for chunk in chunks {
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
             next_batch_size += 1;
             let client = self.client.clone();
             let chunk_addr = *chunk.address();
@@ -182,10 +368,6 @@ impl Files {
         Ok(())
     }
 
-    // --------------------------------------------
-    // ---------- Private helpers -----------------
-    // --------------------------------------------
-
     #[instrument(skip(self, bytes), level = "trace")]
     async fn upload_bytes(
         &self,
@@ -197,7 +379,7 @@ impl Files {
             let file = SmallFile::new(bytes)?;
             self.upload_small(file, payment_proofs, verify).await
         } else {
-            let (head_address, chunks) = encrypt_large(bytes)?;
+            let (head_address, chunks) = encrypt_large(bytes, self.owner())?;
             self.upload_chunks_in_batches(chunks.into_iter(), payment_proofs, verify)
                 .await?;
             Ok(ChunkAddress::new(head_address))
@@ -213,7 +395,7 @@ impl Files {
         payment_proofs: &PaymentProofsMap,
         verify_store: bool,
     ) -> Result<ChunkAddress> {
-        let chunk = package_small(small)?;
+        let chunk = package_small(small, self.owner())?;
         let address = *chunk.address();
         let payment = payment_proofs
             .get(address.xorname())
@@ -236,6 +418,53 @@ impl Files {
         Ok(bytes)
     }
 
+    // Gets and decrypts the given batch of chunks, returning the raw data of each one, in order.
+    async fn read_batch(&self, data_map: &DataMap, batch: Vec<ChunkInfo>) -> Result<Vec<Bytes>> {
+        let encrypted_chunks = self.try_get_chunks(batch.clone()).await?;
+
+        batch
+            .iter()
+            .map(|info| {
+                let encrypted_chunk = encrypted_chunks
+                    .iter()
+                    .find(|chunk| chunk.index == info.index)
+                    .ok_or(Error::NotEnoughChunksRetrieved {
+                        expected: batch.len(),
+                        retrieved: encrypted_chunks.len(),
+                        missing_chunks: vec![info.dst_hash],
+                    })?;
+                // Each chunk can be decrypted on its own, as the keys only depend on the data map.
+                let bytes = self_encryption::decrypt_range(
+                    data_map,
+                    std::slice::from_ref(encrypted_chunk),
+                    0,
+                    info.src_size,
+                )
+                .map_err(Error::SelfEncryption)?;
+                Ok(bytes)
+            })
+            .collect()
+    }
+
+    /// Unpacks the head chunk of a file, decrypting it first if the file is private.
+    #[instrument(skip_all, level = "trace")]
+    async fn unpack_head_chunk(&self, chunk: Chunk) -> Result<Unpacked> {
+        let mut content = chunk.value().clone();
+        if let Ok(DataMapLevel::Encrypted(ciphertext)) = deserialize(&content) {
+            content = unseal(&ciphertext, &self.client.signer)?;
+        }
+
+        // First try to deserialize a LargeFile, if it works, we go and read it.
+        // If an error occurs, we consider it to be a SmallFile.
+        // The error is ignored to avoid leaking the storage format detail of SmallFiles and LargeFiles.
+        // The basic idea is that we're trying to deserialize as one, and then the other.
+        // The cost of it is that some errors will not be seen without a refactor.
+        match self.unpack_chunk(to_chunk(content.clone())).await {
+            Ok(data_map) => Ok(Unpacked::DataMap(data_map)),
+            Err(_) => Ok(Unpacked::SmallFile(content)),
+        }
+    }
+
     /// Extracts a file DataMapLevel from a chunk.
     /// If the DataMapLevel is not the first level mapping directly to the user's contents,
     /// the process repeats itself until it obtains the first level DataMapLevel.
@@ -250,6 +479,10 @@ impl Files {
                     let serialized_chunk = self.read_all(data_map).await?;
                     chunk = deserialize(&serialized_chunk).map_err(Error::Serialisation)?;
                 }
+                // Only the head chunk of a private file is encrypted, which is handled by `unpack_head_chunk`.
+                DataMapLevel::Encrypted(_) => {
+                    return Err(Error::PrivateDataMapDecryption)?;
+                }
             }
         }
     }
@@ -345,13 +578,39 @@ impl Files {
 /// Encrypts a [`LargeFile`] and returns the resulting address and all chunks.
 /// Does not store anything to the network.
 #[instrument(skip(bytes), level = "trace")]
-fn encrypt_large(bytes: Bytes) -> Result<(XorName, Vec<Chunk>)> {
-    Ok(super::chunks::encrypt_large(bytes)?)
+fn encrypt_large(bytes: Bytes, owner: Option<PublicKey>) -> Result<(XorName, Vec<Chunk>)> {
+    Ok(super::chunks::encrypt_large(bytes, owner)?)
+}
+
+/// Chunks the file at `path`, writing the chunks into `chunks_dir`.
+/// Returns the data-map address, and the names and paths of all the chunks.
+fn chunk_file_blocking(
+    path: &Path,
+    chunks_dir: &Path,
+    owner: Option<PublicKey>,
+) -> Result<(XorName, Vec<(XorName, PathBuf)>)> {
+    let file_size = std::fs::metadata(path).map_err(Error::Io)?.len() as usize;
+    if file_size < MIN_ENCRYPTABLE_BYTES {
+        let bytes = Bytes::from(std::fs::read(path).map_err(Error::Io)?);
+        let chunk = package_small(SmallFile::new(bytes)?, owner)?;
+        let name = *chunk.name();
+        let chunk_path = chunks_dir.join(hex::encode(name));
+        std::fs::write(&chunk_path, chunk.value()).map_err(Error::Io)?;
+        Ok((name, vec![(name, chunk_path)]))
+    } else {
+        Ok(encrypt_from_path(path, chunks_dir, owner)?)
+    }
 }
 
 /// Packages a [`SmallFile`] and returns the resulting address and the chunk.
+/// If an owner is provided, the content is encrypted to it.
 /// Does not store anything to the network.
-fn package_small(file: SmallFile) -> Result<Chunk> {
+fn package_small(file: SmallFile, owner: Option<PublicKey>) -> Result<Chunk> {
+    if let Some(owner) = owner {
+        // The encrypted content is told apart from a LargeFile's data map when read back,
+        // so it does not have to stay under `MIN_ENCRYPTABLE_BYTES`.
+        return Ok(to_chunk(seal(&file.bytes(), owner)?));
+    }
     let chunk = to_chunk(file.bytes());
     if chunk.value().len() >= self_encryption::MIN_ENCRYPTABLE_BYTES {
         return Err(Error::SmallFilePaddingNeeded(chunk.value().len()))?;
@@ -0,0 +1,309 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    chunks::Error as ChunksError, error::Result, ClientRegister, Error, Files, WalletClient,
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sn_protocol::storage::{ChunkAddress, RegisterAddress};
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use walkdir::WalkDir;
+use xor_name::{XorName, XOR_NAME_LEN};
+
+// The names of chunks, with the paths of the files they were written to.
+type ChunksPaths = Vec<(XorName, PathBuf)>;
+
+/// An entry of a [`FolderManifest`], describing one file of the folder.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FolderEntry {
+    /// Path of the file relative to the root of the folder, with `/` as separator.
+    pub path: String,
+    /// Size of the file, in bytes.
+    pub size: u64,
+    /// Last modification time of the file, in seconds since the Unix epoch, if it was known.
+    pub modified: Option<u64>,
+    /// Address of the file on the network. Empty files have no content to store, thus no address.
+    pub address: Option<ChunkAddress>,
+}
+
+impl FolderEntry {
+    /// Returns the path of the file relative to the root of the folder,
+    /// refusing any path which would lead outside of it.
+    pub fn relative_path(&self) -> Result<PathBuf> {
+        let segments: Vec<_> = self.path.split('/').collect();
+        let path = PathBuf::from_iter(&segments);
+        if segments
+            .iter()
+            .all(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+            && path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            Ok(path)
+        } else {
+            Err(Error::InvalidFolderEntryPath(self.path.clone()))
+        }
+    }
+}
+
+/// A manifest of the files in a folder.
+///
+/// The manifest is itself stored on the network as a file, so that the
+/// whole tree of files can be recreated from the single address of the manifest.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FolderManifest {
+    entries: Vec<FolderEntry>,
+}
+
+// The manifest as it is serialised, tagged with the version of its format.
+#[derive(Serialize, Deserialize)]
+enum VersionedFolderManifest {
+    V1(Vec<FolderEntry>),
+}
+
+impl FolderManifest {
+    /// Adds an entry to the manifest.
+    pub fn add_entry(&mut self, entry: FolderEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Returns the entries of the manifest.
+    pub fn entries(&self) -> &[FolderEntry] {
+        &self.entries
+    }
+
+    /// Serialises the manifest, using the latest version of the format.
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        let versioned = VersionedFolderManifest::V1(self.entries.clone());
+        Ok(Bytes::from(bincode::serialize(&versioned)?))
+    }
+
+    /// Deserialises a manifest of any known version of the format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bincode::deserialize(bytes)? {
+            VersionedFolderManifest::V1(entries) => Ok(Self { entries }),
+        }
+    }
+}
+
+impl Files {
+    /// Tries to chunk all the files under `folder_path`, together with a manifest of them,
+    /// writing the chunks into `chunks_dir`, without storing anything to network.
+    /// Returns the address of the manifest, the manifest,
+    /// and the names and paths of all the chunks, those of the manifest included.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn chunk_folder(
+        &self,
+        folder_path: &Path,
+        chunks_dir: &Path,
+    ) -> Result<(XorName, FolderManifest, ChunksPaths)> {
+        let mut manifest = FolderManifest::default();
+        // Files with the same content share their chunks.
+        let mut chunks = BTreeMap::new();
+
+        for entry in WalkDir::new(folder_path).sort_by_file_name() {
+            let entry = entry.map_err(|err| ChunksError::Io(err.into()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(folder_path)
+                .unwrap_or_else(|_| entry.path());
+            let path = match relative_path
+                .components()
+                .map(|component| component.as_os_str().to_str())
+                .collect::<Option<Vec<_>>>()
+            {
+                Some(components) => components.join("/"),
+                None => {
+                    warn!("Skipping file {relative_path:?} as its path is not valid UTF-8.");
+                    continue;
+                }
+            };
+
+            let metadata = entry
+                .metadata()
+                .map_err(|err| ChunksError::Io(err.into()))?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+
+            let address = if metadata.len() == 0 {
+                None
+            } else {
+                let (address, file_chunks) = self.chunk_file(entry.path(), chunks_dir).await?;
+                chunks.extend(file_chunks);
+                Some(ChunkAddress::new(address))
+            };
+
+            manifest.add_entry(FolderEntry {
+                path,
+                size: metadata.len(),
+                modified,
+                address,
+            });
+        }
+
+        let manifest_bytes = manifest.to_bytes()?;
+        let (manifest_address, manifest_chunks) = self
+            .chunk_reader(&mut manifest_bytes.as_ref(), chunks_dir)
+            .await?;
+        chunks.extend(manifest_chunks);
+
+        Ok((manifest_address, manifest, chunks.into_iter().collect()))
+    }
+
+    /// Reads the folder manifest stored at the given address.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn read_folder_manifest(&self, address: ChunkAddress) -> Result<FolderManifest> {
+        let bytes = self.read_bytes(address).await?;
+        FolderManifest::from_bytes(&bytes)
+    }
+
+    /// Recreates the folder whose manifest is stored at the given address under `dest_dir`.
+    /// Returns the manifest of the folder.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn download_folder(
+        &self,
+        address: ChunkAddress,
+        dest_dir: &Path,
+    ) -> Result<FolderManifest> {
+        let manifest = self.read_folder_manifest(address).await?;
+
+        for entry in manifest.entries() {
+            let file_path = dest_dir.join(entry.relative_path()?);
+            if let Some(parent) = file_path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(ChunksError::Io)?;
+            }
+
+            match entry.address {
+                Some(file_address) => {
+                    let _ = self.read_to_path(file_address, &file_path).await?;
+                }
+                None => {
+                    tokio::fs::write(&file_path, b"")
+                        .await
+                        .map_err(ChunksError::Io)?;
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Writes the address of a folder manifest to the register of the given name,
+    /// creating the register, paid for with the given wallet, if it does not yet exist.
+    /// Returns the address of the register, through which the latest version of the folder can be found.
+    #[instrument(skip(self, wallet_client), level = "debug")]
+    pub async fn publish_folder_to_register(
+        &self,
+        meta: XorName,
+        manifest_address: ChunkAddress,
+        wallet_client: &mut WalletClient,
+        verify_store: bool,
+    ) -> Result<RegisterAddress> {
+        let address = RegisterAddress::new(meta, self.client.signer_pk());
+        let mut register = match self.client.get_register(address).await {
+            Ok(register) => register,
+            Err(_) => {
+                ClientRegister::create_online(
+                    self.client.clone(),
+                    meta,
+                    wallet_client,
+                    verify_store,
+                )
+                .await?
+            }
+        };
+
+        register
+            .write_merging_branches_online(&manifest_address.xorname().0, verify_store)
+            .await?;
+
+        Ok(*register.address())
+    }
+
+    /// Returns the address of the folder manifest the given register currently points to.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn folder_address_from_register(
+        &self,
+        address: RegisterAddress,
+    ) -> Result<ChunkAddress> {
+        let register = self.client.get_register(address).await?;
+        let entries = register.read();
+
+        let entry = match entries.iter().next() {
+            Some((_, entry)) if entries.len() == 1 => entry,
+            Some(_) => return Err(Error::ContentBranchDetected(entries)),
+            None => return Err(Error::InvalidFolderPointer(Box::new(address))),
+        };
+
+        let name: [u8; XOR_NAME_LEN] = entry
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::InvalidFolderPointer(Box::new(address)))?;
+        Ok(ChunkAddress::new(XorName(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FolderEntry, FolderManifest};
+
+    use eyre::Result;
+    use sn_protocol::storage::ChunkAddress;
+    use xor_name::XorName;
+
+    #[test]
+    fn folder_manifest_to_and_from_bytes() -> Result<()> {
+        let mut manifest = FolderManifest::default();
+        manifest.add_entry(FolderEntry {
+            path: "docs/readme.md".to_string(),
+            size: 1024,
+            modified: Some(1_690_000_000),
+            address: Some(ChunkAddress::new(XorName::random(&mut rand::thread_rng()))),
+        });
+        manifest.add_entry(FolderEntry {
+            path: "empty".to_string(),
+            size: 0,
+            modified: None,
+            address: None,
+        });
+
+        let deserialized = FolderManifest::from_bytes(&manifest.to_bytes()?)?;
+        assert_eq!(deserialized, manifest);
+        Ok(())
+    }
+
+    #[test]
+    fn folder_entry_paths_cannot_leave_the_folder() {
+        let entry = |path: &str| FolderEntry {
+            path: path.to_string(),
+            size: 0,
+            modified: None,
+            address: None,
+        };
+
+        assert!(entry("docs/readme.md").relative_path().is_ok());
+        assert!(entry("../outside").relative_path().is_err());
+        assert!(entry("docs/../../outside").relative_path().is_err());
+        assert!(entry("/etc/passwd").relative_path().is_err());
+    }
+}
@@ -15,6 +15,7 @@ mod error;
 mod event;
 mod faucet;
 mod file_apis;
+mod folders;
 mod register;
 mod wallet;
 
@@ -25,9 +26,11 @@ pub use self::{
     event::{ClientEvent, ClientEventsReceiver},
     faucet::{get_tokens_from_faucet, load_faucet_wallet_from_genesis_wallet},
     file_apis::Files,
+    folders::{FolderEntry, FolderManifest},
     register::ClientRegister,
-    wallet::{send, WalletClient},
+    wallet::{send, TxReconciliation, WalletAudit, WalletClient},
 };
+pub use sn_networking::{NetworkConfig, NetworkConfigArgs};
 
 use self::event::ClientEventsChannel;
 use indicatif::ProgressBar;
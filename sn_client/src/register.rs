@@ -6,14 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{Client, Error, Result};
+use crate::{Client, Error, Result, WalletClient};
 
 use bls::PublicKey;
 use libp2p::kad::Record;
 use sn_protocol::{
     error::Error as ProtocolError,
-    messages::RegisterCmd,
-    storage::{try_serialize_record, RecordKind},
+    messages::{PaymentProof, RegisterCmd},
+    storage::{try_serialize_record, RecordKind, RegisterWithPayment},
     NetworkAddress,
 };
 use sn_registers::{
@@ -29,7 +29,8 @@ use xor_name::XorName;
 pub struct ClientRegister {
     client: Client,
     register: Register,
-    ops: LinkedList<RegisterCmd>, // Cached operations.
+    ops: LinkedList<RegisterCmd>,  // Cached operations.
+    payment: Option<PaymentProof>, // Payment for creating the Register on the network.
 }
 
 impl ClientRegister {
@@ -42,6 +43,7 @@ impl ClientRegister {
             client,
             register,
             ops: LinkedList::new(),
+            payment: None,
         };
 
         Ok(reg)
@@ -52,21 +54,30 @@ impl ClientRegister {
         Self::create_register(client, meta, Permissions::new_owner_only())
     }
 
-    /// Create a new public Register (Anybody can write to it) and send it so the Network.
+    /// Create a new public Register (Anybody can write to it) and send it so the Network,
+    /// paying for its storage with the given wallet.
     /// This will optionally verify the Register was stored on the network.
     pub async fn create_public_online(
         client: Client,
         meta: XorName,
+        wallet_client: &mut WalletClient,
         verify_store: bool,
     ) -> Result<Self> {
         let mut reg = Self::create_register(client, meta, Permissions::new_owner_only())?;
+        reg.pay(wallet_client, verify_store).await?;
         reg.sync(verify_store).await?;
         Ok(reg)
     }
 
-    /// Create a new Register and send it to the Network.
-    pub async fn create_online(client: Client, meta: XorName, verify_store: bool) -> Result<Self> {
+    /// Create a new Register and send it to the Network, paying for its storage with the given wallet.
+    pub async fn create_online(
+        client: Client,
+        meta: XorName,
+        wallet_client: &mut WalletClient,
+        verify_store: bool,
+    ) -> Result<Self> {
         let mut reg = Self::create_register(client, meta, Permissions::new_owner_only())?;
+        reg.pay(wallet_client, verify_store).await?;
         reg.sync(verify_store).await?;
         Ok(reg)
     }
@@ -79,6 +90,7 @@ impl ClientRegister {
             client,
             register,
             ops: LinkedList::new(),
+            payment: None,
         })
     }
 
@@ -162,6 +174,24 @@ impl ClientRegister {
 
     // ********* Online methods  *********
 
+    /// Pay for storing this Register on the network with the given wallet.
+    /// A Register which doesn't exist yet on the network needs to be paid for before being synced.
+    pub async fn pay(
+        &mut self,
+        wallet_client: &mut WalletClient,
+        verify_store: bool,
+    ) -> Result<()> {
+        let name = self.address().xorname();
+        let (mut proofs, _) = wallet_client
+            .pay_for_register_storage(self.address(), verify_store)
+            .await?;
+        let payment = proofs
+            .remove(&name)
+            .ok_or_else(|| Error::MissingRegisterPaymentProof(Box::new(*self.address())))?;
+        self.payment = Some(payment);
+        Ok(())
+    }
+
     /// Sync this Register with the replicas on the network.
     /// This will optionally verify the stored Register on the network is the same as the local one.
     pub async fn sync(&mut self, verify_store: bool) -> Result<()> {
@@ -175,9 +205,13 @@ impl ClientRegister {
                         "Creating Register as it doesn't exist at {:?}!",
                         self.address(),
                     );
+                    let payment = self.payment.clone().ok_or_else(|| {
+                        Error::MissingRegisterPaymentProof(Box::new(*self.address()))
+                    })?;
                     let cmd = RegisterCmd::Create {
                         register: self.register.clone(),
                         signature: self.client.sign(self.register.bytes()?),
+                        payment,
                     };
                     self.publish_register(cmd, verify_store).await?;
                     self.register.clone()
@@ -258,7 +292,7 @@ impl ClientRegister {
         debug!("Querying existing Register for cmd: {cmd_dst:?}");
         let network_reg = self
             .client
-            .get_signed_register_from_network(cmd.dst())
+            .get_register_with_payment_from_network(cmd.dst())
             .await;
 
         debug!("Publishing Register cmd: {cmd_dst:?}");
@@ -266,23 +300,31 @@ impl ClientRegister {
             RegisterCmd::Create {
                 register,
                 signature,
+                payment,
             } => {
                 if let Ok(existing_reg) = network_reg {
-                    if existing_reg.owner() != register.owner() {
-                        return Err(ProtocolError::RegisterAlreadyClaimed(existing_reg.owner()))?;
+                    if existing_reg.register.owner() != register.owner() {
+                        return Err(ProtocolError::RegisterAlreadyClaimed(
+                            existing_reg.register.owner(),
+                        ))?;
                     }
                     return Ok(()); // no op, since already created
                 }
-                SignedRegister::new(register, signature)
+                RegisterWithPayment {
+                    register: SignedRegister::new(register, signature),
+                    payment,
+                }
             }
             RegisterCmd::Edit(op) => {
+                // The edited Register is sent along with the payment made for its creation.
                 let mut reg = network_reg?;
-                reg.add_op(op)?;
+                reg.register.add_op(op)?;
                 reg
             }
         };
 
-        let key = NetworkAddress::from_register_address(*register.address()).to_record_key();
+        let key =
+            NetworkAddress::from_register_address(*register.register.address()).to_record_key();
 
         let record = Record {
             key,
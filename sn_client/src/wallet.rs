@@ -8,41 +8,92 @@
 
 use super::Client;
 
+use libp2p::PeerId;
 use rand::rngs::OsRng;
-use sn_dbc::{Dbc, PublicAddress, Token};
-use sn_protocol::{messages::PaymentProof, storage::ChunkAddress, NetworkAddress};
+use sn_dbc::{Dbc, DbcId, Hash, PublicAddress, Token};
+use sn_protocol::{
+    messages::{PaymentProof, Transfer},
+    storage::{ChunkAddress, RegisterAddress},
+    NetworkAddress,
+};
 use sn_transfers::{
     client_transfers::TransferOutputs,
     payment_proof::build_payment_proofs,
     wallet::{Error, LocalWallet, PaymentProofsMap, Result},
 };
 
-use futures::future::join_all;
-use std::{iter::Iterator, time::Duration};
+use futures::{future::join_all, stream, StreamExt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::Iterator,
+    time::Duration,
+};
 use tokio::time::sleep;
 use xor_name::XorName;
 
+// Maximum number of addresses the store cost quotes are concurrently requested for.
+const STORE_COST_QUOTES_CONCURRENCY: usize = 32;
+// Maximum number of dbcs whose spends are concurrently looked up when auditing the wallet.
+const AUDIT_CONCURRENCY: usize = 32;
+
+/// The outcome of checking the available dbcs of a wallet against the network.
+#[derive(Debug, Default)]
+pub struct WalletAudit {
+    /// The number of available dbcs checked.
+    pub checked: usize,
+    /// The dbcs found spent in the network, with their amount.
+    /// They have been moved to the spent dbcs of the wallet.
+    pub spent: Vec<(DbcId, Token)>,
+    /// The dbcs found spent more than once, with the hashes of the txs they were spent in.
+    pub double_spent: Vec<(DbcId, Vec<Hash>)>,
+    /// The dbcs whose spends could not be looked up, with the error.
+    pub failed: Vec<(DbcId, String)>,
+}
+
+/// The outcome of reconciling the unconfirmed transfers of a wallet with the network.
+#[derive(Debug, Default)]
+pub struct TxReconciliation {
+    /// The txs whose spends were all found in the network.
+    pub confirmed: Vec<Hash>,
+    /// The txs successfully republished to the network.
+    pub republished: Vec<Hash>,
+    /// The txs rolled back, as some of their inputs, or of the txs they spend the outputs of,
+    /// were spent in another tx.
+    pub rolled_back: Vec<Hash>,
+    /// The txs still unconfirmed, to be retried later.
+    pub unconfirmed: Vec<Hash>,
+}
+
 /// A wallet client can be used to send and
 /// receive tokens to/from other wallets.
+///
+/// The transfers which have not yet been successfully confirmed in the network
+/// are kept by the wallet, stored along with it, and republished to reach network validity.
 pub struct WalletClient {
     client: Client,
     wallet: LocalWallet,
-    /// These have not yet been successfully confirmed in
-    /// the network and need to be republished, to reach network validity.
-    /// We maintain the order they were added in, as to republish
-    /// them in the correct order, in case any later spend was
-    /// dependent on an earlier spend.
-    unconfirmed_txs: Vec<TransferOutputs>,
+    reconciled_txs: TxReconciliation,
 }
 
 impl WalletClient {
     /// Create a new wallet client.
-    pub fn new(client: Client, wallet: LocalWallet) -> Self {
-        Self {
+    /// The transfers left unconfirmed by previous runs are first reconciled with the network.
+    pub async fn new(client: Client, wallet: LocalWallet) -> Self {
+        let mut wallet_client = Self {
             client,
             wallet,
-            unconfirmed_txs: vec![],
+            reconciled_txs: TxReconciliation::default(),
+        };
+        if wallet_client.unconfirmed_txs_exist() {
+            wallet_client.reconciled_txs = wallet_client.reconcile_unconfirmed_txs().await;
         }
+        wallet_client
+    }
+
+    /// The outcome of reconciling the transfers left unconfirmed by previous runs,
+    /// when the wallet client was created.
+    pub fn reconciled_txs(&self) -> &TxReconciliation {
+        &self.reconciled_txs
     }
 
     /// Get any known store cost estimate
@@ -52,7 +103,7 @@ impl WalletClient {
 
     /// Do we have any unconfirmed transactions?
     pub fn unconfirmed_txs_exist(&self) -> bool {
-        !self.unconfirmed_txs.is_empty()
+        !self.wallet.unconfirmed_txs().is_empty()
     }
 
     /// Send tokens to another wallet.
@@ -63,27 +114,107 @@ impl WalletClient {
         to: PublicAddress,
         verify_store: bool,
     ) -> Result<Dbc> {
+        let transfer = self.send_outputs(vec![(amount, to)], verify_store).await?;
+
+        // return created DBCs even if network part failed???
+        match &transfer.created_dbcs[..] {
+            [info, ..] => Ok(info.clone()),
+            [] => Err(Error::CouldNotSendTokens(
+                "No DBCs were returned from the wallet.".into(),
+            )),
+        }
+    }
+
+    /// Send tokens to another wallet, returning the transfer to hand over to the recipient.
+    /// Only the recipient can decrypt it, and receive the tokens out of it once the spends
+    /// are found in the network.
+    pub async fn send_transfer(
+        &mut self,
+        amount: Token,
+        to: PublicAddress,
+        verify_store: bool,
+    ) -> Result<Transfer> {
+        let transfer = self.send_outputs(vec![(amount, to)], verify_store).await?;
+
+        match transfer.recipient_transfers.into_iter().next() {
+            Some((_, recipient_transfer)) => Ok(recipient_transfer),
+            None => Err(Error::CouldNotSendTokens(
+                "No transfer was created for the recipient.".into(),
+            )),
+        }
+    }
+
+    /// Send tokens to several wallets in a single transaction,
+    /// returning the transfer to hand over to each recipient.
+    pub async fn send_to_many(
+        &mut self,
+        to: Vec<(Token, PublicAddress)>,
+        verify_store: bool,
+    ) -> Result<Vec<(PublicAddress, Transfer)>> {
+        let transfer = self.send_outputs(to, verify_store).await?;
+        Ok(transfer.recipient_transfers)
+    }
+
+    /// Receive the tokens of a transfer made to us.
+    /// The dbcs are rebuilt out of the spends found in the network,
+    /// verified, and deposited to the wallet.
+    pub async fn receive(&mut self, transfer: &Transfer) -> Result<Vec<Dbc>> {
+        let mut dbcs = vec![];
+        for utxo in self.wallet.unwrap_transfer(transfer)? {
+            // The parent spend leads us to the tx the dbc was created in.
+            let parent_spend = self
+                .client
+                .get_spend_at_address(*utxo.parent_spend())
+                .await
+                .map_err(|err| Error::CouldNotReceiveTransfer(err.to_string()))?;
+            let src_tx = parent_spend.spent_tx();
+
+            // All the inputs of the tx must have been spent in it for the dbc to be valid.
+            let mut tasks = Vec::new();
+            for input in &src_tx.inputs {
+                tasks.push(self.client.get_spend_from_network(&input.dbc_id));
+            }
+            let mut signed_spends = BTreeSet::new();
+            for result in join_all(tasks).await {
+                let signed_spend =
+                    result.map_err(|err| Error::CouldNotReceiveTransfer(err.to_string()))?;
+                let _ = signed_spends.insert(signed_spend);
+            }
+
+            dbcs.push(
+                self.wallet
+                    .rebuild_received_dbc(&utxo, src_tx, signed_spends)?,
+            );
+        }
+
+        self.wallet.deposit(dbcs.clone());
+        Ok(dbcs)
+    }
+
+    // Creates the transfer and sends its spends to the network,
+    // retrying the previous failures first.
+    async fn send_outputs(
+        &mut self,
+        to: Vec<(Token, PublicAddress)>,
+        verify_store: bool,
+    ) -> Result<TransferOutputs> {
         // retry previous failures
         self.resend_pending_txs(verify_store).await;
 
         // offline transfer
-        let transfer = self.wallet.local_send(vec![(amount, to)], None).await?;
-        let dbcs = transfer.created_dbcs.clone();
+        let transfer = self.wallet.local_send(to, None).await?;
 
         // send to network
         trace!("Sending transfer to the network: {transfer:#?}");
-        if let Err(error) = self.client.send(transfer.clone(), verify_store).await {
-            warn!("The transfer was not successfully registered in the network: {error:?}. It will be retried later.");
-            self.unconfirmed_txs.push(transfer);
+        match self.client.send(transfer.clone(), verify_store).await {
+            Ok(()) => self.wallet.confirm_tx(transfer.tx.hash()),
+            Err(error) => {
+                warn!("The transfer was not successfully registered in the network: {error:?}. It will be retried later.");
+                self.wallet.add_unconfirmed_tx(transfer.clone());
+            }
         }
 
-        // return created DBCs even if network part failed???
-        match &dbcs[..] {
-            [info, ..] => Ok(info.clone()),
-            [] => Err(Error::CouldNotSendTokens(
-                "No DBCs were returned from the wallet.".into(),
-            )),
-        }
+        Ok(transfer)
     }
 
     /// Get storecost from the network
@@ -106,12 +237,43 @@ impl WalletClient {
         &mut self,
         content_addrs: impl Iterator<Item = &XorName>,
         verify_store: bool,
+    ) -> Result<(PaymentProofsMap, Option<Token>)> {
+        let addresses = content_addrs
+            .map(|name| {
+                (
+                    *name,
+                    NetworkAddress::from_chunk_address(ChunkAddress::new(*name)),
+                )
+            })
+            .collect();
+        self.pay_for_addresses(addresses, verify_store).await
+    }
+
+    /// Send tokens to nodes closest to the Register we want to make a storage payment for,
+    /// along with the quotes they made for its own address.
+    pub async fn pay_for_register_storage(
+        &mut self,
+        address: &RegisterAddress,
+        verify_store: bool,
+    ) -> Result<(PaymentProofsMap, Option<Token>)> {
+        let addresses = vec![(
+            address.xorname(),
+            NetworkAddress::from_register_address(*address),
+        )];
+        self.pay_for_addresses(addresses, verify_store).await
+    }
+
+    // Pays for the content of each name, getting the quotes of the nodes closest to its address.
+    async fn pay_for_addresses(
+        &mut self,
+        addresses: Vec<(XorName, NetworkAddress)>,
+        verify_store: bool,
     ) -> Result<(PaymentProofsMap, Option<Token>)> {
         // Let's filter the content addresses we hold payment proofs for, i.e. avoid
         // paying for those chunks we've already paid for with this wallet.
         let mut proofs = PaymentProofsMap::default();
 
-        let addrs_to_pay: Vec<&XorName> = content_addrs.collect();
+        let addrs_to_pay: Vec<&XorName> = addresses.iter().map(|(name, _)| name).collect();
         // TODO: reenable this when we have a way to get the store cost from the network
         // per chunk, and can readily check what we've paid here.
         // .filter(|name| {
@@ -132,14 +294,66 @@ impl WalletClient {
             return Ok((proofs, None));
         }
 
+        // The content sharing a close group is paid for with the same quotes, as nodes accept
+        // those of any node within close group distance of the content, so they are only
+        // requested once per close group, for the first address of its content.
+        // The close groups are told apart with our routing table, every address being its
+        // own group while it's too small for that.
+        let close_group_size = self.client.network.network_config.close_group_size;
+        let mut names_by_close_group: BTreeMap<Vec<PeerId>, (NetworkAddress, Vec<XorName>)> =
+            BTreeMap::new();
+        let mut ungrouped = vec![];
+        for (name, address) in addresses.iter().cloned() {
+            let mut close_group = self
+                .client
+                .network
+                .get_closest_local_peers(&address)
+                .await
+                .map_err(|error| Error::CouldNotGetStoreCost(error.to_string()))?;
+            if close_group.len() < close_group_size {
+                ungrouped.push((address, vec![name]));
+                continue;
+            }
+            close_group.sort();
+            names_by_close_group
+                .entry(close_group)
+                .or_insert_with(|| (address, vec![]))
+                .1
+                .push(name);
+        }
+        trace!(
+            "Getting the store cost quotes of {} close groups for {} records",
+            names_by_close_group.len() + ungrouped.len(),
+            addresses.len()
+        );
+
+        // As every record is paid the same amount, the highest of the quoted costs is paid.
+        let mut quotes_by_addr = BTreeMap::new();
+        let mut highest_cost = Token::zero();
+        let client = self.client.clone();
+        let mut quote_tasks = stream::iter(names_by_close_group.into_values().chain(ungrouped))
+            .map(|(address, names)| {
+                let client = client.clone();
+                async move { (names, client.get_store_cost_quotes(address).await) }
+            })
+            .buffer_unordered(STORE_COST_QUOTES_CONCURRENCY);
+        while let Some((names, result)) = quote_tasks.next().await {
+            let (cost, quotes) =
+                result.map_err(|error| Error::CouldNotGetStoreCost(error.to_string()))?;
+            highest_cost = highest_cost.max(cost);
+            for name in names {
+                let _ = quotes_by_addr.insert(name, quotes.clone());
+            }
+        }
+        self.client.network_store_cost = highest_cost.as_nano();
+
         // Let's build the payment proofs for list of content addresses
         let (root_hash, audit_trail_info) = build_payment_proofs(addrs_to_pay.into_iter())?;
         let num_of_addrs = audit_trail_info.len() as u64;
 
-        // Always check storage cost, and overpay to allow margin when validation.
-        self.set_store_cost_from_random_address().await?;
+        // Always overpay to allow margin when validation.
         let storage_cost = Token::from_nano(
-            self.store_cost().as_nano() * (2.0f64.powf((num_of_addrs / 100 + 1) as f64)) as u64,
+            highest_cost.as_nano() * (2.0f64.powf((num_of_addrs / 100 + 1) as f64)) as u64,
         );
 
         info!("Storage cost per record: {}", storage_cost);
@@ -150,16 +364,22 @@ impl WalletClient {
 
         let transfer = self
             .wallet
-            .local_send_storage_payment(Token::from_nano(amount_to_pay), root_hash, None)
+            .local_send_storage_payment(
+                Token::from_nano(amount_to_pay),
+                root_hash,
+                num_of_addrs as usize,
+                None,
+            )
             .await?;
 
         // send to network
         trace!("Sending storage payment transfer to the network: {transfer:#?}");
         if let Err(error) = self.client.send(transfer.clone(), verify_store).await {
             warn!("The storage payment transfer was not successfully registered in the network: {error:?}. It will be retried later.");
-            self.unconfirmed_txs.push(transfer);
+            self.wallet.add_unconfirmed_tx(transfer);
             return Err(error);
         }
+        self.wallet.confirm_tx(transfer.tx.hash());
 
         let spent_ids: Vec<_> = transfer.tx.inputs.iter().map(|i| i.dbc_id()).collect();
 
@@ -172,6 +392,7 @@ impl WalletClient {
                         spent_ids: spent_ids.clone(),
                         audit_trail,
                         path,
+                        quotes: quotes_by_addr.remove(&addr).unwrap_or_default(),
                     },
                 )
             })
@@ -189,7 +410,9 @@ impl WalletClient {
     /// Resend failed txs
     /// This can optionally verify the store has been successful (this will attempt to GET the dbc from the network)
     pub async fn resend_pending_txs(&mut self, verify_store: bool) {
-        for (index, transfer) in self.unconfirmed_txs.clone().into_iter().enumerate() {
+        // Cloned, as they are confirmed along the way.
+        let unconfirmed_txs = self.wallet.unconfirmed_txs().to_vec();
+        for transfer in unconfirmed_txs {
             let tx_hash = transfer.tx.hash();
             println!("Trying to republish pending tx: {tx_hash:?}..");
             if self
@@ -199,7 +422,7 @@ impl WalletClient {
                 .is_ok()
             {
                 println!("Tx {tx_hash:?} was successfully republished!");
-                let _ = self.unconfirmed_txs.remove(index);
+                self.wallet.confirm_tx(tx_hash);
                 // We might want to be _really_ sure and do the below
                 // as well, but it's not necessary.
                 // use crate::domain::wallet::VerifyingClient;
@@ -208,6 +431,132 @@ impl WalletClient {
         }
     }
 
+    /// Reconciles the unconfirmed transfers with the network, e.g. those left by a previous run.
+    ///
+    /// Those whose spends are all found in the network are confirmed, while the others are
+    /// republished. Those which can't be completed anymore, as some of their inputs were spent
+    /// in another tx, are rolled back, their remaining inputs being available again.
+    /// Returns what became of each of them.
+    pub async fn reconcile_unconfirmed_txs(&mut self) -> TxReconciliation {
+        let mut reconciliation = TxReconciliation::default();
+        // Cloned, as they are confirmed or rolled back along the way.
+        let unconfirmed_txs = self.wallet.unconfirmed_txs().to_vec();
+        for transfer in unconfirmed_txs {
+            let tx_hash = transfer.tx.hash();
+            // Rolled back along with an earlier one.
+            if reconciliation.rolled_back.contains(&tx_hash) {
+                continue;
+            }
+
+            let mut tasks = Vec::new();
+            for spend_request in &transfer.all_spend_requests {
+                tasks.push(
+                    self.client
+                        .get_spend_from_network(spend_request.signed_spend.dbc_id()),
+                );
+            }
+
+            // The inputs whose spend is found in the network, ours or another one.
+            let mut still_spent = BTreeSet::new();
+            let mut spent_elsewhere = false;
+            for (result, spend_request) in join_all(tasks)
+                .await
+                .into_iter()
+                .zip(&transfer.all_spend_requests)
+            {
+                if let Ok(signed_spend) = result {
+                    spent_elsewhere |= signed_spend != spend_request.signed_spend;
+                    let _ = still_spent.insert(*spend_request.signed_spend.dbc_id());
+                }
+            }
+
+            if spent_elsewhere {
+                warn!("Tx {tx_hash:?} can't be completed, as some of its inputs were spent in another tx. Rolling it back.");
+                let rolled_back = self.wallet.rollback_tx(tx_hash, &still_spent);
+                if rolled_back.len() > 1 {
+                    warn!("Rolled back {rolled_back:?}, spending the outputs of tx {tx_hash:?}.");
+                }
+                reconciliation.rolled_back.extend(rolled_back);
+            } else if still_spent.len() == transfer.all_spend_requests.len() {
+                info!("Tx {tx_hash:?} was confirmed by the network.");
+                self.wallet.confirm_tx(tx_hash);
+                reconciliation.confirmed.push(tx_hash);
+            } else if self.client.send(transfer, true).await.is_ok() {
+                info!("Tx {tx_hash:?} was successfully republished.");
+                self.wallet.confirm_tx(tx_hash);
+                reconciliation.republished.push(tx_hash);
+            } else {
+                warn!("Tx {tx_hash:?} is still unconfirmed. It will be retried later.");
+                reconciliation.unconfirmed.push(tx_hash);
+            }
+        }
+        reconciliation
+    }
+
+    /// Checks the available dbcs of the wallet are still unspent in the network, as they may
+    /// not be when the wallet was restored from a backup, or is used from several machines.
+    ///
+    /// Those found spent are moved to the spent dbcs, the balance being recomputed,
+    /// and any evidence of a double spend is reported.
+    pub async fn verify_wallet(&mut self) -> WalletAudit {
+        let dbcs: Vec<_> = self
+            .wallet
+            .available_dbcs()
+            .into_iter()
+            .map(|(dbc, _)| dbc)
+            .collect();
+        let mut audit = WalletAudit {
+            checked: dbcs.len(),
+            ..Default::default()
+        };
+
+        let client = self.client.clone();
+        let mut spend_tasks = stream::iter(dbcs)
+            .map(|dbc| {
+                let client = client.clone();
+                async move {
+                    let spends = client.get_spends_from_network(&dbc.id()).await;
+                    (dbc, spends)
+                }
+            })
+            .buffer_unordered(AUDIT_CONCURRENCY);
+
+        while let Some((dbc, result)) = spend_tasks.next().await {
+            let dbc_id = dbc.id();
+            let signed_spends = match result {
+                Ok(signed_spends) => signed_spends,
+                Err(err) => {
+                    warn!("Could not look up the spends of {dbc_id:?}: {err}");
+                    audit.failed.push((dbc_id, err.to_string()));
+                    continue;
+                }
+            };
+            if signed_spends.is_empty() {
+                continue;
+            }
+
+            if signed_spends.len() > 1 {
+                warn!("Found a double spend of {dbc_id:?} in the network");
+                let tx_hashes = signed_spends
+                    .iter()
+                    .map(|signed_spend| signed_spend.spent_tx_hash())
+                    .collect();
+                audit.double_spent.push((dbc_id, tx_hashes));
+            }
+            info!("Found {dbc_id:?} spent in the network, moving it to the spent dbcs");
+            if self
+                .wallet
+                .mark_as_spent(&dbc_id, signed_spends[0].spent_tx_hash())
+            {
+                audit
+                    .spent
+                    .push((dbc_id, dbc.token().unwrap_or(Token::zero())));
+            }
+        }
+
+        audit
+    }
+
     /// Return the wallet.
     pub fn into_wallet(self) -> LocalWallet {
         self.wallet
@@ -292,7 +641,7 @@ pub async fn send(
         panic!("Amount must be more than zero.");
     }
 
-    let mut wallet_client = WalletClient::new(client.clone(), from);
+    let mut wallet_client = WalletClient::new(client.clone(), from).await;
     let new_dbc = wallet_client
         .send(amount, to, verify_store)
         .await
@@ -0,0 +1,137 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::executor::block_on;
+use libp2p::{request_response::Codec, PeerId, StreamProtocol};
+use serde::{de::DeserializeOwned, Serialize};
+use sn_networking::{MsgCodec, NetworkConfig};
+use sn_protocol::{
+    messages::{
+        Cmd, PaymentProof, QueryResponse, ReplicatedData, Request, Response, MESSAGES_VERSION,
+    },
+    storage::{Chunk, ChunkWithPayment},
+    NetworkAddress,
+};
+
+// The number of keys of a node holding as many records as it does by default.
+const REPLICATED_KEYS: usize = 2048;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+fn protocol() -> StreamProtocol {
+    StreamProtocol::try_from_owned(format!("/safe/bench/messages/{MESSAGES_VERSION}"))
+        .expect("Invalid protocol")
+}
+
+fn replicate_request() -> Request {
+    Request::Cmd(Cmd::Replicate {
+        holder: NetworkAddress::from_peer(PeerId::random()),
+        keys: (0..REPLICATED_KEYS)
+            .map(|_| NetworkAddress::RecordKey(rand::random::<[u8; 32]>().to_vec()))
+            .collect(),
+    })
+}
+
+fn chunk_response() -> Response {
+    let value: Vec<u8> = (0..CHUNK_SIZE).map(|_| rand::random()).collect();
+    let chunk = Chunk::new(Bytes::from(value));
+    let address = NetworkAddress::from_chunk_address(*chunk.address());
+    Response::Query(QueryResponse::GetReplicatedData(Ok((
+        address,
+        ReplicatedData::Chunk(ChunkWithPayment {
+            chunk,
+            payment: PaymentProof {
+                spent_ids: vec![],
+                audit_trail: vec![],
+                path: vec![],
+                quotes: vec![],
+            },
+        }),
+    ))))
+}
+
+// The messages encoded with CBOR, as `request_response::cbor::Behaviour` did before `MsgCodec`.
+fn encode_cbor<M: Serialize>(msg: &M) -> Vec<u8> {
+    cbor4ii::serde::to_vec(Vec::new(), msg).expect("Failed to encode the message")
+}
+
+fn decode_cbor<M: DeserializeOwned>(bytes: &[u8]) -> M {
+    cbor4ii::serde::from_slice(bytes).expect("Failed to decode the message")
+}
+
+fn encode_request(codec: &mut MsgCodec, protocol: &StreamProtocol, request: Request) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    block_on(codec.write_request(protocol, &mut bytes, request))
+        .expect("Failed to encode the request");
+    bytes
+}
+
+fn encode_response(codec: &mut MsgCodec, protocol: &StreamProtocol, response: Response) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    block_on(codec.write_response(protocol, &mut bytes, response))
+        .expect("Failed to encode the response");
+    bytes
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut codec = MsgCodec::new(NetworkConfig::default().max_packet_size);
+    let protocol = protocol();
+
+    let request = replicate_request();
+    let cbor = encode_cbor(&request);
+    let framed = encode_request(&mut codec, &protocol, request.clone());
+    println!(
+        "Replicate request encoded by cbor: {} bytes, framed: {} bytes",
+        cbor.len(),
+        framed.len()
+    );
+
+    let mut group = c.benchmark_group(format!("Replicate {REPLICATED_KEYS} keys"));
+    group.bench_function(BenchmarkId::new("encode", "cbor"), |b| {
+        b.iter(|| encode_cbor(&request))
+    });
+    group.bench_function(BenchmarkId::new("decode", "cbor"), |b| {
+        b.iter(|| decode_cbor::<Request>(&cbor))
+    });
+    group.bench_function(BenchmarkId::new("encode", "framed"), |b| {
+        b.iter(|| encode_request(&mut codec, &protocol, request.clone()))
+    });
+    group.bench_function(BenchmarkId::new("decode", "framed"), |b| {
+        b.iter(|| block_on(codec.read_request(&protocol, &mut &framed[..])))
+    });
+    group.finish();
+
+    let response = chunk_response();
+    let cbor = encode_cbor(&response);
+    let framed = encode_response(&mut codec, &protocol, response.clone());
+    println!(
+        "Chunk response encoded by cbor: {} bytes, framed: {} bytes",
+        cbor.len(),
+        framed.len()
+    );
+
+    let mut group = c.benchmark_group("GetReplicatedData 1MB chunk");
+    group.throughput(Throughput::Bytes(CHUNK_SIZE as u64));
+    group.bench_function(BenchmarkId::new("encode", "cbor"), |b| {
+        b.iter(|| encode_cbor(&response))
+    });
+    group.bench_function(BenchmarkId::new("decode", "cbor"), |b| {
+        b.iter(|| decode_cbor::<Response>(&cbor))
+    });
+    group.bench_function(BenchmarkId::new("encode", "framed"), |b| {
+        b.iter(|| encode_response(&mut codec, &protocol, response.clone()))
+    });
+    group.bench_function(BenchmarkId::new("decode", "framed"), |b| {
+        b.iter(|| block_on(codec.read_response(&protocol, &mut &framed[..])))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -7,9 +7,9 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{error::Error, MsgResponder, NetworkEvent, SwarmDriver};
-use crate::{error::Result, multiaddr_pop_p2p, sort_peers_by_address, CLOSE_GROUP_SIZE};
+use crate::{error::Result, multiaddr_pop_p2p, sort_peers_by_address, PeerMisbehaviour, PeerScore};
 use libp2p::{
-    kad::{store::RecordStore, Quorum, Record, RecordKey},
+    kad::{store::RecordStore, Record, RecordKey},
     swarm::{
         dial_opts::{DialOpts, PeerCondition},
         DialError,
@@ -19,7 +19,7 @@ use libp2p::{
 use sn_dbc::Token;
 use sn_protocol::{
     messages::{Request, Response},
-    NetworkAddress, PrettyPrintRecordKey,
+    NetworkAddress,
 };
 use std::collections::HashSet;
 use tokio::sync::oneshot;
@@ -96,20 +96,28 @@ pub enum SwarmCmd {
         key: RecordKey,
         sender: oneshot::Sender<Option<Record>>,
     },
-    /// Put record to network
-    PutRecord {
-        record: Record,
-        sender: oneshot::Sender<Result<()>>,
-    },
     /// Put record to the local RecordStore
     PutLocalRecord {
         record: Record,
     },
+    /// Remove a record from the local RecordStore, e.g. a corrupt copy
+    RemoveLocalRecord {
+        key: RecordKey,
+    },
     /// The keys added to the replication fetcher are later used to fetch the Record from the peer/network
     AddKeysToReplicationFetcher {
         peer: PeerId,
         keys: Vec<NetworkAddress>,
     },
+    /// Penalise a peer for misbehaving
+    ReportPeer {
+        peer: PeerId,
+        misbehaviour: PeerMisbehaviour,
+    },
+    /// Get the scores of the penalised peers
+    GetPeerScores {
+        sender: oneshot::Sender<Vec<PeerScore>>,
+    },
 }
 
 /// Snapshot of information kept in the Swarm's local state
@@ -145,6 +153,7 @@ impl SwarmDriver {
                 if !keys_to_fetch.is_empty() {
                     self.send_event(NetworkEvent::KeysForReplication(keys_to_fetch));
                 }
+                self.penalise_replication_timeouts();
             }
             SwarmCmd::GetNetworkRecord { key, sender } => {
                 let query_id = self.swarm.behaviour_mut().kademlia.get_record(key);
@@ -171,33 +180,6 @@ impl SwarmDriver {
                     .map(|rec| rec.into_owned());
                 let _ = sender.send(record);
             }
-            SwarmCmd::PutRecord { record, sender } => {
-                let record_key = PrettyPrintRecordKey::from(record.key.clone());
-                trace!(
-                    "Putting record sized: {:?} to network {:?}",
-                    record.value.len(),
-                    record_key
-                );
-                let res = match self
-                    .swarm
-                    .behaviour_mut()
-                    .kademlia
-                    .put_record(record, Quorum::All)
-                {
-                    Ok(request_id) => {
-                        trace!("Sent record {record_key:?} to network. Request id: {request_id:?} to network");
-                        Ok(())
-                    }
-                    Err(error) => {
-                        error!("Error sending record {record_key:?} to network");
-                        Err(Error::from(error))
-                    }
-                };
-
-                if let Err(err) = sender.send(res) {
-                    error!("Could not send response to PutRecord cmd: {:?}", err);
-                }
-            }
             SwarmCmd::PutLocalRecord { record } => {
                 let key = record.key.clone();
                 match self
@@ -212,10 +194,20 @@ impl SwarmDriver {
                         if !new_keys_to_fetch.is_empty() {
                             self.send_event(NetworkEvent::KeysForReplication(new_keys_to_fetch));
                         }
+                        self.penalise_replication_timeouts();
                     }
-                    Err(err) => return Err(err.into()),
+                    Err(err) => return Err(err),
                 };
             }
+            SwarmCmd::ReportPeer { peer, misbehaviour } => {
+                self.penalise_peer(peer, misbehaviour);
+            }
+            SwarmCmd::GetPeerScores { sender } => {
+                let _ = sender.send(self.peer_reputation.scores());
+            }
+            SwarmCmd::RemoveLocalRecord { key } => {
+                self.swarm.behaviour_mut().kademlia.store_mut().remove(&key);
+            }
             SwarmCmd::RecordStoreHasKey { key, sender } => {
                 let has_key = self
                     .swarm
@@ -268,7 +260,7 @@ impl SwarmDriver {
                     .kademlia
                     .get_closest_local_peers(&key)
                     .map(|peer| peer.into_preimage())
-                    .take(CLOSE_GROUP_SIZE)
+                    .take(self.network_config.close_group_size)
                     .collect();
 
                 let _ = sender.send(closest_peers);
@@ -365,12 +357,13 @@ impl SwarmDriver {
     // Hence, the ilog2 calculation based on close_range cannot cover such case.
     // And have to sort all nodes to figure out whether self is among the close_group to the target.
     fn is_in_close_range(&self, target: &NetworkAddress, all_peers: Vec<PeerId>) -> bool {
-        if all_peers.len() <= CLOSE_GROUP_SIZE + 2 {
+        // Margin of 2 to allow our RT being bit lagging.
+        let close_range_size = self.network_config.close_group_size + 2;
+        if all_peers.len() <= close_range_size {
             return true;
         }
 
-        // Margin of 2 to allow our RT being bit lagging.
-        match sort_peers_by_address(all_peers, target, CLOSE_GROUP_SIZE + 2) {
+        match sort_peers_by_address(all_peers, target, close_range_size) {
             Ok(close_group) => close_group.contains(&self.self_peer_id),
             Err(err) => {
                 warn!("Could not get sorted peers for {target:?} with error {err:?}");
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{cmd::SwarmCmd, NetworkEvent};
+use super::{cmd::SwarmCmd, record_storage::RecordStorageKind, NetworkEvent};
 
 use libp2p::{
     kad::{self, Record},
@@ -14,7 +14,7 @@ use libp2p::{
     swarm::DialError,
     TransportError,
 };
-use sn_protocol::{messages::Response, PrettyPrintRecordKey};
+use sn_protocol::{error::Error as ProtocolError, messages::Response, PrettyPrintRecordKey};
 use std::{io, path::PathBuf};
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot};
@@ -35,6 +35,15 @@ pub enum Error {
     #[error("Close group size must be a non-zero usize")]
     InvalidCloseGroupSize,
 
+    #[error("Could not read the network config at: {path:?}, error: {source}")]
+    FailedToReadNetworkConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid network config: {0}")]
+    InvalidNetworkConfig(String),
+
     #[error("Internal messaging channel was dropped")]
     InternalMsgChannelDropped,
 
@@ -50,12 +59,26 @@ pub enum Error {
     #[error("Record retrieved from the network does not match the one we attempted to store {0:}")]
     ReturnedRecordDoesNotMatch(PrettyPrintRecordKey),
 
+    #[error("Record {key:} was accepted by {accepted} peers only, not a majority of its close group, rejections: {rejections:?}")]
+    RecordNotAcceptedByMajority {
+        key: PrettyPrintRecordKey,
+        accepted: usize,
+        rejections: Vec<ProtocolError>,
+    },
+
     #[error("Could not create storage dir: {path:?}, error: {source}")]
     FailedToCreateRecordStoreDir {
         path: PathBuf,
         source: std::io::Error,
     },
 
+    #[error("Could not open the {storage_kind} record storage at: {path:?}, error: {source}")]
+    FailedToOpenRecordStorage {
+        path: PathBuf,
+        storage_kind: RecordStorageKind,
+        source: std::io::Error,
+    },
+
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
 
@@ -95,6 +118,12 @@ pub enum Error {
     #[error("Get Record completed with non enough copies")]
     RecordNotEnoughCopies(Record),
 
+    #[error("Get Record found {} differing copies of the record", .0.len())]
+    SplitRecord(Vec<Record>),
+
+    #[error("Get Record timed out")]
+    GetRecordTimeout,
+
     #[error("Error putting record")]
     PutRecordError(#[from] kad::PutRecordError),
 
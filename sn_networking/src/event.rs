@@ -13,8 +13,9 @@ use super::{
 };
 
 use crate::{
-    close_group_majority, multiaddr_is_global, multiaddr_strip_p2p, sort_peers_by_address,
-    CLOSE_GROUP_SIZE, IDENTIFY_AGENT_STR,
+    multiaddr_is_global, multiaddr_strip_p2p,
+    network_config::{has_compatible_parameters, is_same_network},
+    sort_peers_by_address, MsgCodec, PeerMisbehaviour, IDENTIFY_AGENT_STR,
 };
 
 use core::fmt;
@@ -23,12 +24,15 @@ use itertools::Itertools;
 #[cfg(feature = "local-discovery")]
 use libp2p::mdns;
 use libp2p::{
+    allow_block_list::{self, BlockedPeers},
     autonat::{self, NatStatus},
+    connection_limits, dcutr,
     kad::{
         GetRecordError, GetRecordOk, InboundRequest, Kademlia, KademliaEvent, PeerRecord, QueryId,
         QueryResult, Record, RecordKey, K_VALUE,
     },
     multiaddr::Protocol,
+    relay,
     request_response::{self, ResponseChannel as PeerResponseChannel},
     swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
     Multiaddr, PeerId,
@@ -54,12 +58,17 @@ pub(super) type GetRecordResultMap = HashMap<XorName, (Record, HashSet<PeerId>)>
 #[derive(NetworkBehaviour)]
 #[behaviour(to_swarm = "NodeEvent")]
 pub(super) struct NodeBehaviour {
-    pub(super) request_response: request_response::cbor::Behaviour<Request, Response>,
+    pub(super) request_response: request_response::Behaviour<MsgCodec>,
     pub(super) kademlia: Kademlia<DiskBackedRecordStore>,
     #[cfg(feature = "local-discovery")]
     pub(super) mdns: mdns::tokio::Behaviour,
     pub(super) identify: libp2p::identify::Behaviour,
     pub(super) autonat: Toggle<autonat::Behaviour>,
+    pub(super) relay_server: Toggle<relay::Behaviour>,
+    pub(super) relay_client: relay::client::Behaviour,
+    pub(super) dcutr: dcutr::Behaviour,
+    pub(super) blocked_peers: allow_block_list::Behaviour<BlockedPeers>,
+    pub(super) connection_limits: connection_limits::Behaviour,
 }
 
 /// NodeEvent enum
@@ -71,6 +80,9 @@ pub(super) enum NodeEvent {
     Mdns(Box<mdns::Event>),
     Identify(Box<libp2p::identify::Event>),
     Autonat(autonat::Event),
+    RelayServer(Box<relay::Event>),
+    RelayClient(Box<relay::client::Event>),
+    Dcutr(Box<dcutr::Event>),
 }
 
 impl From<request_response::Event<Request, Response>> for NodeEvent {
@@ -104,6 +116,31 @@ impl From<autonat::Event> for NodeEvent {
     }
 }
 
+impl From<relay::Event> for NodeEvent {
+    fn from(event: relay::Event) -> Self {
+        NodeEvent::RelayServer(Box::new(event))
+    }
+}
+
+impl From<relay::client::Event> for NodeEvent {
+    fn from(event: relay::client::Event) -> Self {
+        NodeEvent::RelayClient(Box::new(event))
+    }
+}
+
+impl From<dcutr::Event> for NodeEvent {
+    fn from(event: dcutr::Event) -> Self {
+        NodeEvent::Dcutr(Box::new(event))
+    }
+}
+
+// The block list and the connection limits never emit any event.
+impl From<void::Void> for NodeEvent {
+    fn from(event: void::Void) -> Self {
+        void::unreachable(event)
+    }
+}
+
 #[derive(CustomDebug)]
 /// Channel to send the `Response` through.
 pub enum MsgResponder {
@@ -128,8 +165,8 @@ pub enum NetworkEvent {
         /// Response
         res: Response,
     },
-    /// Peer has been added to the Routing Table
-    PeerAdded(PeerId),
+    /// Peer has been added to the Routing Table, along with the addresses it is reachable at
+    PeerAdded(PeerId, Vec<Multiaddr>),
     // Peer has been removed from the Routing Table
     PeerRemoved(PeerId),
     /// The Records for the these keys are to be fetched from the provided Peer or from the network
@@ -138,8 +175,20 @@ pub enum NetworkEvent {
     NewListenAddr(Multiaddr),
     /// AutoNAT status changed
     NatStatusChanged(NatStatus),
-    /// Report unverified record
-    UnverifiedRecord(Record),
+    /// Report unverified record, along with the peer which sent it, if any
+    UnverifiedRecord {
+        /// The record to be validated before being stored
+        record: Record,
+        /// The peer the record was received from
+        source: Option<PeerId>,
+    },
+    /// The records found in storage at startup have been checked and indexed
+    RecordsRecovered {
+        /// Number of valid records now held by the node
+        recovered: usize,
+        /// Number of corrupt records moved out of the storage
+        quarantined: usize,
+    },
 }
 
 // Manually implement Debug as `#[debug(with = "unverified_record_fmt")]` not working as expected.
@@ -152,8 +201,8 @@ impl Debug for NetworkEvent {
             NetworkEvent::ResponseReceived { res, .. } => {
                 write!(f, "NetworkEvent::ResponseReceived({res:?})")
             }
-            NetworkEvent::PeerAdded(peer_id) => {
-                write!(f, "NetworkEvent::PeerAdded({peer_id:?})")
+            NetworkEvent::PeerAdded(peer_id, addrs) => {
+                write!(f, "NetworkEvent::PeerAdded({peer_id:?}, {addrs:?})")
             }
             NetworkEvent::PeerRemoved(peer_id) => {
                 write!(f, "NetworkEvent::PeerRemoved({peer_id:?})")
@@ -174,9 +223,21 @@ impl Debug for NetworkEvent {
             NetworkEvent::NatStatusChanged(nat_status) => {
                 write!(f, "NetworkEvent::NatStatusChanged({nat_status:?})")
             }
-            NetworkEvent::UnverifiedRecord(record) => {
+            NetworkEvent::UnverifiedRecord { record, source } => {
                 let pretty_key = PrettyPrintRecordKey::from(record.key.clone());
-                write!(f, "NetworkEvent::UnverifiedRecord({pretty_key:?})")
+                write!(
+                    f,
+                    "NetworkEvent::UnverifiedRecord({pretty_key:?}, source: {source:?})"
+                )
+            }
+            NetworkEvent::RecordsRecovered {
+                recovered,
+                quarantined,
+            } => {
+                write!(
+                    f,
+                    "NetworkEvent::RecordsRecovered {{ recovered: {recovered}, quarantined: {quarantined} }}"
+                )
             }
         }
     }
@@ -202,8 +263,29 @@ impl SwarmDriver {
                     libp2p::identify::Event::Received { peer_id, info } => {
                         debug!(%peer_id, ?info, "identify: received info");
 
+                        // Peers of other networks, such as testnets discovered on the same LAN,
+                        // are not to be merged into ours.
+                        if !is_same_network(&info.protocol_version, &self.network_config.network_id)
+                        {
+                            warn!(%peer_id, protocol_version = info.protocol_version, "identify: refusing peer of another network");
+                            let _ = self.swarm.disconnect_peer_id(peer_id);
+                        }
+                        // Peers with other network parameters would not agree with us on
+                        // which records each node holds, nor on what they cost.
+                        else if !has_compatible_parameters(
+                            &info.agent_version,
+                            &self.parameters_id,
+                        ) {
+                            warn!(%peer_id, agent_version = info.agent_version, "identify: refusing peer with incompatible network parameters");
+                            let _ = self.swarm.disconnect_peer_id(peer_id);
+                        }
+                        // Peers too far behind or ahead of us would not understand our messages.
+                        else if !self.speaks_common_messages_version(&info.agent_version) {
+                            warn!(%peer_id, agent_version = info.agent_version, "identify: refusing peer speaking no version of the messages in common");
+                            let _ = self.swarm.disconnect_peer_id(peer_id);
+                        }
                         // If we are not local, we care only for peers that we dialed and thus are reachable.
-                        if (self.local || self.dialed_peers.contains(&peer_id))
+                        else if (self.local || self.dialed_peers.contains(&peer_id))
                             && info.agent_version.starts_with(IDENTIFY_AGENT_STR)
                         {
                             let addrs = match self.local {
@@ -223,13 +305,29 @@ impl SwarmDriver {
                                 .unique()
                                 .collect();
 
-                            debug!(%peer_id, ?addrs, "identify: adding addresses to routing table");
-                            for multiaddr in addrs.clone() {
-                                let _routing_update = self
-                                    .swarm
-                                    .behaviour_mut()
-                                    .kademlia
-                                    .add_address(&peer_id, multiaddr);
+                            // Peers penalised for misbehaving are kept out until they recover
+                            if self.peer_reputation.is_deprioritised(&peer_id) {
+                                debug!(%peer_id, "identify: not adding deprioritised peer to routing table");
+                            } else {
+                                debug!(%peer_id, ?addrs, "identify: adding addresses to routing table");
+                                for multiaddr in addrs.clone() {
+                                    let _routing_update = self
+                                        .swarm
+                                        .behaviour_mut()
+                                        .kademlia
+                                        .add_address(&peer_id, multiaddr);
+                                }
+                            }
+
+                            // If the peer offers relay slots, keep it in case we turn out to be private
+                            if !self.is_client
+                                && info
+                                    .protocols
+                                    .iter()
+                                    .any(|protocol| *protocol == relay::HOP_PROTOCOL_NAME)
+                            {
+                                self.relay_manager
+                                    .add_candidate(peer_id, &addrs, &mut self.swarm);
                             }
 
                             // If the peer supports AutoNAT, add it as server
@@ -293,6 +391,15 @@ impl SwarmDriver {
 
                 info!("Local node is listening on {address:?}");
             }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                addresses,
+                reason,
+            } => {
+                info!("Listener {listener_id:?} on {addresses:?} closed: {reason:?}");
+                self.relay_manager
+                    .on_listener_closed(listener_id, &mut self.swarm);
+            }
             SwarmEvent::IncomingConnection { .. } => {}
             SwarmEvent::ConnectionEstablished {
                 peer_id,
@@ -353,14 +460,50 @@ impl SwarmDriver {
                             // to any peers anymore? (E.g., our connections timed out etc)
                             // let all_peers: Vec<_> = self.swarm.connected_peers().cloned().collect();
                             // self.swarm.behaviour_mut().identify.push(all_peers);
+                            self.relay_manager.set_behind_nat(false, &mut self.swarm);
                         }
                         NatStatus::Private => {
-                            // We could just straight out error here. In the future we might try to activate a relay mechanism.
+                            // Reserve a relay slot, so that peers can reach us through a `/p2p-circuit` address.
+                            self.relay_manager.set_behind_nat(true, &mut self.swarm);
                         }
                         NatStatus::Unknown => {}
                     };
                 }
             },
+            SwarmEvent::Behaviour(NodeEvent::RelayServer(event)) => {
+                debug!("Relay server event: {event:?}");
+            }
+            SwarmEvent::Behaviour(NodeEvent::RelayClient(event)) => match *event {
+                relay::client::Event::ReservationReqAccepted {
+                    relay_peer_id,
+                    renewal,
+                    limit,
+                } => {
+                    info!("Relay {relay_peer_id:?} accepted our reservation (renewal: {renewal}, limit: {limit:?})");
+                }
+                relay::client::Event::ReservationReqFailed {
+                    relay_peer_id,
+                    renewal,
+                    error,
+                } => {
+                    warn!("Relay {relay_peer_id:?} failed our reservation (renewal: {renewal}): {error:?}");
+                    self.relay_manager
+                        .on_reservation_failed(relay_peer_id, &mut self.swarm);
+                }
+                other => debug!("Relay client event: {other:?}"),
+            },
+            SwarmEvent::Behaviour(NodeEvent::Dcutr(event)) => match *event {
+                dcutr::Event::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
+                    info!("Hole punched a direct connection to {remote_peer_id:?}");
+                }
+                dcutr::Event::DirectConnectionUpgradeFailed {
+                    remote_peer_id,
+                    error,
+                } => {
+                    debug!("Hole punching to {remote_peer_id:?} failed: {error:?}");
+                }
+                other => debug!("DCUtR event: {other:?}"),
+            },
             other => debug!("SwarmEvent has been ignored: {other:?}"),
         }
         Ok(())
@@ -406,14 +549,14 @@ impl SwarmDriver {
             //     with `ProgressStep::last` to be `true`
             //          `QueryStats::requests` to be 20 (K-Value)
             //          `QueryStats::success` to be over majority of the requests
-            //          `err::NotFound::closest_peers` contains a list of CLOSE_GROUP_SIZE peers
+            //          `err::NotFound::closest_peers` contains a list of close_group_size peers
             //   2, targeting an existing entry
-            //     there will a sequence of (at least CLOSE_GROUP_SIZE) events of
+            //     there will a sequence of (at least close_group_size) events of
             //     `KademliaEvent::OutboundQueryProgressed` to be received
             //     with `QueryStats::end` always being `None`
             //          `ProgressStep::last` all to be `false`
             //          `ProgressStep::count` to be increased with step of 1
-            //             capped and stopped at CLOSE_GROUP_SIZE, may have duplicated counts
+            //             capped and stopped at close_group_size, may have duplicated counts
             //          `PeerRecord::peer` could be None to indicate from self
             //             in which case it always use a duplicated `PregressStep::count`
             //     the sequence will be completed with `FinishedWithNoAdditionalRecord`
@@ -443,7 +586,15 @@ impl SwarmDriver {
             } => {
                 trace!("Query task {id:?} of get_record completed with {stats:?} - {step:?}");
                 if let Some((sender, result_map)) = self.pending_get_record.remove(&id) {
-                    if let Some((record, _)) = result_map.values().next() {
+                    if result_map.len() > 1 {
+                        info!(
+                            "Getting record completed with {} differing copies received",
+                            result_map.len()
+                        );
+                        sender
+                            .send(Err(Error::SplitRecord(split_copies(&result_map))))
+                            .map_err(|_| Error::InternalMsgChannelDropped)?;
+                    } else if let Some((record, _)) = result_map.values().next() {
                         info!(
                             "Getting record {:?} early completed with {:?} copies received",
                             PrettyPrintRecordKey::from(record.key.clone()),
@@ -468,10 +619,11 @@ impl SwarmDriver {
                 stats,
                 step,
             } => {
-                match err {
+                let error = match err {
                     GetRecordError::NotFound { key, closest_peers } => {
                         info!("Query task {id:?} NotFound record {:?} among peers {closest_peers:?}, {stats:?} - {step:?}",
                             PrettyPrintRecordKey::from(key.clone()));
+                        Error::RecordNotFound
                     }
                     GetRecordError::QuorumFailed {
                         key,
@@ -484,18 +636,20 @@ impl SwarmDriver {
                             .collect_vec();
                         info!("Query task {id:?} QuorumFailed record {:?} among peers {peers:?} with quorum {quorum:?}, {stats:?} - {step:?}",
                             PrettyPrintRecordKey::from(key.clone()));
+                        Error::RecordNotFound
                     }
                     GetRecordError::Timeout { key } => {
                         info!(
                             "Query task {id:?} timed out when looking for record {:?}",
                             PrettyPrintRecordKey::from(key.clone())
                         );
+                        Error::GetRecordTimeout
                     }
-                }
+                };
 
                 if let Some((sender, _)) = self.pending_get_record.remove(&id) {
                     sender
-                        .send(Err(Error::RecordNotFound))
+                        .send(Err(error))
                         .map_err(|_| Error::InternalMsgChannelDropped)?;
                 }
             }
@@ -512,12 +666,13 @@ impl SwarmDriver {
             KademliaEvent::RoutingUpdated {
                 peer,
                 is_new_peer,
+                addresses,
                 old_peer,
                 ..
             } => {
                 if is_new_peer {
                     self.log_kbuckets(&peer);
-                    self.send_event(NetworkEvent::PeerAdded(peer));
+                    self.send_event(NetworkEvent::PeerAdded(peer, addresses.into_vec()));
                     let connected_peers = self.swarm.connected_peers().count();
 
                     info!("Connected peers: {connected_peers}");
@@ -536,11 +691,27 @@ impl SwarmDriver {
                 }
                 let _ = self.check_for_change_in_our_close_group();
             }
+            KademliaEvent::InboundRequest {
+                request:
+                    InboundRequest::PutRecord {
+                        source,
+                        record: Some(record),
+                        ..
+                    },
+            } => {
+                // `Record filtering` is enabled, so the records are not stored by Kad.
+                // Records are PUT with `Cmd::PutRecord`, which is rejected when beyond the
+                // peer's limits, whereas Kad acknowledges a PUT before we get to see it.
+                // So none of the records PUT over Kad are accepted.
+                warn!(
+                    "Not accepting record {:?} PUT over Kad by {source:?}, records are PUT with Cmd::PutRecord",
+                    PrettyPrintRecordKey::from(record.key)
+                );
+            }
             KademliaEvent::InboundRequest {
                 request: InboundRequest::PutRecord { .. },
             } => {
-                // Ignored to reduce logging. When `Record filtering` is enabled,
-                // the `record` variable will contain the content for further validation before put.
+                // Ignored to reduce logging.
             }
             KademliaEvent::InboundRequest {
                 request:
@@ -549,7 +720,7 @@ impl SwarmDriver {
                         present_locally,
                     },
             } => {
-                if !present_locally && num_closer_peers < CLOSE_GROUP_SIZE {
+                if !present_locally && num_closer_peers < self.network_config.close_group_size {
                     trace!("InboundRequest::GetRecord doesn't have local record, with {num_closer_peers:?} closer_peers");
                 }
             }
@@ -574,13 +745,14 @@ impl SwarmDriver {
     }
 
     // Check for changes in our close group
-    fn check_for_change_in_our_close_group(&mut self) -> Option<Vec<PeerId>> {
+    pub(super) fn check_for_change_in_our_close_group(&mut self) -> Option<Vec<PeerId>> {
         let new_closest_peers = {
             let all_peers = self.get_all_local_peers();
+            let close_group_size = self.network_config.close_group_size;
             sort_peers_by_address(
                 all_peers,
                 &NetworkAddress::from_peer(self.self_peer_id),
-                CLOSE_GROUP_SIZE,
+                close_group_size,
             )
             .ok()?
         };
@@ -621,7 +793,7 @@ impl SwarmDriver {
         Some(())
     }
 
-    fn log_kbuckets(&mut self, peer: &PeerId) {
+    pub(super) fn log_kbuckets(&mut self, peer: &PeerId) {
         let distance = NetworkAddress::from_peer(self.self_peer_id)
             .distance(&NetworkAddress::from_peer(*peer));
         info!("Peer {peer:?} has a {:?} distance to us", distance.ilog2());
@@ -643,9 +815,9 @@ impl SwarmDriver {
     }
 
     // Completes when any of the following condition reaches first:
-    // 1, Return whenever reached majority of CLOSE_GROUP_SIZE
-    // 2, In case of split, return with NotFound,
-    //    whenever `ProgressStep::count` hits CLOSE_GROUP_SIZE
+    // 1, Return whenever reached majority of the close group
+    // 2, In case of split, return with the differing copies, or NotFound if all are the same,
+    //    whenever `ProgressStep::count` hits the close group size
     fn accumulate_get_record_ok(
         &mut self,
         query_id: QueryId,
@@ -670,16 +842,22 @@ impl SwarmDriver {
                     peer_list
                 };
 
-            let result = if peer_list.len() >= close_group_majority() {
-                Some(Ok(peer_record.record.clone()))
-            } else if usize::from(count) >= CLOSE_GROUP_SIZE {
-                Some(Err(Error::RecordNotFound))
+            let has_majority = peer_list.len() >= self.network_config.close_group_majority();
+            let record = peer_record.record.clone();
+            let _ = result_map.insert(record_content_hash, (peer_record.record, peer_list));
+
+            let result = if has_majority {
+                Some(Ok(record))
+            } else if usize::from(count) >= self.network_config.close_group_size {
+                if result_map.len() > 1 {
+                    Some(Err(Error::SplitRecord(split_copies(&result_map))))
+                } else {
+                    Some(Err(Error::RecordNotFound))
+                }
             } else {
                 None
             };
 
-            let _ = result_map.insert(record_content_hash, (peer_record.record, peer_list));
-
             if let Some(result) = result {
                 let _ = sender.send(result);
                 self.try_update_self_for_split_record(result_map);
@@ -703,9 +881,23 @@ impl SwarmDriver {
             return;
         }
 
+        // Copies legitimately diverge while they propagate, so only the holders of a copy
+        // failing validation are penalised, and such copies don't take part in the vote.
+        let (valid_copies, invalid_copies): (Vec<_>, Vec<_>) = result_map
+            .iter()
+            .partition(|(_, (record, _))| DiskBackedRecordStore::is_record_intact(record));
+        let invalid_holders: Vec<_> = invalid_copies
+            .into_iter()
+            .flat_map(|(_, (_, peer_list))| peer_list.iter().copied())
+            .filter(|peer_id| *peer_id != self.self_peer_id)
+            .collect();
+        for peer_id in invalid_holders {
+            self.penalise_peer(peer_id, PeerMisbehaviour::InvalidRecord);
+        }
+
         let mut highest_count = 0;
         let mut highest_records = BTreeMap::new();
-        for (xor_name, (record, peer_list)) in &result_map {
+        for (xor_name, (record, peer_list)) in valid_copies {
             if peer_list.len() > highest_count {
                 // Cleanup whenever there is a record got more votes
                 highest_records = BTreeMap::new();
@@ -741,3 +933,12 @@ fn endpoint_str(endpoint: &libp2p::core::ConnectedPoint) -> String {
         }
     }
 }
+
+/// The differing copies of a split record, in the order of their content hash.
+fn split_copies(result_map: &GetRecordResultMap) -> Vec<Record> {
+    result_map
+        .iter()
+        .sorted_by_key(|(content_hash, _)| **content_hash)
+        .map(|(_, (record, _))| record.clone())
+        .collect()
+}
@@ -13,14 +13,26 @@ mod circular_vec;
 mod cmd;
 mod error;
 mod event;
+mod messages_version;
 mod msg;
+mod network_config;
+mod peer_reputation;
+mod record_storage;
 mod record_store;
+mod relay_manager;
 mod replication_fetcher;
+mod request_limiter;
 
 pub use self::{
     cmd::SwarmLocalState,
     error::Error,
     event::{MsgResponder, NetworkEvent},
+    msg::MsgCodec,
+    network_config::{NetworkConfig, NetworkConfigArgs},
+    peer_reputation::{PeerMisbehaviour, PeerScore},
+    record_storage::RecordStorageKind,
+    record_store::{DiskBackedRecordStore, RecordStoreOptions},
+    request_limiter::RequestLimits,
 };
 
 use self::{
@@ -28,19 +40,25 @@ use self::{
     cmd::SwarmCmd,
     error::Result,
     event::{GetRecordResultMap, NodeBehaviour},
-    record_store::{
-        DiskBackedRecordStore, DiskBackedRecordStoreConfig, REPLICATION_INTERVAL_LOWER_BOUND,
-        REPLICATION_INTERVAL_UPPER_BOUND,
-    },
+    messages_version::{advertised_versions, messages_protocols},
+    peer_reputation::PeerReputation,
+    record_store::{DiskBackedRecordStoreConfig, WriteOutcome},
+    relay_manager::{relay_server_config, RelayManager},
     replication_fetcher::ReplicationFetcher,
+    request_limiter::RequestLimiter,
 };
+#[cfg(feature = "quic")]
+use futures::future::Either;
 use futures::{future::select_all, StreamExt};
 use itertools::Itertools;
+#[cfg(feature = "quic")]
+use libp2p::core::muxing::StreamMuxerBox;
 #[cfg(feature = "local-discovery")]
 use libp2p::mdns;
 use libp2p::{
+    connection_limits::ConnectionLimits,
     identity::Keypair,
-    kad::{KBucketKey, Kademlia, KademliaConfig, QueryId, Record, RecordKey},
+    kad::{KBucketKey, Kademlia, KademliaConfig, KademliaStoreInserts, QueryId, Record, RecordKey},
     multiaddr::Protocol,
     request_response::{self, Config as RequestResponseConfig, ProtocolSupport, RequestId},
     swarm::{behaviour::toggle::Toggle, StreamProtocol, Swarm, SwarmBuilder},
@@ -49,15 +67,19 @@ use libp2p::{
 use rand::Rng;
 use sn_dbc::Token;
 use sn_protocol::{
-    messages::{Query, QueryResponse, Request, Response},
+    messages::{
+        Cmd, CmdResponse, Query, QueryResponse, Request, Response, StoreCostQuote,
+        SUPPORTED_MESSAGES_VERSIONS,
+    },
     NetworkAddress, PrettyPrintRecordKey,
 };
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
     num::NonZeroUsize,
+    ops::RangeInclusive,
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tokio::sync::{mpsc, oneshot};
 use tracing::warn;
@@ -65,16 +87,21 @@ use tracing::warn;
 /// The maximum number of peers to return in a `GetClosestPeers` response.
 /// This is the group size used in safe network protocol to be responsible for
 /// an item in the network.
-/// The peer should be present among the CLOSE_GROUP_SIZE if we're fetching the close_group(peer)
-pub const CLOSE_GROUP_SIZE: usize = 8;
+#[deprecated(note = "the close group size is configured with `NetworkConfig::close_group_size`")]
+pub const CLOSE_GROUP_SIZE: usize = network_config::DEFAULT_CLOSE_GROUP_SIZE;
 
 /// What is the largest packet to send over the network.
 /// Records larger than this will be rejected.
-// TODO: revisit once utxo is in
-pub const MAX_PACKET_SIZE: usize = 1024 * 1024 * 2; // the chunk size is 1mb, so should be higher than that to prevent failures
+#[deprecated(note = "the packet size is configured with `NetworkConfig::max_packet_size`")]
+pub const MAX_PACKET_SIZE: usize = network_config::DEFAULT_MAX_PACKET_SIZE;
+
+/// Majority of a given group (i.e. > 1/2).
+#[deprecated(note = "use `NetworkConfig::close_group_majority`")]
+#[inline]
+pub const fn close_group_majority() -> usize {
+    network_config::DEFAULT_CLOSE_GROUP_SIZE / 2 + 1
+}
 
-// Timeout for requests sent/received through the request_response behaviour.
-const REQUEST_TIMEOUT_DEFAULT_S: Duration = Duration::from_secs(30);
 // Sets the keep-alive timeout of idle connections.
 const CONNECTION_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -83,12 +110,9 @@ pub const IDENTIFY_AGENT_STR: &str = "safe/node/";
 
 /// The suffix is the version of the node.
 const SN_NODE_VERSION_STR: &str = concat!("safe/node/", env!("CARGO_PKG_VERSION"));
-/// / first version for the req/response protocol
-const REQ_RESPONSE_VERSION_STR: &str = concat!("/safe/node/", env!("CARGO_PKG_VERSION"));
 
 /// The suffix is the version of the client.
 const IDENTIFY_CLIENT_VERSION_STR: &str = concat!("safe/client/", env!("CARGO_PKG_VERSION"));
-const IDENTIFY_PROTOCOL_STR: &str = concat!("safe/", env!("CARGO_PKG_VERSION"));
 
 /// Duration to wait for verification
 const REVERIFICATION_WAIT_TIME_S: std::time::Duration = std::time::Duration::from_secs(3);
@@ -99,11 +123,9 @@ const VERIFICATION_ATTEMPTS: usize = 30;
 const PUT_RECORD_RETRIES: usize = 3;
 
 const NETWORKING_CHANNEL_SIZE: usize = 10_000;
-/// Majority of a given group (i.e. > 1/2).
-#[inline]
-pub const fn close_group_majority() -> usize {
-    CLOSE_GROUP_SIZE / 2 + 1
-}
+
+/// How often the state kept on peers, such as their blocks and request allowances, is cleaned up
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(60);
 
 type PendingGetClosest = HashMap<QueryId, (oneshot::Sender<HashSet<PeerId>>, HashSet<PeerId>)>;
 type PendingGetRecord = HashMap<QueryId, (oneshot::Sender<Result<Record>>, GetRecordResultMap)>;
@@ -130,6 +152,19 @@ pub struct SwarmDriver {
     /// Perform initial kad bootstrap process on adding the first peer
     bootstrap_done: bool,
     is_client: bool,
+    /// Reserves a relay slot when we are found to be behind a NAT
+    relay_manager: RelayManager,
+    /// Scores the peers on their misbehaviours, deprioritising or blocking the worst ones
+    peer_reputation: PeerReputation,
+    /// Rejects the requests of the peers beyond their limits
+    request_limiter: RequestLimiter,
+    network_config: NetworkConfig,
+    /// Advertised to the peers, which must share it for us to work with them.
+    parameters_id: String,
+    /// The versions of the messages we speak.
+    messages_versions: RangeInclusive<u32>,
+    /// The outcomes of the record store's writes to storage, reported back to it.
+    record_write_outcomes: mpsc::UnboundedReceiver<WriteOutcome>,
 }
 
 impl SwarmDriver {
@@ -145,16 +180,45 @@ impl SwarmDriver {
     ///
     /// # Errors
     ///
-    /// Returns an error if there is a problem initializing the mDNS behaviour.
+    /// Returns an error if there is a problem initializing the mDNS behaviour,
+    /// or opening the record storage.
     pub fn new(
         keypair: Keypair,
         addr: SocketAddr,
         local: bool,
         root_dir: PathBuf,
+        network_config: NetworkConfig,
+        store_options: RecordStoreOptions,
+        request_limits: RequestLimits,
+    ) -> Result<(Network, mpsc::Receiver<NetworkEvent>, Self)> {
+        Self::new_with_messages_versions(
+            keypair,
+            addr,
+            local,
+            root_dir,
+            network_config,
+            store_options,
+            request_limits,
+            SUPPORTED_MESSAGES_VERSIONS,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Same as `new` API but speaking the provided versions of the messages only,
+    /// as the nodes of other releases do.
+    pub(crate) fn new_with_messages_versions(
+        keypair: Keypair,
+        addr: SocketAddr,
+        local: bool,
+        root_dir: PathBuf,
+        network_config: NetworkConfig,
+        store_options: RecordStoreOptions,
+        request_limits: RequestLimits,
+        messages_versions: RangeInclusive<u32>,
     ) -> Result<(Network, mpsc::Receiver<NetworkEvent>, Self)> {
-        // get a random integer between REPLICATION_INTERVAL_LOWER_BOUND and REPLICATION_INTERVAL_UPPER_BOUND
-        let replication_interval = rand::thread_rng()
-            .gen_range(REPLICATION_INTERVAL_LOWER_BOUND..REPLICATION_INTERVAL_UPPER_BOUND);
+        // get a random interval between the configured bounds
+        let replication_interval =
+            rand::thread_rng().gen_range(network_config.replication_interval_range());
 
         let mut kad_cfg = KademliaConfig::default();
         let _ = kad_cfg
@@ -166,20 +230,20 @@ impl SwarmDriver {
             // how often a node will publish a record key, aka telling the others it exists
             // Set to `None` to ensure periodic publish disabled.
             .set_publication_interval(None)
-            // 1mb packet size
-            .set_max_packet_size(MAX_PACKET_SIZE)
+            .set_max_packet_size(network_config.max_packet_size)
             // How many nodes _should_ store data.
             .set_replication_factor(
-                NonZeroUsize::new(CLOSE_GROUP_SIZE).ok_or_else(|| Error::InvalidCloseGroupSize)?,
+                NonZeroUsize::new(network_config.close_group_size)
+                    .ok_or_else(|| Error::InvalidCloseGroupSize)?,
             )
             .set_query_timeout(Duration::from_secs(5 * 60))
             // Require iterative queries to use disjoint paths for increased resiliency in the presence of potentially adversarial nodes.
             .disjoint_query_paths(true)
             // Records never expire
             .set_record_ttl(None)
-            // Emit PUT events for validation prior to insertion into the RecordStore.
-            // This is no longer needed as the record_storage::put now can carry out validation.
-            // .set_record_filtering(KademliaStoreInserts::FilterBoth)
+            // Emit PUT events prior to insertion into the RecordStore, so that the peer which
+            // sent the record is known, should it fail validation.
+            .set_record_filtering(KademliaStoreInserts::FilterBoth)
             // Disable provider records publication job
             .set_provider_publication_interval(None);
 
@@ -190,18 +254,34 @@ impl SwarmDriver {
             local,
             false,
             replication_interval,
+            network_config,
+            store_options,
+            request_limits,
             None,
             ProtocolSupport::Full,
-            SN_NODE_VERSION_STR.to_string(),
+            messages_versions,
+            SN_NODE_VERSION_STR,
         )?;
 
         // Listen on the provided address
-        let addr = Multiaddr::from(addr.ip()).with(Protocol::Tcp(addr.port()));
+        let tcp_addr = Multiaddr::from(addr.ip()).with(Protocol::Tcp(addr.port()));
         let _listener_id = swarm_driver
             .swarm
-            .listen_on(addr)
+            .listen_on(tcp_addr)
             .expect("Failed to listen on the provided address");
 
+        // Listen for QUIC connections on the same port, over UDP
+        #[cfg(feature = "quic")]
+        {
+            let quic_addr = Multiaddr::from(addr.ip())
+                .with(Protocol::Udp(addr.port()))
+                .with(Protocol::QuicV1);
+            let _listener_id = swarm_driver
+                .swarm
+                .listen_on(quic_addr)
+                .expect("Failed to listen on the provided QUIC address");
+        }
+
         Ok((network, events_receiver, swarm_driver))
     }
 
@@ -209,19 +289,20 @@ impl SwarmDriver {
     pub fn new_client(
         local: bool,
         request_timeout: Option<Duration>,
+        network_config: NetworkConfig,
     ) -> Result<(Network, mpsc::Receiver<NetworkEvent>, Self)> {
         // Create a Kademlia behaviour for client mode, i.e. set req/resp protocol
         // to outbound-only mode and don't listen on any address
         let mut kad_cfg = KademliaConfig::default(); // default query timeout is 60 secs
 
-        // 1mb packet size
         let _ = kad_cfg
-            .set_max_packet_size(MAX_PACKET_SIZE)
+            .set_max_packet_size(network_config.max_packet_size)
             // Require iterative queries to use disjoint paths for increased resiliency in the presence of potentially adversarial nodes.
             .disjoint_query_paths(true)
             // How many nodes _should_ store data.
             .set_replication_factor(
-                NonZeroUsize::new(CLOSE_GROUP_SIZE).ok_or_else(|| Error::InvalidCloseGroupSize)?,
+                NonZeroUsize::new(network_config.close_group_size)
+                    .ok_or_else(|| Error::InvalidCloseGroupSize)?,
             );
 
         Self::with(
@@ -232,17 +313,25 @@ impl SwarmDriver {
             true,
             // Nonsense interval for the client which never replicates
             Duration::from_secs(1000),
+            network_config,
+            // Clients do not store records, and may share the temp dir with other clients
+            RecordStoreOptions {
+                storage_kind: RecordStorageKind::FlatFile,
+                ..Default::default()
+            },
+            // Clients do not serve requests
+            RequestLimits::default(),
             request_timeout,
             ProtocolSupport::Outbound,
-            IDENTIFY_CLIENT_VERSION_STR.to_string(),
+            SUPPORTED_MESSAGES_VERSIONS,
+            IDENTIFY_CLIENT_VERSION_STR,
         )
     }
 
     /// Sends an event after pushing it off thread so as to be non-blocking
     /// this is a wrapper around the `mpsc::Sender::send` call
     fn send_event(&self, event: NetworkEvent) {
-        let event_sender = self.event_sender.clone();
-        let capacity = event_sender.capacity();
+        let capacity = self.event_sender.capacity();
 
         if capacity == 0 {
             warn!(
@@ -254,6 +343,12 @@ impl SwarmDriver {
             return;
         }
 
+        self.queue_event(event);
+    }
+
+    /// Sends the event even if the channel is full, waiting for room in it.
+    fn queue_event(&self, event: NetworkEvent) {
+        let event_sender = self.event_sender.clone();
         // push the event off thread so as to be non-blocking
         let _handle = tokio::spawn(async move {
             if let Err(error) = event_sender.send(event).await {
@@ -267,30 +362,53 @@ impl SwarmDriver {
     fn with(
         root_dir_path: PathBuf,
         keypair: Keypair,
-        kad_cfg: KademliaConfig,
+        mut kad_cfg: KademliaConfig,
         local: bool,
         is_client: bool,
         replication_interval: Duration,
+        network_config: NetworkConfig,
+        store_options: RecordStoreOptions,
+        request_limits: RequestLimits,
         request_response_timeout: Option<Duration>,
         req_res_protocol: ProtocolSupport,
-        identify_version: String,
+        messages_versions: RangeInclusive<u32>,
+        identify_version: &str,
     ) -> Result<(Network, mpsc::Receiver<NetworkEvent>, Self)> {
+        network_config.validate()?;
         let peer_id = PeerId::from(keypair.public());
         info!("Node (PID: {}) with PeerId: {peer_id}", std::process::id());
         info!("PeerId: {peer_id} has replication interval of {replication_interval:?}");
+        info!("Using the network parameters: {network_config:?}");
+
+        // The protocols carry the network id, so that peers of other networks can't talk to us
+        let network_id = &network_config.network_id;
+        let stream_protocol = |protocol: String| {
+            StreamProtocol::try_from_owned(protocol)
+                .map_err(|err| Error::InvalidNetworkConfig(err.to_string()))
+        };
+        // One protocol per version of the messages, so that the nodes of the previous
+        // release are still understood while the network upgrades.
+        let req_response_protocols = messages_protocols(network_id, &messages_versions)
+            .into_iter()
+            .map(|protocol| Ok((stream_protocol(protocol)?, req_res_protocol.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        let _ = kad_cfg.set_protocol_names(vec![stream_protocol(format!(
+            "/safe/{network_id}/kad/1.0.0"
+        ))?]);
+        let identify_protocol = format!("safe/{network_id}/{}", env!("CARGO_PKG_VERSION"));
 
         // RequestResponse Behaviour
         let request_response = {
             let mut cfg = RequestResponseConfig::default();
             let _ = cfg
-                .set_request_timeout(request_response_timeout.unwrap_or(REQUEST_TIMEOUT_DEFAULT_S))
+                .set_request_timeout(
+                    request_response_timeout.unwrap_or(network_config.request_timeout()),
+                )
                 .set_connection_keep_alive(CONNECTION_KEEP_ALIVE_TIMEOUT);
 
-            request_response::cbor::Behaviour::new(
-                [(
-                    StreamProtocol::new(REQ_RESPONSE_VERSION_STR),
-                    req_res_protocol,
-                )],
+            request_response::Behaviour::with_codec(
+                MsgCodec::new(network_config.max_packet_size),
+                req_response_protocols,
                 cfg,
             )
         };
@@ -298,7 +416,7 @@ impl SwarmDriver {
         let (network_event_sender, network_event_receiver) = mpsc::channel(NETWORKING_CHANNEL_SIZE);
 
         // Kademlia Behaviour
-        let kademlia = {
+        let (kademlia, record_write_outcomes) = {
             // Configures the disk_store to store records under the provided path and increase the max record size
             let storage_dir_path = root_dir_path.join("record_store");
             if let Err(error) = std::fs::create_dir_all(&storage_dir_path) {
@@ -309,20 +427,39 @@ impl SwarmDriver {
             }
 
             let store_cfg = DiskBackedRecordStoreConfig {
-                max_value_bytes: MAX_PACKET_SIZE, // TODO, does this need to be _less_ than MAX_PACKET_SIZE
-                storage_dir: storage_dir_path,
+                max_value_bytes: network_config.max_packet_size, // TODO, does this need to be _less_ than the max packet size
+                storage_dir: storage_dir_path.clone(),
                 replication_interval,
-                ..Default::default()
+                storage_kind: store_options.storage_kind,
+                max_records: store_options.max_records,
+                max_storage_bytes: store_options.max_storage_bytes,
+                network_max_records: network_config.price_scale_records,
+                puts_per_price_step: network_config.puts_per_price_step,
             };
 
-            Kademlia::with_config(
+            let open_error = |source| Error::FailedToOpenRecordStorage {
+                path: storage_dir_path.clone(),
+                storage_kind: store_options.storage_kind,
+                source,
+            };
+            let mut store = DiskBackedRecordStore::with_config(
                 peer_id,
-                DiskBackedRecordStore::with_config(
-                    peer_id,
-                    store_cfg,
-                    Some(network_event_sender.clone()),
-                ),
-                kad_cfg,
+                store_cfg,
+                Some(network_event_sender.clone()),
+            )
+            .map_err(open_error)?;
+
+            // Clients don't hold records, so only nodes pick up the ones stored before a restart
+            if !is_client {
+                store.restore_records().map_err(open_error)?;
+            }
+            let Some(record_write_outcomes) = store.take_write_outcomes() else {
+                unreachable!("The write outcomes of a newly opened store are not taken yet");
+            };
+
+            (
+                Kademlia::with_config(peer_id, store, kad_cfg),
+                record_write_outcomes,
             )
         };
 
@@ -338,27 +475,53 @@ impl SwarmDriver {
         #[cfg(feature = "local-discovery")]
         let mdns = mdns::tokio::Behaviour::new(mdns_config, peer_id)?;
 
-        // Identify Behaviour
+        // Identify Behaviour, advertising the versions of the messages we speak and our network
+        // parameters after our version
+        let parameters_id = network_config.parameters_id();
         let identify = {
-            let cfg =
-                libp2p::identify::Config::new(IDENTIFY_PROTOCOL_STR.to_string(), keypair.public())
-                    .with_agent_version(identify_version);
+            let cfg = libp2p::identify::Config::new(identify_protocol, keypair.public())
+                .with_agent_version(format!(
+                    "{identify_version}/{}/{parameters_id}",
+                    advertised_versions(&messages_versions)
+                ));
             libp2p::identify::Behaviour::new(cfg)
         };
 
+        // The relay client lets us listen and dial through relay circuits,
+        // which get the same upgrades as the direct TCP connections.
+        let (relay_transport, relay_client) = libp2p::relay::client::new(peer_id);
+
         // Transport
-        let mut transport = libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default())
+        let tcp_transport = relay_transport
+            .or_transport(libp2p::tcp::tokio::Transport::new(
+                libp2p::tcp::Config::default(),
+            ))
             .upgrade(libp2p::core::upgrade::Version::V1)
             .authenticate(
                 libp2p::noise::Config::new(&keypair)
                     .expect("Signing libp2p-noise static DH keypair failed."),
             )
-            .multiplex(libp2p::yamux::Config::default())
-            .boxed();
+            .multiplex(libp2p::yamux::Config::default());
+
+        // QUIC brings its own encryption and multiplexing, so it is combined with the upgraded TCP.
+        #[cfg(feature = "quic")]
+        let mut transport = {
+            let quic_transport =
+                libp2p::quic::tokio::Transport::new(libp2p::quic::Config::new(&keypair));
+            tcp_transport
+                .or_transport(quic_transport)
+                .map(|either_output, _| match either_output {
+                    Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+                    Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+                })
+                .boxed()
+        };
+        #[cfg(not(feature = "quic"))]
+        let mut transport = tcp_transport.boxed();
 
         if !local {
             debug!("Preventing non-global dials");
-            // Wrap TCP in a transport that prevents dialing local addresses.
+            // Wrap the transport in one that prevents dialing local addresses.
             transport = libp2p::core::transport::global_only::Transport::new(transport).boxed();
         }
 
@@ -382,6 +545,17 @@ impl SwarmDriver {
         };
         let autonat = Toggle::from(autonat);
 
+        // Only nodes offer relay slots, clients are not meant to be dialed.
+        let relay_server = if !is_client {
+            Some(libp2p::relay::Behaviour::new(
+                peer_id,
+                relay_server_config(network_config.max_packet_size),
+            ))
+        } else {
+            None
+        };
+        let relay_server = Toggle::from(relay_server);
+
         let behaviour = NodeBehaviour {
             request_response,
             kademlia,
@@ -389,6 +563,14 @@ impl SwarmDriver {
             #[cfg(feature = "local-discovery")]
             mdns,
             autonat,
+            relay_server,
+            relay_client,
+            dcutr: libp2p::dcutr::Behaviour::new(peer_id),
+            blocked_peers: Default::default(),
+            connection_limits: libp2p::connection_limits::Behaviour::new(
+                ConnectionLimits::default()
+                    .with_max_established_per_peer(Some(request_limits.max_connections_per_peer)),
+            ),
         };
         let swarm = SwarmBuilder::with_tokio_executor(transport, behaviour, peer_id).build();
 
@@ -411,6 +593,13 @@ impl SwarmDriver {
             close_group: Default::default(),
             bootstrap_done: false,
             is_client,
+            relay_manager: Default::default(),
+            peer_reputation: Default::default(),
+            request_limiter: RequestLimiter::new(request_limits),
+            network_config: network_config.clone(),
+            parameters_id,
+            messages_versions,
+            record_write_outcomes,
         };
 
         Ok((
@@ -418,6 +607,7 @@ impl SwarmDriver {
                 swarm_cmd_sender,
                 peer_id,
                 root_dir_path,
+                network_config,
                 keypair,
             },
             network_event_receiver,
@@ -433,6 +623,7 @@ impl SwarmDriver {
     /// and command receiver messages, ensuring efficient handling of multiple
     /// asynchronous tasks.
     pub async fn run(mut self) {
+        let mut housekeeping_interval = tokio::time::interval(HOUSEKEEPING_INTERVAL);
         loop {
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
@@ -448,13 +639,20 @@ impl SwarmDriver {
                     },
                     None =>  continue,
                 },
+                Some(outcome) = self.record_write_outcomes.recv() => {
+                    self.swarm.behaviour_mut().kademlia.store_mut().handle_write_outcome(outcome);
+                },
+                _ = housekeeping_interval.tick() => {
+                    self.unblock_expired_peers();
+                    self.request_limiter.prune_idle_peers();
+                },
             }
         }
     }
 }
 
 /// Sort the provided peers by their distance to the given `NetworkAddress`.
-/// Return with the closest expected number of entries if has.
+/// Return with the closest expected number of entries, erroring if there are fewer peers.
 pub fn sort_peers_by_address(
     peers: Vec<PeerId>,
     address: &NetworkAddress,
@@ -464,7 +662,7 @@ pub fn sort_peers_by_address(
 }
 
 /// Sort the provided peers by their distance to the given `KBucketKey`.
-/// Return with the closest expected number of entries if has.
+/// Return with the closest expected number of entries, erroring if there are fewer peers.
 pub fn sort_peers_by_key<T>(
     mut peers: Vec<PeerId>,
     key: &KBucketKey<T>,
@@ -478,11 +676,11 @@ pub fn sort_peers_by_key<T>(
     });
     let peers: Vec<PeerId> = peers.iter().take(expected_entries).cloned().collect();
 
-    if CLOSE_GROUP_SIZE > peers.len() {
+    if expected_entries > peers.len() {
         warn!("Not enough peers in the k-bucket to satisfy the request");
         return Err(Error::NotEnoughPeers {
             found: peers.len(),
-            required: CLOSE_GROUP_SIZE,
+            required: expected_entries,
         });
     }
     Ok(peers)
@@ -494,6 +692,7 @@ pub struct Network {
     pub swarm_cmd_sender: mpsc::Sender<SwarmCmd>,
     pub peer_id: PeerId,
     pub root_dir_path: PathBuf,
+    pub network_config: NetworkConfig,
     keypair: Keypair,
 }
 
@@ -503,6 +702,11 @@ impl Network {
         self.keypair.sign(msg).map_err(Error::from)
     }
 
+    /// Returns the node's public key, from which its `PeerId` is derived, encoded in protobuf.
+    pub fn encoded_public_key(&self) -> Vec<u8> {
+        self.keypair.public().encode_protobuf()
+    }
+
     ///  Listen for incoming connections on the given address.
     pub async fn start_listening(&self, addr: Multiaddr) -> Result<()> {
         let (sender, receiver) = oneshot::channel();
@@ -631,7 +835,7 @@ impl Network {
         &self,
         record_address: NetworkAddress,
         any_cost_will_do: bool,
-    ) -> Result<Token> {
+    ) -> Result<(Token, Vec<StoreCostQuote>)> {
         let (sender, receiver) = oneshot::channel();
         debug!("Attempting to get store cost");
         // first we need to get CLOSE_GROUP of the dbc_id
@@ -646,22 +850,38 @@ impl Network {
             .into_iter()
             .collect_vec();
 
-        let request = Request::Query(Query::GetStoreCost(record_address));
+        let request = Request::Query(Query::GetStoreCost(record_address.clone()));
         let responses = self
             .send_and_get_responses(close_nodes, &request, true)
             .await;
 
-        // loop over responses, generating an avergae fee and storing all responses along side
+        // loop over responses, generating an avergae fee and storing all valid quotes along side
         let mut all_costs = vec![];
+        let mut quotes = vec![];
+        let now = SystemTime::now();
         for response in responses.into_iter().flatten() {
-            if let Response::Query(QueryResponse::GetStoreCost(Ok(cost))) = response {
-                all_costs.push(cost);
+            if let Response::Query(QueryResponse::GetStoreCost(Ok(quote))) = response {
+                if quote.address != record_address || quote.has_expired(now) {
+                    warn!("Ignoring store cost quote from {:?} which is not for {record_address:?} or has expired", quote.peer);
+                    continue;
+                }
+                if let Err(err) = quote.verify() {
+                    warn!("Ignoring store cost quote: {err}");
+                    continue;
+                }
+                all_costs.push(quote.cost);
+                quotes.push(quote);
             } else {
                 println!("other response was {:?}", response);
             }
         }
 
-        get_fee_from_store_cost_quotes(&mut all_costs, any_cost_will_do)
+        let fee = get_fee_from_store_cost_quotes(
+            &mut all_costs,
+            self.network_config.close_group_size,
+            any_cost_will_do,
+        )?;
+        Ok((fee, quotes))
     }
 
     /// Get the Record from the network
@@ -746,6 +966,29 @@ impl Network {
         Err(Error::RecordNotFound)
     }
 
+    /// Get all the copies of the Record held by its holders, in a single attempt.
+    /// No copies are returned when the Record is not found, while all of them are returned
+    /// when the holders disagree on it. Failing to reach the holders in time is an error.
+    pub async fn get_record_copies_from_network(&self, key: RecordKey) -> Result<Vec<Record>> {
+        debug!(
+            "Getting the copies of record {:?}",
+            PrettyPrintRecordKey::from(key.clone()),
+        );
+
+        let (sender, receiver) = oneshot::channel();
+        self.send_swarm_cmd(SwarmCmd::GetNetworkRecord { key, sender })?;
+
+        match receiver
+            .await
+            .map_err(|_e| Error::InternalMsgChannelDropped)?
+        {
+            Ok(record) | Err(Error::RecordNotEnoughCopies(record)) => Ok(vec![record]),
+            Err(Error::SplitRecord(records)) => Ok(records),
+            Err(Error::RecordNotFound) => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    }
+
     /// Get the cost of storing the next record from the network
     pub async fn get_local_storecost(&self) -> Result<Token> {
         let (sender, receiver) = oneshot::channel();
@@ -801,13 +1044,18 @@ impl Network {
             record.value.len()
         );
         let the_record = record.clone();
-        // Waiting for a response to avoid flushing to network too quick that causing choke
-        let (sender, receiver) = oneshot::channel();
-        self.send_swarm_cmd(SwarmCmd::PutRecord {
-            record: record.clone(),
-            sender,
-        })?;
-        let response = receiver.await?;
+        let record_key = PrettyPrintRecordKey::from(record.key.clone());
+        let request = Request::Cmd(Cmd::PutRecord {
+            address: NetworkAddress::from_record_key(record.key.clone()),
+            value: record.value.clone(),
+        });
+        // Waiting for the responses to avoid flushing to network too quick that causing choke
+        let responses = self.client_send_to_closest(&request, true).await?;
+        accepted_by_majority(
+            record_key,
+            responses,
+            self.network_config.close_group_majority(),
+        )?;
 
         if verify_store {
             // Verify the record is stored, requiring re-attempts
@@ -823,7 +1071,7 @@ impl Network {
                 })?;
         }
 
-        response
+        Ok(())
     }
 
     /// Put `Record` to the local RecordStore
@@ -837,6 +1085,15 @@ impl Network {
         self.send_swarm_cmd(SwarmCmd::PutLocalRecord { record })
     }
 
+    /// Remove a `Record` from the local RecordStore
+    pub fn remove_local_record(&self, key: RecordKey) -> Result<()> {
+        debug!(
+            "Removing Record locally, for {:?}",
+            PrettyPrintRecordKey::from(key.clone())
+        );
+        self.send_swarm_cmd(SwarmCmd::RemoveLocalRecord { key })
+    }
+
     /// Returns true if a RecordKey is present locally in the RecordStore
     pub async fn is_key_present_locally(&self, key: &RecordKey) -> Result<bool> {
         let (sender, receiver) = oneshot::channel();
@@ -869,6 +1126,22 @@ impl Network {
         self.send_swarm_cmd(SwarmCmd::AddKeysToReplicationFetcher { peer, keys })
     }
 
+    /// Penalises the peer for the misbehaviour, lowering its score.
+    /// Peers scoring too low are removed from the routing table, then blocked for a while.
+    pub fn report_peer(&self, peer: PeerId, misbehaviour: PeerMisbehaviour) -> Result<()> {
+        self.send_swarm_cmd(SwarmCmd::ReportPeer { peer, misbehaviour })
+    }
+
+    /// Returns the scores of the peers penalised for misbehaving, the worst first.
+    pub async fn get_peer_scores(&self) -> Result<Vec<PeerScore>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_swarm_cmd(SwarmCmd::GetPeerScores { sender })?;
+
+        receiver
+            .await
+            .map_err(|_e| Error::InternalMsgChannelDropped)
+    }
+
     /// Send `Request` to the given `PeerId` and await for the response. If `self` is the recipient,
     /// then the `Request` is forwarded to itself and handled, and a corresponding `Response` is created
     /// and returned to itself. Hence the flow remains the same and there is no branching at the upper
@@ -940,12 +1213,13 @@ impl Network {
         })?;
         let k_bucket_peers = receiver.await?;
 
-        // Count self in if among the CLOSE_GROUP_SIZE closest and sort the result
+        // Count self in if among the close group closest and sort the result
         let mut closest_peers: Vec<_> = k_bucket_peers.into_iter().collect();
         if !client {
             closest_peers.push(self.peer_id);
         }
-        sort_peers_by_address(closest_peers, key, CLOSE_GROUP_SIZE)
+        let close_group_size = self.network_config.close_group_size;
+        sort_peers_by_address(closest_peers, key, close_group_size)
     }
 
     /// Send a `Request` to the provided set of peers and wait for their responses concurrently.
@@ -987,10 +1261,11 @@ impl Network {
 /// Given `all_costs` it will return the CLOSE_GROUP majority cost.
 fn get_fee_from_store_cost_quotes(
     all_costs: &mut Vec<Token>,
+    close_group_size: usize,
     any_cost_will_do: bool,
 ) -> Result<Token> {
     // we're zero indexed, so we want the middle index
-    let target_cost_index = CLOSE_GROUP_SIZE / 2;
+    let target_cost_index = close_group_size / 2;
 
     // sort all costs by fee, lowest to highest
     all_costs.sort();
@@ -1018,6 +1293,37 @@ fn get_fee_from_store_cost_quotes(
     Ok(token_fee)
 }
 
+/// Given the `responses` of the close group to the PUT of a record, checks a majority accepted it.
+/// A peer rejecting the record, or failing to respond, doesn't fail the PUT on its own.
+fn accepted_by_majority(
+    record_key: PrettyPrintRecordKey,
+    responses: Vec<Result<Response>>,
+    majority: usize,
+) -> Result<()> {
+    let mut accepted = 0;
+    let mut rejections = vec![];
+    for response in responses {
+        match response {
+            Ok(Response::Cmd(CmdResponse::PutRecord(Ok(())))) => accepted += 1,
+            Ok(Response::Cmd(CmdResponse::PutRecord(Err(err)))) => {
+                warn!("The PUT of {record_key:?} was rejected by a peer: {err}");
+                rejections.push(err);
+            }
+            Ok(other) => warn!("Unexpected response to the PUT of {record_key:?}: {other}"),
+            Err(err) => warn!("Failed to PUT {record_key:?} to a peer: {err:?}"),
+        }
+    }
+
+    if accepted < majority {
+        return Err(Error::RecordNotAcceptedByMajority {
+            key: record_key,
+            accepted,
+            rejections,
+        });
+    }
+    Ok(())
+}
+
 /// Verifies if `Multiaddr` contains IPv4 address that is not global.
 /// This is used to filter out unroutable addresses from the Kademlia routing table.
 pub fn multiaddr_is_global(multiaddr: &Multiaddr) -> bool {
@@ -1048,7 +1354,14 @@ pub(crate) fn multiaddr_pop_p2p(multiaddr: &mut Multiaddr) -> Option<PeerId> {
 }
 
 /// Build a `Multiaddr` with the p2p protocol filtered out.
+/// The relay's p2p protocol of a `/p2p-circuit` address is kept, as the peer is reached through it.
 pub(crate) fn multiaddr_strip_p2p(multiaddr: &Multiaddr) -> Multiaddr {
+    if multiaddr.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+        let mut multiaddr = multiaddr.clone();
+        let _ = multiaddr_pop_p2p(&mut multiaddr);
+        return multiaddr;
+    }
+
     multiaddr
         .iter()
         .filter(|p| !matches!(p, Protocol::P2p(_)))
@@ -1057,25 +1370,592 @@ pub(crate) fn multiaddr_strip_p2p(multiaddr: &Multiaddr) -> Multiaddr {
 
 #[cfg(test)]
 mod tests {
-    use eyre::bail;
+    use assert_fs::TempDir;
+    use eyre::{bail, eyre};
+    use futures::{AsyncRead, AsyncWrite};
+    use libp2p::swarm::SwarmEvent;
+    use sn_protocol::messages::{v1, MESSAGES_VERSION};
+    use std::io;
 
     use super::*;
 
+    // Keeps the node's event receiver and storage dir alive for as long as it is in use.
+    type LocalNode = (Network, mpsc::Receiver<NetworkEvent>, TempDir);
+
+    // Spawns a node listening on random local ports.
+    fn spawn_local_node() -> eyre::Result<LocalNode> {
+        spawn_local_node_with(NetworkConfig::default(), RequestLimits::default())
+    }
+
+    fn spawn_local_node_with(
+        network_config: NetworkConfig,
+        request_limits: RequestLimits,
+    ) -> eyre::Result<LocalNode> {
+        let root_dir = TempDir::new()?;
+        let (network, events_receiver, swarm_driver) = SwarmDriver::new(
+            Keypair::generate_ed25519(),
+            "127.0.0.1:0".parse()?,
+            true,
+            root_dir.path().to_path_buf(),
+            network_config,
+            RecordStoreOptions::default(),
+            request_limits,
+        )?;
+        let _handle = tokio::spawn(swarm_driver.run());
+        Ok((network, events_receiver, root_dir))
+    }
+
+    // Spawns a node speaking the provided versions of the messages only.
+    fn spawn_local_node_speaking(
+        messages_versions: RangeInclusive<u32>,
+    ) -> eyre::Result<LocalNode> {
+        let root_dir = TempDir::new()?;
+        let (network, events_receiver, swarm_driver) = SwarmDriver::new_with_messages_versions(
+            Keypair::generate_ed25519(),
+            "127.0.0.1:0".parse()?,
+            true,
+            root_dir.path().to_path_buf(),
+            NetworkConfig::default(),
+            RecordStoreOptions::default(),
+            RequestLimits::default(),
+            messages_versions,
+        )?;
+        let _handle = tokio::spawn(swarm_driver.run());
+        Ok((network, events_receiver, root_dir))
+    }
+
+    // Answers the next request the node receives with the response, returning the request.
+    async fn respond_to_next_request(
+        (network, events, _): &mut LocalNode,
+        response: Response,
+    ) -> eyre::Result<Request> {
+        while let Some(event) = events.recv().await {
+            if let NetworkEvent::RequestReceived { req, channel } = event {
+                network.send_response(response, channel)?;
+                return Ok(req);
+            }
+        }
+        Err(eyre!("The node stopped before receiving a request"))
+    }
+
+    // Waits for the node to listen on an address using the provided transport protocol.
+    async fn listen_addr(
+        network: &Network,
+        is_transport: impl Fn(&Protocol) -> bool,
+    ) -> eyre::Result<Multiaddr> {
+        for _ in 0..50 {
+            let state = network.get_swarm_local_state().await?;
+            if let Some(addr) = state
+                .listeners
+                .into_iter()
+                .find(|addr| addr.iter().any(|protocol| is_transport(&protocol)))
+            {
+                return Ok(addr);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(eyre!("Node is not listening on the expected transport"))
+    }
+
+    // Dials the peer at the address from a fresh node, and waits for the connection.
+    async fn assert_connects(addr: Multiaddr, peer_id: PeerId) -> eyre::Result<()> {
+        let (dialer, _dialer_events, _dialer_dir) = spawn_local_node()?;
+        dialer
+            .dial(addr.clone().with(Protocol::P2p(peer_id)))
+            .await?;
+
+        for _ in 0..100 {
+            let state = dialer.get_swarm_local_state().await?;
+            if state.connected_peers.contains(&peer_id) {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(eyre!("Failed to connect to {peer_id} at {addr}"))
+    }
+
+    #[tokio::test]
+    async fn nodes_interoperate_over_tcp_and_quic() -> eyre::Result<()> {
+        let (listener, _listener_events, _listener_dir) = spawn_local_node()?;
+
+        let tcp_addr =
+            listen_addr(&listener, |protocol| matches!(protocol, Protocol::Tcp(_))).await?;
+        assert_connects(tcp_addr, listener.peer_id).await?;
+
+        #[cfg(feature = "quic")]
+        {
+            let quic_addr =
+                listen_addr(&listener, |protocol| matches!(protocol, Protocol::QuicV1)).await?;
+            assert_connects(quic_addr, listener.peer_id).await?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn private_nodes_are_reachable_through_a_relay() -> eyre::Result<()> {
+        let (relay, _relay_events, _relay_dir) = spawn_local_node()?;
+        let relay_addr = listen_addr(&relay, |protocol| matches!(protocol, Protocol::Tcp(_)))
+            .await?
+            .with(Protocol::P2p(relay.peer_id));
+
+        // Reserves a slot on the relay, as a node found to be behind a NAT would.
+        let (private, _private_events, _private_dir) = spawn_local_node()?;
+        private
+            .start_listening(relay_addr.with(Protocol::P2pCircuit))
+            .await?;
+
+        let circuit_addr = listen_addr(&private, |protocol| {
+            matches!(protocol, Protocol::P2pCircuit)
+        })
+        .await?;
+        assert_connects(circuit_addr, private.peer_id).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peers_of_other_networks_are_refused() -> eyre::Result<()> {
+        let (node, _node_events, _node_dir) = spawn_local_node()?;
+        let (compatible, _compatible_events, _compatible_dir) = spawn_local_node()?;
+        let other_network = NetworkConfig {
+            network_id: "other-testnet".to_string(),
+            ..Default::default()
+        };
+        let other_parameters = NetworkConfig {
+            close_group_size: 4,
+            ..Default::default()
+        };
+        let mut incompatible = vec![];
+        for config in [other_network, other_parameters] {
+            incompatible.push(spawn_local_node_with(config, RequestLimits::default())?);
+        }
+
+        let peers = std::iter::once(&compatible).chain(incompatible.iter().map(|(peer, ..)| peer));
+        for peer in peers {
+            let addr = listen_addr(peer, |protocol| matches!(protocol, Protocol::Tcp(_))).await?;
+            node.dial(addr.with(Protocol::P2p(peer.peer_id))).await?;
+        }
+
+        // Once identified, only the compatible peer is kept, the other ones being disconnected.
+        for _ in 0..100 {
+            let routing_table = node.get_all_local_peers().await?;
+            let state = node.get_swarm_local_state().await?;
+            if routing_table.contains(&compatible.peer_id)
+                && incompatible
+                    .iter()
+                    .all(|(peer, ..)| !state.connected_peers.contains(&peer.peer_id))
+            {
+                for (peer, ..) in &incompatible {
+                    assert!(!routing_table.contains(&peer.peer_id));
+                }
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(eyre!("The peers of other networks were not refused"))
+    }
+
+    #[tokio::test]
+    async fn nodes_of_the_next_messages_version_are_understood() -> eyre::Result<()> {
+        let mut current = spawn_local_node()?;
+        // A later release, still speaking the current version while the network upgrades.
+        let mut next = spawn_local_node_speaking(MESSAGES_VERSION..=MESSAGES_VERSION + 1)?;
+        let (current_id, next_id) = (current.0.peer_id, next.0.peer_id);
+
+        let addr = listen_addr(&current.0, |protocol| matches!(protocol, Protocol::Tcp(_))).await?;
+        next.0.dial(addr.with(Protocol::P2p(current_id))).await?;
+        let mut identified = false;
+        for _ in 0..100 {
+            if current.0.get_all_local_peers().await?.contains(&next_id)
+                && next.0.get_all_local_peers().await?.contains(&current_id)
+            {
+                identified = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if !identified {
+            bail!("The nodes did not identify each other");
+        }
+
+        let address = NetworkAddress::from_peer(next_id);
+        let quote = StoreCostQuote {
+            address: address.clone(),
+            cost: Token::from_nano(100),
+            expiry: SystemTime::now() + Duration::from_secs(60),
+            peer: address.clone(),
+            public_key: vec![],
+            signature: vec![],
+        };
+        let store_cost = Request::Query(Query::GetStoreCost(address.clone()));
+        let quoted = Response::Query(QueryResponse::GetStoreCost(Ok(quote)));
+        let (response, request) = tokio::join!(
+            current.0.send_request(store_cost.clone(), next_id),
+            respond_to_next_request(&mut next, quoted.clone())
+        );
+        assert_eq!(request?, store_cost);
+        assert_eq!(response?, quoted);
+
+        let replicate = Request::Cmd(Cmd::Replicate {
+            holder: address.clone(),
+            keys: vec![address],
+        });
+        let replicated = Response::Cmd(CmdResponse::Replicate(Ok(())));
+        let (response, request) = tokio::join!(
+            next.0.send_request(replicate.clone(), current_id),
+            respond_to_next_request(&mut current, replicated.clone())
+        );
+        assert_eq!(request?, replicate);
+        assert_eq!(response?, replicated);
+        Ok(())
+    }
+
+    // The codec of a peer built with version 1 of the messages.
+    #[derive(Clone)]
+    struct V1Codec(MsgCodec);
+
+    #[async_trait::async_trait]
+    impl request_response::Codec for V1Codec {
+        type Protocol = StreamProtocol;
+        type Request = Request;
+        type Response = v1::Response;
+
+        async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Request>
+        where
+            T: AsyncRead + Unpin + Send,
+        {
+            self.0.read_message(io).await
+        }
+
+        async fn read_response<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+        ) -> io::Result<v1::Response>
+        where
+            T: AsyncRead + Unpin + Send,
+        {
+            self.0.read_message(io).await
+        }
+
+        async fn write_request<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+            req: Request,
+        ) -> io::Result<()>
+        where
+            T: AsyncWrite + Unpin + Send,
+        {
+            self.0.write_message(io, &req, true).await
+        }
+
+        async fn write_response<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+            res: v1::Response,
+        ) -> io::Result<()>
+        where
+            T: AsyncWrite + Unpin + Send,
+        {
+            self.0.write_message(io, &res, false).await
+        }
+    }
+
+    // The requests for a peer of version 1 to send, along with where to send their responses.
+    type V1Requests = mpsc::Sender<(Request, oneshot::Sender<v1::Response>)>;
+
+    // Spawns a peer built with version 1 of the messages, connected to the node at the address.
+    // It answers store cost queries with the cost alone and replication with success, passing on
+    // the requests it received.
+    async fn spawn_v1_peer(
+        node_addr: Multiaddr,
+        node_id: PeerId,
+    ) -> eyre::Result<(PeerId, V1Requests, mpsc::Receiver<Request>)> {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let transport = libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default())
+            .upgrade(libp2p::core::upgrade::Version::V1)
+            .authenticate(libp2p::noise::Config::new(&keypair)?)
+            .multiplex(libp2p::yamux::Config::default())
+            .boxed();
+        let network_config = NetworkConfig::default();
+        let protocols = messages_protocols(&network_config.network_id, &(1..=1))
+            .into_iter()
+            .map(|protocol| {
+                Ok((
+                    StreamProtocol::try_from_owned(protocol)?,
+                    ProtocolSupport::Full,
+                ))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let behaviour = request_response::Behaviour::with_codec(
+            V1Codec(MsgCodec::new(network_config.max_packet_size)),
+            protocols,
+            RequestResponseConfig::default(),
+        );
+        let mut swarm = SwarmBuilder::with_tokio_executor(transport, behaviour, peer_id).build();
+        swarm.dial(node_addr)?;
+
+        let (requests_sender, mut requests) = mpsc::channel(1);
+        let (received_sender, received) = mpsc::channel(10);
+        let (connected_sender, connected) = oneshot::channel();
+        let mut connected_sender = Some(connected_sender);
+        let _handle = tokio::spawn(async move {
+            let mut pending: HashMap<RequestId, oneshot::Sender<v1::Response>> = HashMap::new();
+            loop {
+                tokio::select! {
+                    Some((request, sender)) = requests.recv() => {
+                        let request_id = swarm.behaviour_mut().send_request(&node_id, request);
+                        let _ = pending.insert(request_id, sender);
+                    }
+                    event = swarm.select_next_some() => match event {
+                        SwarmEvent::ConnectionEstablished { .. } => {
+                            if let Some(sender) = connected_sender.take() {
+                                let _ = sender.send(());
+                            }
+                        }
+                        SwarmEvent::Behaviour(request_response::Event::Message {
+                            message: request_response::Message::Request { request, channel, .. },
+                            ..
+                        }) => {
+                            let response = match request {
+                                Request::Query(Query::GetStoreCost(_)) => v1::Response::Query(
+                                    v1::QueryResponse::GetStoreCost(Ok(Token::from_nano(100))),
+                                ),
+                                _ => v1::Response::Cmd(CmdResponse::Replicate(Ok(()))),
+                            };
+                            let _ = swarm.behaviour_mut().send_response(channel, response);
+                            let _ = received_sender.send(request).await;
+                        }
+                        SwarmEvent::Behaviour(request_response::Event::Message {
+                            message: request_response::Message::Response { request_id, response },
+                            ..
+                        }) => {
+                            if let Some(sender) = pending.remove(&request_id) {
+                                let _ = sender.send(response);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        });
+        connected.await?;
+        Ok((peer_id, requests_sender, received))
+    }
+
+    #[tokio::test]
+    async fn nodes_of_the_previous_messages_version_are_understood() -> eyre::Result<()> {
+        let mut node = spawn_local_node()?;
+        let node_id = node.0.peer_id;
+        let node_addr = listen_addr(&node.0, |protocol| matches!(protocol, Protocol::Tcp(_)))
+            .await?
+            .with(Protocol::P2p(node_id));
+        // An earlier release, quoting store costs without signing them.
+        let (previous_id, previous, mut previous_requests) =
+            spawn_v1_peer(node_addr, node_id).await?;
+
+        let address = NetworkAddress::from_peer(node_id);
+        let quote = StoreCostQuote {
+            address: address.clone(),
+            cost: Token::from_nano(100),
+            expiry: SystemTime::now() + Duration::from_secs(60),
+            peer: address.clone(),
+            public_key: vec![],
+            signature: vec![],
+        };
+        let store_cost = Request::Query(Query::GetStoreCost(address.clone()));
+        let (sender, response) = oneshot::channel();
+        previous.send((store_cost.clone(), sender)).await?;
+        let request = respond_to_next_request(
+            &mut node,
+            Response::Query(QueryResponse::GetStoreCost(Ok(quote))),
+        )
+        .await?;
+        assert_eq!(request, store_cost);
+        assert_eq!(
+            response.await?,
+            v1::Response::Query(v1::QueryResponse::GetStoreCost(Ok(Token::from_nano(100))))
+        );
+
+        let replicate = Request::Cmd(Cmd::Replicate {
+            holder: address.clone(),
+            keys: vec![address],
+        });
+        let replicated = Response::Cmd(CmdResponse::Replicate(Ok(())));
+        let (sender, response) = oneshot::channel();
+        previous.send((replicate.clone(), sender)).await?;
+        let request = respond_to_next_request(&mut node, replicated.clone()).await?;
+        assert_eq!(request, replicate);
+        assert_eq!(
+            response.await?,
+            v1::Response::Cmd(CmdResponse::Replicate(Ok(())))
+        );
+
+        // The other way around, the peer can't quote, but still replicates.
+        let response = node.0.send_request(store_cost.clone(), previous_id).await?;
+        assert_eq!(previous_requests.recv().await, Some(store_cost));
+        assert_eq!(
+            response,
+            Response::Query(QueryResponse::GetStoreCost(Err(
+                sn_protocol::error::Error::StoreCostNotQuoted
+            )))
+        );
+        let response = node.0.send_request(replicate.clone(), previous_id).await?;
+        assert_eq!(previous_requests.recv().await, Some(replicate));
+        assert_eq!(response, replicated);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_limits_are_rejected() -> eyre::Result<()> {
+        let request_limits = RequestLimits {
+            max_in_flight_requests_per_peer: 1,
+            ..Default::default()
+        };
+        let (node, _node_events, _node_dir) =
+            spawn_local_node_with(NetworkConfig::default(), request_limits)?;
+        let node_addr = listen_addr(&node, |protocol| matches!(protocol, Protocol::Tcp(_)))
+            .await?
+            .with(Protocol::P2p(node.peer_id));
+
+        // Clients are never part of the node's close group, so they are subject to the limits.
+        let (client, _client_events, client_driver) =
+            SwarmDriver::new_client(true, None, NetworkConfig::default())?;
+        let _handle = tokio::spawn(client_driver.run());
+        client.dial(node_addr).await?;
+        for _ in 0..100 {
+            let state = client.get_swarm_local_state().await?;
+            if state.connected_peers.contains(&node.peer_id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // Nothing handles the node's events here, so the first request to get through stays
+        // in flight, and the other one is rejected right away.
+        let request = Request::Query(Query::GetStoreCost(NetworkAddress::from_peer(node.peer_id)));
+        let first = Box::pin(client.send_request(request.clone(), node.peer_id));
+        let second = Box::pin(client.send_request(request, node.peer_id));
+        let (response, _) = futures::future::select(first, second).await.factor_first();
+
+        assert_eq!(
+            response?,
+            Response::Query(QueryResponse::GetStoreCost(Err(
+                sn_protocol::error::Error::RequestLimitExceeded
+            )))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn puts_beyond_the_rate_are_rejected() -> eyre::Result<()> {
+        let request_limits = RequestLimits {
+            max_requests_per_sec_per_peer: 1,
+            request_burst_per_peer: 1,
+            ..Default::default()
+        };
+        let (node, _node_events, _node_dir) =
+            spawn_local_node_with(NetworkConfig::default(), request_limits)?;
+        let node_addr = listen_addr(&node, |protocol| matches!(protocol, Protocol::Tcp(_)))
+            .await?
+            .with(Protocol::P2p(node.peer_id));
+
+        let (client, _client_events, client_driver) =
+            SwarmDriver::new_client(true, None, NetworkConfig::default())?;
+        let _handle = tokio::spawn(client_driver.run());
+        client.dial(node_addr).await?;
+        for _ in 0..100 {
+            let state = client.get_swarm_local_state().await?;
+            if state.connected_peers.contains(&node.peer_id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let put = Request::Cmd(Cmd::PutRecord {
+            address: NetworkAddress::from_peer(PeerId::random()),
+            value: vec![1, 2, 3],
+        });
+        let response = client.send_request(put.clone(), node.peer_id).await?;
+        assert_eq!(response, Response::Cmd(CmdResponse::PutRecord(Ok(()))));
+
+        // The burst is used up, and the rate doesn't allow another PUT this soon.
+        let response = client.send_request(put, node.peer_id).await?;
+        assert_eq!(
+            response,
+            Response::Cmd(CmdResponse::PutRecord(Err(
+                sn_protocol::error::Error::RequestLimitExceeded
+            )))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn puts_fail_only_without_a_majority_accepting() {
+        let key = || PrettyPrintRecordKey::from(RecordKey::new(&[1, 2, 3]));
+        let accepted = || Ok(Response::Cmd(CmdResponse::PutRecord(Ok(()))));
+        let rejected = || {
+            Ok(Response::Cmd(CmdResponse::PutRecord(Err(
+                sn_protocol::error::Error::RequestLimitExceeded,
+            ))))
+        };
+        let failed = || Err(Error::InternalMsgChannelDropped);
+
+        let responses = vec![accepted(), accepted(), accepted(), rejected(), failed()];
+        assert!(accepted_by_majority(key(), responses, 3).is_ok());
+
+        let responses = vec![accepted(), accepted(), rejected(), failed(), failed()];
+        assert!(matches!(
+            accepted_by_majority(key(), responses, 3),
+            Err(Error::RecordNotAcceptedByMajority { accepted: 2, rejections, .. })
+                if rejections == vec![sn_protocol::error::Error::RequestLimitExceeded]
+        ));
+    }
+
+    #[test]
+    fn circuit_addrs_keep_the_relay_peer_id() -> eyre::Result<()> {
+        let relay_peer_id = PeerId::random();
+        let peer_id = PeerId::random();
+        let circuit_addr = "/ip4/1.2.3.4/tcp/1234"
+            .parse::<Multiaddr>()?
+            .with(Protocol::P2p(relay_peer_id))
+            .with(Protocol::P2pCircuit);
+
+        assert_eq!(
+            multiaddr_strip_p2p(&circuit_addr.clone().with(Protocol::P2p(peer_id))),
+            circuit_addr
+        );
+        assert_eq!(
+            multiaddr_strip_p2p(
+                &"/ip4/1.2.3.4/tcp/1234"
+                    .parse::<Multiaddr>()?
+                    .with(Protocol::P2p(peer_id))
+            ),
+            "/ip4/1.2.3.4/tcp/1234".parse::<Multiaddr>()?
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_get_fee_from_store_cost_quotes() -> Result<()> {
         // for a vec of different costs of CLOUSE_GROUP size
         // ensure we return the CLOSE_GROUP / 2 indexed price
+        let close_group_size = NetworkConfig::default().close_group_size;
         let mut costs = vec![];
-        for i in 0..CLOSE_GROUP_SIZE {
+        for i in 0..close_group_size {
             costs.push(Token::from_nano(i as u64));
         }
-        let price = get_fee_from_store_cost_quotes(&mut costs, false)?;
+        let price = get_fee_from_store_cost_quotes(&mut costs, close_group_size, false)?;
 
         assert_eq!(
             price,
-            Token::from_nano(CLOSE_GROUP_SIZE as u64 / 2),
+            Token::from_nano(close_group_size as u64 / 2),
             "price should be {}",
-            CLOSE_GROUP_SIZE / 2 + 1
+            close_group_size / 2 + 1
         );
 
         Ok(())
@@ -1084,16 +1964,17 @@ mod tests {
     fn test_get_any_fee_from_store_cost_quotes() -> eyre::Result<()> {
         // for a vec of different costs of CLOUSE_GROUP size
         // ensure we return the CLOSE_GROUP / 2 indexed price
+        let close_group_size = NetworkConfig::default().close_group_size;
         let mut costs = vec![];
-        for i in 0..(CLOSE_GROUP_SIZE / 2) - 1 {
+        for i in 0..(close_group_size / 2) - 1 {
             costs.push(Token::from_nano(i as u64));
         }
 
-        if get_fee_from_store_cost_quotes(&mut costs, false).is_ok() {
+        if get_fee_from_store_cost_quotes(&mut costs, close_group_size, false).is_ok() {
             bail!("Should have errored as we have too few quotes")
         }
 
-        let price = match get_fee_from_store_cost_quotes(&mut costs, true) {
+        let price = match get_fee_from_store_cost_quotes(&mut costs, close_group_size, true) {
             Err(_) => bail!("Should have errored as we have too few quotes"),
             Ok(cost) => cost,
         };
@@ -1101,9 +1982,9 @@ mod tests {
         // as we use zero indexing above, the actual price is _two_ less
         assert_eq!(
             price,
-            Token::from_nano((CLOSE_GROUP_SIZE as u64 / 2) - 2),
+            Token::from_nano((close_group_size as u64 / 2) - 2),
             "price should be {}",
-            (CLOSE_GROUP_SIZE as u64 / 2) - 2
+            (close_group_size as u64 / 2) - 2
         );
 
         Ok(())
@@ -0,0 +1,98 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::SwarmDriver;
+use std::ops::RangeInclusive;
+
+/// The request-response protocols of each of the versions of the messages we speak, newest first,
+/// as multistream-select settles on the first one of the dialer's list the listener supports.
+pub(crate) fn messages_protocols(network_id: &str, versions: &RangeInclusive<u32>) -> Vec<String> {
+    versions
+        .clone()
+        .rev()
+        .map(|version| format!("/safe/{network_id}/messages/{version}"))
+        .collect()
+}
+
+/// The version of the messages spoken over the protocol, out of its name.
+pub(crate) fn protocol_version(protocol: &str) -> Option<u32> {
+    protocol.rsplit('/').next()?.parse().ok()
+}
+
+/// The versions of the messages we speak, as advertised in our identify agent string.
+pub(crate) fn advertised_versions(versions: &RangeInclusive<u32>) -> String {
+    format!("{}-{}", versions.start(), versions.end())
+}
+
+/// Parses the versions of the messages a peer speaks out of its identify agent string,
+/// `<agent>/<min>-<max>/<parameters_id>`.
+pub(crate) fn parse_advertised_versions(agent_version: &str) -> Option<RangeInclusive<u32>> {
+    let (min, max) = agent_version.rsplit('/').nth(1)?.split_once('-')?;
+    Some(min.parse().ok()?..=max.parse().ok()?)
+}
+
+/// The highest version of the messages both ranges have in common, if any.
+pub(crate) fn highest_common_version(
+    ours: &RangeInclusive<u32>,
+    theirs: &RangeInclusive<u32>,
+) -> Option<u32> {
+    let highest = *ours.end().min(theirs.end());
+    (highest >= *ours.start().max(theirs.start())).then_some(highest)
+}
+
+impl SwarmDriver {
+    /// Whether the peer speaks any version of the messages we do, out of its identify agent
+    /// string. The protocol of each stream then settles on the highest of them.
+    pub(crate) fn speaks_common_messages_version(&self, agent_version: &str) -> bool {
+        parse_advertised_versions(agent_version)
+            .and_then(|theirs| highest_common_version(&self.messages_versions, &theirs))
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_protocol::messages::{MESSAGES_VERSION, SUPPORTED_MESSAGES_VERSIONS};
+
+    #[test]
+    fn peers_speak_the_highest_common_version() {
+        let ours = SUPPORTED_MESSAGES_VERSIONS;
+        let agent_version = |versions: &str| format!("safe/node/0.1.0/{versions}/0a1b2c3d");
+
+        let same = parse_advertised_versions(&agent_version(&advertised_versions(&ours)));
+        assert_eq!(same, Some(ours.clone()));
+        assert_eq!(highest_common_version(&ours, &ours), Some(MESSAGES_VERSION));
+
+        let next = MESSAGES_VERSION + 1;
+        let upgrading = parse_advertised_versions(&agent_version(&format!("1-{next}")));
+        assert_eq!(upgrading, Some(1..=next));
+        assert_eq!(
+            highest_common_version(&ours, &(MESSAGES_VERSION..=next)),
+            Some(MESSAGES_VERSION)
+        );
+        assert_eq!(highest_common_version(&ours, &(next..=next + 1)), None);
+
+        // Peers of the releases without versioned messages advertise none.
+        assert_eq!(parse_advertised_versions("safe/node/0.1.0/0a1b2c3d"), None);
+    }
+
+    #[test]
+    fn protocols_are_listed_newest_first() {
+        assert_eq!(
+            messages_protocols("testnet", &(1..=3)),
+            vec![
+                "/safe/testnet/messages/3",
+                "/safe/testnet/messages/2",
+                "/safe/testnet/messages/1"
+            ]
+        );
+        assert_eq!(protocol_version("/safe/testnet/messages/3"), Some(3));
+        assert_eq!(protocol_version("/safe/testnet/kad/1.0.0"), None);
+    }
+}
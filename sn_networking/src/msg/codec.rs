@@ -0,0 +1,347 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::messages_version::protocol_version;
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::{request_response, StreamProtocol};
+use serde::{de::DeserializeOwned, Serialize};
+use sn_protocol::messages::{v1, Request, Response};
+use std::io;
+
+/// The frames start with a flags byte, followed by the length of the message as a big-endian u32.
+const FRAME_HEADER_LEN: usize = 5;
+/// The flag of the frames holding a message compressed with zstd.
+const COMPRESSED: u8 = 1;
+/// Requests larger than this are compressed, as the key lists of the replication compress well.
+/// Responses are not, as they mostly carry encrypted chunks, which don't.
+const COMPRESSION_THRESHOLD: usize = 16 * 1024;
+/// Uses the default level of zstd.
+const COMPRESSION_LEVEL: i32 = 0;
+
+/// The codec of the request-response protocols, encoding the messages with msgpack in
+/// length-prefixed frames, compressed with zstd when large.
+/// The responses are converted from and to those of the version spoken over the protocol.
+#[derive(Clone, Debug)]
+pub struct MsgCodec {
+    /// The largest message accepted, once decompressed.
+    max_message_size: usize,
+}
+
+impl MsgCodec {
+    /// Creates a codec accepting messages of up to `max_message_size` bytes.
+    pub fn new(max_message_size: usize) -> Self {
+        Self { max_message_size }
+    }
+
+    // Encodes the message in a frame, compressing it if requested and worth it.
+    fn encode_frame<M: Serialize>(&self, msg: &M, compress: bool) -> io::Result<Vec<u8>> {
+        let mut bytes = rmp_serde::to_vec(msg).map_err(invalid_input)?;
+        if bytes.len() > self.max_message_size {
+            return Err(invalid_input(format!(
+                "Message of {} bytes exceeds the maximum of {}",
+                bytes.len(),
+                self.max_message_size
+            )));
+        }
+
+        let mut flags = 0;
+        if compress && bytes.len() > COMPRESSION_THRESHOLD {
+            let compressed = zstd::bulk::compress(&bytes, COMPRESSION_LEVEL)?;
+            if compressed.len() < bytes.len() {
+                flags |= COMPRESSED;
+                bytes = compressed;
+            }
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + bytes.len());
+        frame.push(flags);
+        frame.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&bytes);
+        Ok(frame)
+    }
+
+    pub(crate) async fn read_message<M, T>(&self, io: &mut T) -> io::Result<M>
+    where
+        M: DeserializeOwned,
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut header = [0; FRAME_HEADER_LEN];
+        io.read_exact(&mut header).await?;
+        let [flags, len @ ..] = header;
+        let len = u32::from_be_bytes(len) as usize;
+        // Checked before allocating, so that peers can't make us reserve any amount of memory.
+        if len > self.max_message_size {
+            return Err(invalid_data(format!(
+                "Message of {len} bytes exceeds the maximum of {}",
+                self.max_message_size
+            )));
+        }
+        let mut bytes = vec![0; len];
+        io.read_exact(&mut bytes).await?;
+
+        if flags & COMPRESSED != 0 {
+            bytes = zstd::bulk::decompress(&bytes, self.max_message_size)?;
+        }
+        rmp_serde::from_slice(&bytes).map_err(invalid_data)
+    }
+
+    pub(crate) async fn write_message<M, T>(
+        &self,
+        io: &mut T,
+        msg: &M,
+        compress: bool,
+    ) -> io::Result<()>
+    where
+        M: Serialize + Sync,
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = self.encode_frame(msg, compress)?;
+        io.write_all(&bytes).await
+    }
+}
+
+#[async_trait]
+impl request_response::Codec for MsgCodec {
+    type Protocol = StreamProtocol;
+    type Request = Request;
+    type Response = Response;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.read_message(io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        if protocol_version(protocol.as_ref()) == Some(1) {
+            let response: v1::Response = self.read_message(io).await?;
+            return Ok(response.into());
+        }
+        self.read_message(io).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        req: Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.write_message(io, &req, true).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+        res: Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        if protocol_version(protocol.as_ref()) == Some(1) {
+            return self
+                .write_message(io, &v1::Response::from(res), false)
+                .await;
+        }
+        self.write_message(io, &res, false).await
+    }
+}
+
+fn invalid_data(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn invalid_input(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use request_response::Codec;
+    use sn_dbc::Token;
+    use sn_protocol::{
+        messages::{
+            Cmd, CmdResponse, Query, QueryResponse, StoreCostQuote, MESSAGES_VERSION,
+            STORE_COST_QUOTE_VALIDITY,
+        },
+        NetworkAddress,
+    };
+    use std::time::SystemTime;
+
+    const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+    fn protocol() -> StreamProtocol {
+        StreamProtocol::try_from_owned(format!("/safe/testnet/messages/{MESSAGES_VERSION}"))
+            .expect("Invalid protocol")
+    }
+
+    fn replicate(keys_count: usize) -> Request {
+        Request::Cmd(Cmd::Replicate {
+            holder: NetworkAddress::from_peer(libp2p::PeerId::random()),
+            keys: (0..keys_count)
+                .map(|_| NetworkAddress::RecordKey(rand::random::<[u8; 32]>().to_vec()))
+                .collect(),
+        })
+    }
+
+    async fn round_trip(
+        codec: &mut MsgCodec,
+        protocol: &StreamProtocol,
+        req: Request,
+        res: Response,
+    ) -> io::Result<(Vec<u8>, Request, Response)> {
+        let mut req_bytes = Vec::new();
+        codec.write_request(protocol, &mut req_bytes, req).await?;
+        let mut res_bytes = Vec::new();
+        codec.write_response(protocol, &mut res_bytes, res).await?;
+        let req = codec.read_request(protocol, &mut &req_bytes[..]).await?;
+        let res = codec.read_response(protocol, &mut &res_bytes[..]).await?;
+        Ok((req_bytes, req, res))
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip() -> eyre::Result<()> {
+        let mut codec = MsgCodec::new(MAX_MESSAGE_SIZE);
+        let store_cost = Request::Query(Query::GetStoreCost(NetworkAddress::from_peer(
+            libp2p::PeerId::random(),
+        )));
+        let replicated = Response::Cmd(CmdResponse::Replicate(Ok(())));
+        let failed = Response::Query(QueryResponse::GetStoreCost(Err(
+            sn_protocol::error::Error::GetStoreCostFailed,
+        )));
+
+        for (req, res) in [(store_cost, failed), (replicate(2048), replicated)] {
+            let (_, read_req, read_res) =
+                round_trip(&mut codec, &protocol(), req.clone(), res.clone()).await?;
+            assert_eq!(read_req, req);
+            assert_eq!(read_res, res);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn responses_are_converted_to_and_from_the_previous_version() -> eyre::Result<()> {
+        let mut codec = MsgCodec::new(MAX_MESSAGE_SIZE);
+        let previous = StreamProtocol::new("/safe/testnet/messages/1");
+        let address = NetworkAddress::from_peer(libp2p::PeerId::random());
+        let quote = StoreCostQuote {
+            address: address.clone(),
+            cost: Token::from_nano(100),
+            expiry: SystemTime::now() + STORE_COST_QUOTE_VALIDITY,
+            peer: address,
+            public_key: vec![],
+            signature: vec![],
+        };
+
+        // Peers of the previous version are only sent the cost.
+        let mut bytes = Vec::new();
+        let quoted = Response::Query(QueryResponse::GetStoreCost(Ok(quote)));
+        codec.write_response(&previous, &mut bytes, quoted).await?;
+        let cost: v1::Response = codec.read_message(&mut &bytes[..]).await?;
+        let cost_only =
+            v1::Response::Query(v1::QueryResponse::GetStoreCost(Ok(Token::from_nano(100))));
+        assert_eq!(cost, cost_only);
+
+        // Which is no quote to pay against, when they send it.
+        let bytes = codec.encode_frame(&cost_only, false)?;
+        let response = codec.read_response(&previous, &mut &bytes[..]).await?;
+        assert_eq!(
+            response,
+            Response::Query(QueryResponse::GetStoreCost(Err(
+                sn_protocol::error::Error::StoreCostNotQuoted
+            )))
+        );
+
+        // The other messages are the same in both versions.
+        let replicated = Response::Cmd(CmdResponse::Replicate(Ok(())));
+        let (_, read_req, read_res) =
+            round_trip(&mut codec, &previous, replicate(8), replicated.clone()).await?;
+        assert!(matches!(read_req, Request::Cmd(Cmd::Replicate { .. })));
+        assert_eq!(read_res, replicated);
+        let bytes =
+            codec.encode_frame(&v1::Response::Cmd(CmdResponse::Replicate(Ok(()))), false)?;
+        assert_eq!(
+            codec.read_response(&protocol(), &mut &bytes[..]).await?,
+            replicated
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_key_lists_are_compressed() -> eyre::Result<()> {
+        let mut codec = MsgCodec::new(MAX_MESSAGE_SIZE);
+        let replicated = Response::Cmd(CmdResponse::Replicate(Ok(())));
+        let request = replicate(2048);
+
+        let plain = rmp_serde::to_vec(&request)?;
+        let (framed, ..) = round_trip(&mut codec, &protocol(), request, replicated).await?;
+
+        assert_eq!(framed[0] & COMPRESSED, COMPRESSED);
+        assert!(framed.len() < plain.len() * 2 / 3);
+
+        // Small requests are not worth compressing.
+        let (small, ..) = round_trip(
+            &mut codec,
+            &protocol(),
+            replicate(1),
+            Response::Cmd(CmdResponse::Replicate(Ok(()))),
+        )
+        .await?;
+        assert_eq!(small[0] & COMPRESSED, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oversized_messages_are_refused() -> eyre::Result<()> {
+        let mut codec = MsgCodec::new(1024);
+        let protocol = protocol();
+
+        let mut bytes = Vec::new();
+        assert!(codec
+            .write_request(&protocol, &mut bytes, replicate(2048))
+            .await
+            .is_err());
+
+        // The length is checked before reading the message.
+        let mut frame = vec![0];
+        frame.extend_from_slice(&u32::MAX.to_be_bytes());
+        let err = codec
+            .read_request(&protocol, &mut &frame[..])
+            .await
+            .expect_err("The oversized message was read");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // As is the decompressed length.
+        let repeated = Request::Cmd(Cmd::Replicate {
+            holder: NetworkAddress::from_peer(libp2p::PeerId::random()),
+            keys: vec![NetworkAddress::RecordKey(vec![0; 32]); 2048],
+        });
+        let large = MsgCodec::new(MAX_MESSAGE_SIZE).encode_frame(&repeated, true)?;
+        assert_eq!(large[0] & COMPRESSED, COMPRESSED);
+        assert!(large.len() < 1024);
+        assert!(codec
+            .read_request(&protocol, &mut &large[..])
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -6,10 +6,22 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod codec;
+
+pub use self::codec::MsgCodec;
+
 use crate::{error::Error, MsgResponder, NetworkEvent, SwarmDriver};
 
-use libp2p::request_response::{self, Message};
-use sn_protocol::messages::{Request, Response};
+use libp2p::{
+    kad::Record,
+    request_response::{self, Message, ResponseChannel},
+    PeerId,
+};
+use sn_protocol::{
+    error::Error as ProtocolError,
+    messages::{Cmd, CmdResponse, Request, Response},
+    NetworkAddress,
+};
 use tracing::{trace, warn};
 
 impl SwarmDriver {
@@ -27,7 +39,31 @@ impl SwarmDriver {
                     ..
                 } => {
                     trace!("Received request {request_id:?} from peer {peer:?}, req: {request:?}");
-                    self.send_event(NetworkEvent::RequestReceived {
+
+                    // The close group is exempt from the limits, as replication relies on it.
+                    let is_close_group = self.close_group.contains(&peer);
+                    if !self
+                        .request_limiter
+                        .try_admit_request(peer, request_id, is_close_group)
+                    {
+                        warn!("Rejecting request {request_id:?} from peer {peer:?}, beyond its limits");
+                        return self.reject_request(
+                            &request,
+                            ProtocolError::RequestLimitExceeded,
+                            channel,
+                        );
+                    }
+                    // The request would be dropped on the floor otherwise. That of the close group
+                    // waits for room in the channel instead.
+                    if self.event_sender.capacity() == 0 && !is_close_group {
+                        warn!("Rejecting request {request_id:?} from peer {peer:?}, NetworkEvent channel is full");
+                        return self.reject_request(&request, ProtocolError::NodeBusy, channel);
+                    }
+
+                    if let Request::Cmd(Cmd::PutRecord { address, value }) = request {
+                        return self.accept_put_record(peer, address, value, channel);
+                    }
+                    self.queue_event(NetworkEvent::RequestReceived {
                         req: request,
                         channel: MsgResponder::FromPeer(channel),
                     })
@@ -86,11 +122,61 @@ impl SwarmDriver {
                 error,
             } => {
                 warn!("RequestResponse: InboundFailure for request_id: {request_id:?} and peer: {peer:?}, with error: {error:?}");
+                self.request_limiter
+                    .on_request_completed(&peer, &request_id);
             }
             request_response::Event::ResponseSent { peer, request_id } => {
                 trace!("ResponseSent for request_id: {request_id:?} and peer: {peer:?}");
+                self.request_limiter
+                    .on_request_completed(&peer, &request_id);
             }
         }
         Ok(())
     }
+
+    // Sends the record to be validated, then stored, responding once it has been accepted.
+    fn accept_put_record(
+        &mut self,
+        peer: PeerId,
+        address: NetworkAddress,
+        value: Vec<u8>,
+        channel: ResponseChannel<Response>,
+    ) -> Result<(), Error> {
+        let record = Record {
+            key: address.to_record_key(),
+            value,
+            publisher: None,
+            expires: None,
+        };
+        let result = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .store_mut()
+            .put_unverified(record, Some(peer))
+            .map_err(|err| {
+                warn!("Record {address:?} sent by {peer:?} not accepted: {err:?}");
+                ProtocolError::RecordNotAccepted(err.to_string())
+            });
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_response(channel, Response::Cmd(CmdResponse::PutRecord(result)))
+            .map_err(Error::OutgoingResponseDropped)
+    }
+
+    // Responds to the request with the error, without handling it.
+    fn reject_request(
+        &mut self,
+        request: &Request,
+        error: ProtocolError,
+        channel: ResponseChannel<Response>,
+    ) -> Result<(), Error> {
+        let response = request.error_response(error);
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_response(channel, response)
+            .map_err(Error::OutgoingResponseDropped)
+    }
 }
@@ -0,0 +1,324 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::error::{Error, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use sn_transfers::dbc_genesis::GENESIS_DBC;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use xor_name::XorName;
+
+// The close group size of the public network.
+pub(crate) const DEFAULT_CLOSE_GROUP_SIZE: usize = 8;
+// The chunk size is 1MB, so the packet size should be higher than that to prevent failures
+pub(crate) const DEFAULT_MAX_PACKET_SIZE: usize = 2 * 1024 * 1024;
+
+/// The parameters all the peers of a network must agree on, along with the timings of the
+/// node's activity. Defaults to the parameters of the public network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The id of the network, isolating it from the other ones. Defaults to an id derived from
+    /// the genesis key, for the networks sharing it to tell each other apart with a custom one.
+    pub network_id: String,
+    /// The number of nodes responsible for each record, i.e. the size of the close group.
+    pub close_group_size: usize,
+    /// The largest packet sent over the network, in bytes. Records larger than this are rejected.
+    pub max_packet_size: usize,
+    /// The default number of records a node stores, on which the store cost scale is based.
+    pub price_scale_records: usize,
+    /// The number of records stored between two steps of the store cost scale.
+    pub puts_per_price_step: usize,
+    /// Each node replicates at an interval picked between these bounds, in seconds,
+    /// staggering the replication activity across the network.
+    pub replication_interval_min_secs: u64,
+    /// The upper bound of the replication interval, in seconds.
+    pub replication_interval_max_secs: u64,
+    /// How long to wait for the response to a request, in seconds.
+    pub request_timeout_secs: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            network_id: genesis_network_id(),
+            close_group_size: DEFAULT_CLOSE_GROUP_SIZE,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            price_scale_records: 2048,
+            puts_per_price_step: 100,
+            replication_interval_min_secs: 180,
+            replication_interval_max_secs: 540,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Loads the config from a TOML file. Missing parameters are set to their default.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| Error::FailedToReadNetworkConfig {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let config: Self = toml::from_str(&contents)
+            .map_err(|err| Error::InvalidNetworkConfig(format!("{path:?}: {err}")))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the parameters are usable.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::InvalidNetworkConfig(reason.to_string()));
+        // The network id is embedded in the protocol strings
+        if self.network_id.is_empty()
+            || !self
+                .network_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return invalid("network_id must be made of alphanumeric characters, '-' and '_'");
+        }
+        if self.close_group_size == 0 {
+            return invalid("close_group_size must be non-zero");
+        }
+        if self.max_packet_size == 0 {
+            return invalid("max_packet_size must be non-zero");
+        }
+        if self.price_scale_records == 0 || self.puts_per_price_step == 0 {
+            return invalid("price_scale_records and puts_per_price_step must be non-zero");
+        }
+        if self.replication_interval_min_secs >= self.replication_interval_max_secs {
+            return invalid(
+                "replication_interval_min_secs must be lower than replication_interval_max_secs",
+            );
+        }
+        if self.request_timeout_secs == 0 {
+            return invalid("request_timeout_secs must be non-zero");
+        }
+        Ok(())
+    }
+
+    /// Majority of the close group (i.e. > 1/2).
+    pub fn close_group_majority(&self) -> usize {
+        self.close_group_size / 2 + 1
+    }
+
+    /// The bounds the node's replication interval is picked between.
+    pub fn replication_interval_range(&self) -> Range<Duration> {
+        Duration::from_secs(self.replication_interval_min_secs)
+            ..Duration::from_secs(self.replication_interval_max_secs)
+    }
+
+    /// How long to wait for the response to a request.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// A short id of the parameters the peers must agree on, for them to work together.
+    /// It is advertised through identify, so that peers with other parameters are refused.
+    pub fn parameters_id(&self) -> String {
+        let parameters = [
+            self.close_group_size,
+            self.max_packet_size,
+            self.price_scale_records,
+            self.puts_per_price_step,
+        ]
+        .iter()
+        .flat_map(|param| (*param as u64).to_be_bytes())
+        .collect::<Vec<_>>();
+        short_id(&parameters)
+    }
+}
+
+// The id of the network of the hard-coded genesis key.
+fn genesis_network_id() -> String {
+    short_id(&GENESIS_DBC.id().to_bytes())
+}
+
+// The first bytes of the hash of the content, in hex.
+fn short_id(content: &[u8]) -> String {
+    let id = XorName::from_content(content);
+    format!("{:02x}{:02x}{:02x}{:02x}", id[0], id[1], id[2], id[3])
+}
+
+/// The command line arguments to load the `NetworkConfig` from.
+/// The parameters provided as flags override the ones of the config file.
+#[derive(Args, Debug, Default)]
+pub struct NetworkConfigArgs {
+    /// A TOML file holding the parameters of the network to join.
+    ///
+    /// Any parameter missing from it is set to the one of the public network.
+    #[clap(long, value_name = "path")]
+    pub network_config: Option<PathBuf>,
+
+    /// The id of the network to join, isolating it from the other networks.
+    ///
+    /// Defaults to an id derived from the genesis key.
+    #[clap(long)]
+    pub network_id: Option<String>,
+
+    /// The number of nodes responsible for each record.
+    #[clap(long)]
+    pub close_group_size: Option<usize>,
+
+    /// The largest packet sent over the network, in bytes.
+    #[clap(long)]
+    pub max_packet_size: Option<usize>,
+
+    /// The default number of records a node stores, on which the store cost scale is based.
+    #[clap(long)]
+    pub price_scale_records: Option<usize>,
+
+    /// The number of records stored between two steps of the store cost scale.
+    #[clap(long)]
+    pub puts_per_price_step: Option<usize>,
+
+    /// The lower bound of the replication interval, in seconds.
+    #[clap(long)]
+    pub replication_interval_min_secs: Option<u64>,
+
+    /// The upper bound of the replication interval, in seconds.
+    #[clap(long)]
+    pub replication_interval_max_secs: Option<u64>,
+
+    /// How long to wait for the response to a request, in seconds.
+    #[clap(long)]
+    pub request_timeout_secs: Option<u64>,
+}
+
+impl NetworkConfigArgs {
+    /// Returns the config of the `--network-config` file, or the default one,
+    /// with the parameters provided as flags applied on top.
+    pub fn load(&self) -> Result<NetworkConfig> {
+        let mut config = match &self.network_config {
+            Some(path) => NetworkConfig::from_file(path)?,
+            None => NetworkConfig::default(),
+        };
+        if let Some(network_id) = &self.network_id {
+            config.network_id = network_id.clone();
+        }
+        config.close_group_size = self.close_group_size.unwrap_or(config.close_group_size);
+        config.max_packet_size = self.max_packet_size.unwrap_or(config.max_packet_size);
+        config.price_scale_records = self
+            .price_scale_records
+            .unwrap_or(config.price_scale_records);
+        config.puts_per_price_step = self
+            .puts_per_price_step
+            .unwrap_or(config.puts_per_price_step);
+        config.replication_interval_min_secs = self
+            .replication_interval_min_secs
+            .unwrap_or(config.replication_interval_min_secs);
+        config.replication_interval_max_secs = self
+            .replication_interval_max_secs
+            .unwrap_or(config.replication_interval_max_secs);
+        config.request_timeout_secs = self
+            .request_timeout_secs
+            .unwrap_or(config.request_timeout_secs);
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Whether the identify protocol version of a peer, `safe/<network_id>/<version>`,
+/// is the one of our network.
+pub(crate) fn is_same_network(protocol_version: &str, network_id: &str) -> bool {
+    protocol_version.split('/').nth(1) == Some(network_id)
+}
+
+/// Whether the identify agent version of a peer advertises the same network parameters as ours.
+pub(crate) fn has_compatible_parameters(agent_version: &str, parameters_id: &str) -> bool {
+    agent_version
+        .rsplit('/')
+        .next()
+        .map_or(false, |id| id == parameters_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+    use eyre::Result;
+
+    #[test]
+    fn config_file_and_flags_override_the_defaults() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let file = tmp_dir.child("network.toml");
+        file.write_str("close_group_size = 4\nrequest_timeout_secs = 10\n")?;
+
+        let args = NetworkConfigArgs {
+            network_config: Some(file.path().to_path_buf()),
+            request_timeout_secs: Some(20),
+            ..Default::default()
+        };
+        let config = args.load()?;
+        assert_eq!(
+            config,
+            NetworkConfig {
+                close_group_size: 4,
+                request_timeout_secs: 20,
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.close_group_majority(), 3);
+
+        file.write_str("close_group_size = 0\n")?;
+        assert!(args.load().is_err());
+        file.write_str("close_group = 4\n")?;
+        assert!(args.load().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn peers_with_other_parameters_are_incompatible() {
+        let ours = NetworkConfig::default().parameters_id();
+        let theirs = NetworkConfig {
+            close_group_size: 4,
+            ..Default::default()
+        }
+        .parameters_id();
+        assert_ne!(ours, theirs);
+
+        // The timings are local to each node, so they don't need to match.
+        let slower = NetworkConfig {
+            request_timeout_secs: 60,
+            ..Default::default()
+        };
+        assert_eq!(ours, slower.parameters_id());
+
+        assert!(has_compatible_parameters(
+            &format!("safe/node/0.1.0/{ours}"),
+            &ours
+        ));
+        assert!(!has_compatible_parameters(
+            &format!("safe/node/0.1.0/{theirs}"),
+            &ours
+        ));
+        assert!(!has_compatible_parameters("safe/node/0.1.0", &ours));
+    }
+
+    #[test]
+    fn networks_are_told_apart_by_their_id() {
+        let config = NetworkConfig::default();
+        assert_eq!(config.network_id, genesis_network_id());
+        let protocol_version = format!("safe/{}/0.1.0", config.network_id);
+        assert!(is_same_network(&protocol_version, &config.network_id));
+        assert!(!is_same_network(&protocol_version, "other-testnet"));
+        assert!(!is_same_network("safe/0.1.0", &config.network_id));
+
+        let invalid = NetworkConfig {
+            network_id: "other/testnet".to_string(),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
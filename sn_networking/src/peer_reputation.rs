@@ -0,0 +1,300 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{NetworkEvent, SwarmDriver};
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// Score of a peer we hold nothing against. Penalised peers recover back up to it over time.
+const MAX_SCORE: u32 = 100;
+// Below this score, a peer is removed from the routing table and not added back until it recovers.
+const DEPRIORITISE_THRESHOLD: u32 = 60;
+// At or below this score, a peer is disconnected and blocked.
+const BLOCK_THRESHOLD: u32 = 20;
+// How long a peer stays blocked for, before being given another chance.
+const BLOCK_DURATION: Duration = Duration::from_secs(30 * 60);
+// A penalised peer recovers one point of score for each of these without misbehaving.
+const SCORE_RECOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The misbehaviours a peer is penalised for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerMisbehaviour {
+    /// The peer did not send a record it announced for replication in time.
+    ReplicationFetchTimeout,
+    /// The peer sent a record which failed validation.
+    InvalidRecord,
+}
+
+impl PeerMisbehaviour {
+    fn penalty(&self) -> u32 {
+        match self {
+            Self::ReplicationFetchTimeout => 5,
+            Self::InvalidRecord => 30,
+        }
+    }
+}
+
+/// The current score of a peer which has been penalised.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerScore {
+    /// The penalised peer
+    pub peer_id: PeerId,
+    /// Its score, out of 100
+    pub score: u32,
+    /// Whether it is currently blocked
+    pub blocked: bool,
+}
+
+/// What is to be done with a peer, once penalised.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ReputationAction {
+    None,
+    Deprioritise,
+    Block,
+}
+
+#[derive(Debug)]
+struct Reputation {
+    score: u32,
+    // When the score was last lowered, which it recovers from.
+    updated_at: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Reputation {
+    fn score_at(&self, now: Instant) -> u32 {
+        // Blocked peers only get their score back once unblocked.
+        if self.blocked_until.is_some() {
+            return self.score;
+        }
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        let recovered = elapsed.as_secs() / SCORE_RECOVERY_INTERVAL.as_secs();
+        let recovered = u32::try_from(recovered).unwrap_or(u32::MAX);
+        self.score.saturating_add(recovered).min(MAX_SCORE)
+    }
+}
+
+/// Scores the peers based on their misbehaviours, so that the ones failing repeatedly
+/// are first kept out of the routing table, then blocked for a while.
+#[derive(Default, Debug)]
+pub(crate) struct PeerReputation {
+    // Only the penalised peers are tracked, any other peer has the max score.
+    peers: HashMap<PeerId, Reputation>,
+}
+
+impl PeerReputation {
+    /// Lowers the score of the peer, returning what is to be done with it.
+    pub(crate) fn penalise(
+        &mut self,
+        peer_id: PeerId,
+        misbehaviour: PeerMisbehaviour,
+    ) -> ReputationAction {
+        self.penalise_at(peer_id, misbehaviour, Instant::now())
+    }
+
+    /// Whether the peer is to be kept out of the routing table.
+    pub(crate) fn is_deprioritised(&self, peer_id: &PeerId) -> bool {
+        let now = Instant::now();
+        self.peers.get(peer_id).map_or(false, |reputation| {
+            reputation.score_at(now) < DEPRIORITISE_THRESHOLD
+        })
+    }
+
+    /// Returns the peers whose block expired, giving them another chance with a low score.
+    /// The peers which fully recovered are no longer tracked.
+    pub(crate) fn take_expired_blocks(&mut self) -> Vec<PeerId> {
+        self.take_expired_blocks_at(Instant::now())
+    }
+
+    /// Returns the scores of the penalised peers, the worst first.
+    pub(crate) fn scores(&self) -> Vec<PeerScore> {
+        let now = Instant::now();
+        let mut scores: Vec<_> = self
+            .peers
+            .iter()
+            .map(|(peer_id, reputation)| PeerScore {
+                peer_id: *peer_id,
+                score: reputation.score_at(now),
+                blocked: reputation.blocked_until.is_some(),
+            })
+            .collect();
+        scores.sort_by_key(|score| (score.score, score.peer_id));
+        scores
+    }
+
+    fn penalise_at(
+        &mut self,
+        peer_id: PeerId,
+        misbehaviour: PeerMisbehaviour,
+        now: Instant,
+    ) -> ReputationAction {
+        let reputation = self.peers.entry(peer_id).or_insert(Reputation {
+            score: MAX_SCORE,
+            updated_at: now,
+            blocked_until: None,
+        });
+        if reputation.blocked_until.is_some() {
+            return ReputationAction::None;
+        }
+
+        reputation.score = reputation
+            .score_at(now)
+            .saturating_sub(misbehaviour.penalty());
+        reputation.updated_at = now;
+
+        if reputation.score <= BLOCK_THRESHOLD {
+            reputation.blocked_until = Some(now + BLOCK_DURATION);
+            ReputationAction::Block
+        } else if reputation.score < DEPRIORITISE_THRESHOLD {
+            ReputationAction::Deprioritise
+        } else {
+            ReputationAction::None
+        }
+    }
+
+    fn take_expired_blocks_at(&mut self, now: Instant) -> Vec<PeerId> {
+        let mut expired = vec![];
+        for (peer_id, reputation) in self.peers.iter_mut() {
+            if matches!(reputation.blocked_until, Some(until) if until <= now) {
+                reputation.blocked_until = None;
+                reputation.score = BLOCK_THRESHOLD + 1;
+                reputation.updated_at = now;
+                expired.push(*peer_id);
+            }
+        }
+        self.peers
+            .retain(|_, reputation| reputation.score_at(now) < MAX_SCORE);
+        expired
+    }
+}
+
+impl SwarmDriver {
+    /// Penalises the peer for the misbehaviour. Once its score is low enough, it is removed
+    /// from the routing table, and once even lower, disconnected and blocked.
+    pub(crate) fn penalise_peer(&mut self, peer_id: PeerId, misbehaviour: PeerMisbehaviour) {
+        if peer_id == self.self_peer_id {
+            return;
+        }
+
+        match self.peer_reputation.penalise(peer_id, misbehaviour) {
+            ReputationAction::None => {
+                debug!("Penalised {peer_id:?} for {misbehaviour:?}");
+            }
+            ReputationAction::Deprioritise => {
+                info!("Penalised {peer_id:?} for {misbehaviour:?}, removing it from the routing table");
+                self.remove_from_routing_table(peer_id);
+            }
+            ReputationAction::Block => {
+                warn!("Penalised {peer_id:?} for {misbehaviour:?}, blocking it");
+                self.remove_from_routing_table(peer_id);
+                // Also closes the connections to the peer
+                self.swarm.behaviour_mut().blocked_peers.block_peer(peer_id);
+            }
+        }
+    }
+
+    /// Penalises the holders which did not send the records to be replicated in time.
+    pub(crate) fn penalise_replication_timeouts(&mut self) {
+        for peer_id in self.replication_fetcher.take_timed_out_holders() {
+            self.penalise_peer(peer_id, PeerMisbehaviour::ReplicationFetchTimeout);
+        }
+    }
+
+    /// Unblocks the peers whose block expired.
+    pub(crate) fn unblock_expired_peers(&mut self) {
+        for peer_id in self.peer_reputation.take_expired_blocks() {
+            info!("Block of {peer_id:?} expired, unblocking it");
+            self.swarm
+                .behaviour_mut()
+                .blocked_peers
+                .unblock_peer(peer_id);
+        }
+    }
+
+    fn remove_from_routing_table(&mut self, peer_id: PeerId) {
+        if self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .remove_peer(&peer_id)
+            .is_some()
+        {
+            self.send_event(NetworkEvent::PeerRemoved(peer_id));
+            self.log_kbuckets(&peer_id);
+            let _ = self.check_for_change_in_our_close_group();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_misbehaviours_deprioritise_then_block() {
+        let mut reputation = PeerReputation::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        let actions: Vec<_> = (0..3)
+            .map(|_| reputation.penalise_at(peer_id, PeerMisbehaviour::InvalidRecord, now))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ReputationAction::None,
+                ReputationAction::Deprioritise,
+                ReputationAction::Block
+            ]
+        );
+        assert!(reputation.is_deprioritised(&peer_id));
+
+        // Once blocked, further misbehaviours are ignored until the block expires.
+        assert_eq!(
+            reputation.penalise_at(peer_id, PeerMisbehaviour::InvalidRecord, now),
+            ReputationAction::None
+        );
+        assert!(reputation.take_expired_blocks_at(now).is_empty());
+
+        let expiry = now + BLOCK_DURATION;
+        assert_eq!(reputation.take_expired_blocks_at(expiry), vec![peer_id]);
+        let scores = reputation.scores();
+        assert_eq!(scores.len(), 1);
+        assert!(!scores[0].blocked);
+
+        // Being on probation, a single misbehaviour gets it blocked again.
+        assert_eq!(
+            reputation.penalise_at(peer_id, PeerMisbehaviour::ReplicationFetchTimeout, expiry),
+            ReputationAction::Block
+        );
+    }
+
+    #[test]
+    fn scores_recover_over_time() {
+        let mut reputation = PeerReputation::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            let _ = reputation.penalise_at(peer_id, PeerMisbehaviour::ReplicationFetchTimeout, now);
+        }
+        assert_eq!(reputation.peers[&peer_id].score_at(now), MAX_SCORE - 50);
+        assert!(reputation.is_deprioritised(&peer_id));
+
+        let later = now + SCORE_RECOVERY_INTERVAL * 10;
+        assert_eq!(reputation.peers[&peer_id].score_at(later), MAX_SCORE - 40);
+
+        // Fully recovered peers are no longer tracked.
+        let much_later = now + SCORE_RECOVERY_INTERVAL * MAX_SCORE;
+        assert!(reputation.take_expired_blocks_at(much_later).is_empty());
+        assert!(reputation.scores().is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use libp2p::kad::record::Key;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Name of the sled database directory, within the record store's storage dir.
+const SLED_DB_DIR_NAME: &str = "sled";
+/// Name of the directory, or sled tree, where corrupt records are moved to.
+const QUARANTINE_NAME: &str = "quarantine";
+/// Extension of the files records are written to, before being renamed into place.
+const TMP_FILE_EXTENSION: &str = "tmp";
+
+/// The backend a `DiskBackedRecordStore` persists the records' values to.
+///
+/// Writes are expected to be durable once they return successfully,
+/// so that the store's index never refers to a record which is not on disk.
+pub(crate) trait RecordStorage: Send + Sync {
+    /// Reads the value stored for the key, if any.
+    fn read(&self, key: &Key) -> io::Result<Option<Vec<u8>>>;
+
+    /// Durably writes the value for the key, replacing any existing one.
+    fn write(&self, key: &Key, value: &[u8]) -> io::Result<()>;
+
+    /// Removes the value stored for the key, if any.
+    fn remove(&self, key: &Key) -> io::Result<()>;
+
+    /// Lists the keys of all the records held in storage.
+    fn keys(&self) -> io::Result<Vec<Key>>;
+
+    /// Moves the value stored for the key aside, so that it is no longer served
+    /// but can still be inspected.
+    fn quarantine(&self, key: &Key) -> io::Result<()>;
+}
+
+/// The kinds of `RecordStorage` a node can be configured with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordStorageKind {
+    /// One file per record, named after the hex encoded key.
+    #[default]
+    FlatFile,
+    /// An embedded sled key-value database.
+    Sled,
+}
+
+impl RecordStorageKind {
+    /// Opens the storage of this kind under the provided directory.
+    pub(crate) fn open(&self, storage_dir: &Path) -> io::Result<Box<dyn RecordStorage>> {
+        let storage: Box<dyn RecordStorage> = match self {
+            Self::FlatFile => Box::new(FlatFileStorage::new(storage_dir.to_path_buf())),
+            Self::Sled => Box::new(SledStorage::open(&storage_dir.join(SLED_DB_DIR_NAME))?),
+        };
+        Ok(storage)
+    }
+}
+
+impl fmt::Display for RecordStorageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FlatFile => write!(f, "flat-file"),
+            Self::Sled => write!(f, "sled"),
+        }
+    }
+}
+
+impl FromStr for RecordStorageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat-file" => Ok(Self::FlatFile),
+            "sled" => Ok(Self::Sled),
+            other => Err(format!(
+                "Unknown record storage backend {other:?}, expected \"flat-file\" or \"sled\""
+            )),
+        }
+    }
+}
+
+/// Stores each record in its own file, named after the hex encoded key.
+pub(crate) struct FlatFileStorage {
+    storage_dir: PathBuf,
+}
+
+impl FlatFileStorage {
+    pub(crate) fn new(storage_dir: PathBuf) -> Self {
+        Self { storage_dir }
+    }
+
+    // Converts a Key into a Hex string.
+    fn key_to_hex(key: &Key) -> String {
+        let key_bytes = key.as_ref();
+        let mut hex_string = String::with_capacity(key_bytes.len() * 2);
+        for byte in key_bytes {
+            hex_string.push_str(&format!("{:02x}", byte));
+        }
+        hex_string
+    }
+
+    // Converts a Hex string back into a Key, if it is a valid one.
+    fn hex_to_key(hex_string: &str) -> Option<Key> {
+        if hex_string.is_empty() || hex_string.len() % 2 != 0 {
+            return None;
+        }
+
+        let key_bytes = (0..hex_string.len())
+            .step_by(2)
+            .map(|i| {
+                hex_string
+                    .get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Key::from(key_bytes))
+    }
+
+    fn file_path(&self, key: &Key) -> PathBuf {
+        self.storage_dir.join(Self::key_to_hex(key))
+    }
+
+    // Flushes the directory entries, so that a rename or removal survives a crash.
+    #[cfg(unix)]
+    fn sync_dir(dir: &Path) -> io::Result<()> {
+        File::open(dir)?.sync_all()
+    }
+
+    // Directories cannot be opened as files on other platforms.
+    #[cfg(not(unix))]
+    fn sync_dir(_dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl RecordStorage for FlatFileStorage {
+    fn read(&self, key: &Key) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.file_path(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write(&self, key: &Key, value: &[u8]) -> io::Result<()> {
+        let file_path = self.file_path(key);
+        let tmp_file_path = file_path.with_extension(TMP_FILE_EXTENSION);
+
+        // Write to a temporary file first and rename it into place once fully synced,
+        // so that a crash mid-write never leaves a truncated record behind.
+        let result = File::create(&tmp_file_path)
+            .and_then(|mut file| {
+                file.write_all(value)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp_file_path, &file_path))
+            .and_then(|_| Self::sync_dir(&self.storage_dir));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_file_path);
+        }
+        result
+    }
+
+    fn remove(&self, key: &Key) -> io::Result<()> {
+        match fs::remove_file(self.file_path(key)) {
+            Ok(()) => Self::sync_dir(&self.storage_dir),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn keys(&self) -> io::Result<Vec<Key>> {
+        let mut keys = vec![];
+        for entry in fs::read_dir(&self.storage_dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if let Some(hex_string) = file_name.strip_suffix(&format!(".{TMP_FILE_EXTENSION}")) {
+                // Leftover of a write which was interrupted before being renamed into place.
+                if Self::hex_to_key(hex_string).is_some() {
+                    warn!("Removing incomplete record file {path:?}");
+                    fs::remove_file(&path)?;
+                }
+                continue;
+            }
+
+            match Self::hex_to_key(file_name) {
+                Some(key) => keys.push(key),
+                None => warn!("Ignoring unknown file in the record storage: {path:?}"),
+            }
+        }
+        Ok(keys)
+    }
+
+    fn quarantine(&self, key: &Key) -> io::Result<()> {
+        let quarantine_dir = self.storage_dir.join(QUARANTINE_NAME);
+        fs::create_dir_all(&quarantine_dir)?;
+
+        fs::rename(
+            self.file_path(key),
+            quarantine_dir.join(Self::key_to_hex(key)),
+        )?;
+        Self::sync_dir(&quarantine_dir)?;
+        Self::sync_dir(&self.storage_dir)
+    }
+}
+
+/// Stores the records in an embedded sled database, keyed by the record key.
+pub(crate) struct SledStorage {
+    db: sled::Db,
+    quarantine: sled::Tree,
+}
+
+impl SledStorage {
+    pub(crate) fn open(db_path: &Path) -> io::Result<Self> {
+        let db = sled::open(db_path)?;
+        let quarantine = db.open_tree(QUARANTINE_NAME)?;
+        Ok(Self { db, quarantine })
+    }
+}
+
+impl RecordStorage for SledStorage {
+    fn read(&self, key: &Key) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.db.get(key.as_ref())?.map(|value| value.to_vec()))
+    }
+
+    fn write(&self, key: &Key, value: &[u8]) -> io::Result<()> {
+        let _ = self.db.insert(key.as_ref(), value)?;
+        let _ = self.db.flush()?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &Key) -> io::Result<()> {
+        let _ = self.db.remove(key.as_ref())?;
+        let _ = self.db.flush()?;
+        Ok(())
+    }
+
+    fn keys(&self) -> io::Result<Vec<Key>> {
+        self.db
+            .iter()
+            .keys()
+            .map(|key| Ok(Key::from(key?.to_vec())))
+            .collect()
+    }
+
+    fn quarantine(&self, key: &Key) -> io::Result<()> {
+        if let Some(value) = self.db.remove(key.as_ref())? {
+            let _ = self.quarantine.insert(key.as_ref(), value)?;
+        }
+        let _ = self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use eyre::Result;
+    use libp2p::PeerId;
+    use sn_protocol::NetworkAddress;
+
+    fn write_read_remove(storage: &dyn RecordStorage) -> Result<()> {
+        let key = NetworkAddress::from_peer(PeerId::random()).to_record_key();
+        let value = (0..50).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+
+        assert_eq!(storage.read(&key)?, None);
+
+        storage.write(&key, &value)?;
+        assert_eq!(storage.read(&key)?, Some(value));
+
+        // Writing again replaces the existing value.
+        storage.write(&key, b"replaced")?;
+        assert_eq!(storage.read(&key)?, Some(b"replaced".to_vec()));
+
+        storage.remove(&key)?;
+        assert_eq!(storage.read(&key)?, None);
+
+        // Removing a missing record is not an error.
+        storage.remove(&key)?;
+        Ok(())
+    }
+
+    fn list_and_quarantine(storage: &dyn RecordStorage) -> Result<()> {
+        let kept = NetworkAddress::from_peer(PeerId::random()).to_record_key();
+        let corrupt = NetworkAddress::from_peer(PeerId::random()).to_record_key();
+        storage.write(&kept, b"kept")?;
+        storage.write(&corrupt, b"corrupt")?;
+
+        let keys = storage.keys()?;
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&kept));
+        assert!(keys.contains(&corrupt));
+
+        storage.quarantine(&corrupt)?;
+        assert_eq!(storage.keys()?, vec![kept.clone()]);
+        assert_eq!(storage.read(&corrupt)?, None);
+        assert_eq!(storage.read(&kept)?, Some(b"kept".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn storage_backends_write_read_remove() -> Result<()> {
+        for kind in [RecordStorageKind::FlatFile, RecordStorageKind::Sled] {
+            let tmp_dir = TempDir::new()?;
+            let storage = kind.open(tmp_dir.path())?;
+            write_read_remove(storage.as_ref())?;
+            list_and_quarantine(storage.as_ref())?;
+            assert_eq!(kind.to_string().parse::<RecordStorageKind>(), Ok(kind));
+        }
+        Ok(())
+    }
+}
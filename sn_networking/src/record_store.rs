@@ -5,7 +5,12 @@
 // under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
-use crate::event::NetworkEvent;
+use crate::{
+    error::Result as NetworkResult,
+    event::NetworkEvent,
+    network_config::NetworkConfig,
+    record_storage::{RecordStorage, RecordStorageKind},
+};
 use libp2p::{
     identity::PeerId,
     kad::{
@@ -15,30 +20,31 @@ use libp2p::{
     },
 };
 use rand::Rng;
+use sn_dbc::SignedSpend;
 use sn_dbc::Token;
-use sn_protocol::{NetworkAddress, PrettyPrintRecordKey};
+use sn_protocol::{
+    storage::{
+        try_deserialize_record, ChunkWithPayment, DbcAddress, RecordHeader, RecordKind,
+        RegisterWithPayment,
+    },
+    NetworkAddress, PrettyPrintRecordKey,
+};
 use sn_transfers::dbc_genesis::TOTAL_SUPPLY;
 use std::{
     borrow::Cow,
-    collections::{hash_set, HashSet},
-    fs,
-    path::{Path, PathBuf},
+    collections::{hash_map, HashMap, HashSet},
+    io,
+    path::PathBuf,
+    sync::{mpsc as std_mpsc, Arc},
+    thread::{self, JoinHandle},
     time::Duration,
     vec,
 };
 use tokio::sync::mpsc;
 use xor_name::XorName;
 
-// Each node will have a replication interval between these bounds
-// This should serve to stagger the intense replication activity across the network
-pub const REPLICATION_INTERVAL_UPPER_BOUND: Duration = Duration::from_secs(540);
-pub const REPLICATION_INTERVAL_LOWER_BOUND: Duration = Duration::from_secs(180);
-
-/// Max number of records a node can store
-const MAX_RECORDS_COUNT: usize = 2048;
-
-/// ~Number of puts per price step
-const PUTS_PER_PRICE_STEP: usize = 100;
+/// Max size of a record counted towards the default max number of bytes a node can store, 1MB
+const MAX_RECORD_BYTES: u64 = 1024 * 1024;
 
 /// A `RecordStore` that stores records on disk.
 pub struct DiskBackedRecordStore {
@@ -46,8 +52,22 @@ pub struct DiskBackedRecordStore {
     local_key: KBucketKey<PeerId>,
     /// The configuration of the store.
     config: DiskBackedRecordStoreConfig,
-    /// A set of keys, each corresponding to a data `Record` stored on disk.
-    records: HashSet<Key>,
+    /// The keys of the data `Record`s stored on disk, along with the size of their values.
+    records: HashMap<Key, usize>,
+    /// The total size of the values of the records stored on disk.
+    used_bytes: u64,
+    /// The backend the records' values are persisted to.
+    storage: Arc<dyn RecordStorage>,
+    /// The values of the records queued to be written, served from memory until they are on disk.
+    unwritten: HashMap<Key, (u64, Vec<u8>)>,
+    /// The id of the next write, telling apart the successive writes of a record.
+    next_write_id: u64,
+    /// Queues the changes to storage, applied in order by the writer thread.
+    storage_ops: Option<std_mpsc::Sender<StorageOp>>,
+    /// The outcomes of the writes, until taken by the `SwarmDriver` to report them back.
+    write_outcomes: Option<mpsc::UnboundedReceiver<WriteOutcome>>,
+    /// The thread writing to storage, off the swarm's event loop.
+    writer: Option<JoinHandle<()>>,
     /// Currently only used to notify the record received via network put to be validated.
     event_sender: Option<mpsc::Sender<NetworkEvent>>,
     /// Distance range specify the acceptable range of record entry.
@@ -55,6 +75,24 @@ pub struct DiskBackedRecordStore {
     distance_range: Option<Distance>,
 }
 
+/// A change to storage, applied by the writer thread.
+enum StorageOp {
+    Write {
+        key: Key,
+        value: Vec<u8>,
+        write_id: u64,
+    },
+    Remove(Key),
+}
+
+/// The outcome of a write to storage, reported back to the store.
+#[derive(Debug)]
+pub(crate) struct WriteOutcome {
+    key: Key,
+    write_id: u64,
+    result: io::Result<()>,
+}
+
 /// Configuration for a `DiskBackedRecordStore`.
 #[derive(Debug, Clone)]
 pub struct DiskBackedRecordStoreConfig {
@@ -62,78 +100,178 @@ pub struct DiskBackedRecordStoreConfig {
     pub storage_dir: PathBuf,
     /// The maximum number of records.
     pub max_records: usize,
+    /// The maximum number of bytes, summing the size of the record values.
+    pub max_storage_bytes: u64,
     /// The maximum size of record values, in bytes.
     pub max_value_bytes: usize,
     /// This node's replication interval
-    /// Which should be between REPLICATION_INTERVAL_LOWER_BOUND and REPLICATION_INTERVAL_UPPER_BOUND
+    /// Which should be within the network config's replication interval range
     pub replication_interval: Duration,
+    /// The backend used to persist the records under the storage dir.
+    pub storage_kind: RecordStorageKind,
+    /// The default max number of records of the network, on which the price scale is based.
+    pub network_max_records: usize,
+    /// ~Number of puts per price step
+    pub puts_per_price_step: usize,
 }
 
 impl Default for DiskBackedRecordStoreConfig {
     fn default() -> Self {
-        // get a random integer between REPLICATION_INTERVAL_LOWER_BOUND and REPLICATION_INTERVAL_UPPER_BOUND
-        let replication_interval = rand::thread_rng()
-            .gen_range(REPLICATION_INTERVAL_LOWER_BOUND..REPLICATION_INTERVAL_UPPER_BOUND);
+        let network_config = NetworkConfig::default();
+        // get a random interval between the configured bounds
+        let replication_interval =
+            rand::thread_rng().gen_range(network_config.replication_interval_range());
+        let options = RecordStoreOptions::default();
 
         Self {
             storage_dir: std::env::temp_dir(),
-            max_records: MAX_RECORDS_COUNT,
+            max_records: options.max_records,
+            max_storage_bytes: options.max_storage_bytes,
             max_value_bytes: 65 * 1024,
             replication_interval,
+            storage_kind: options.storage_kind,
+            network_max_records: network_config.price_scale_records,
+            puts_per_price_step: network_config.puts_per_price_step,
+        }
+    }
+}
+
+/// The options of a node's record store which can be set by its operator.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordStoreOptions {
+    /// The backend used to persist the records.
+    pub storage_kind: RecordStorageKind,
+    /// The maximum number of records.
+    pub max_records: usize,
+    /// The maximum number of bytes, summing the size of the record values.
+    pub max_storage_bytes: u64,
+}
+
+impl Default for RecordStoreOptions {
+    fn default() -> Self {
+        Self::with_network_config(&NetworkConfig::default())
+    }
+}
+
+impl RecordStoreOptions {
+    /// The default options for a node of the network, storing its default max number of
+    /// records of up to 1MB.
+    pub fn with_network_config(network_config: &NetworkConfig) -> Self {
+        Self {
+            storage_kind: RecordStorageKind::default(),
+            max_records: network_config.price_scale_records,
+            max_storage_bytes: network_config.price_scale_records as u64 * MAX_RECORD_BYTES,
         }
     }
 }
 
 impl DiskBackedRecordStore {
     /// Creates a new `DiskBackedStore` with the given configuration.
+    ///
+    /// Returns an error if the configured storage backend could not be opened.
     pub fn with_config(
         local_id: PeerId,
         config: DiskBackedRecordStoreConfig,
         event_sender: Option<mpsc::Sender<NetworkEvent>>,
-    ) -> Self {
-        DiskBackedRecordStore {
+    ) -> io::Result<Self> {
+        let storage: Arc<dyn RecordStorage> = config.storage_kind.open(&config.storage_dir)?.into();
+
+        let (ops_sender, ops_receiver) = std_mpsc::channel();
+        let (outcome_sender, outcome_receiver) = mpsc::unbounded_channel();
+        let writer_storage = Arc::clone(&storage);
+        let writer = thread::Builder::new()
+            .name("record-store-writer".to_string())
+            .spawn(move || {
+                write_to_storage(writer_storage.as_ref(), ops_receiver, outcome_sender)
+            })?;
+
+        Ok(DiskBackedRecordStore {
             local_key: KBucketKey::from(local_id),
             config,
             records: Default::default(),
+            used_bytes: 0,
+            storage,
+            unwritten: Default::default(),
+            next_write_id: 0,
+            storage_ops: Some(ops_sender),
+            write_outcomes: Some(outcome_receiver),
+            writer: Some(writer),
             event_sender,
             distance_range: None,
-        }
+        })
     }
 
-    /// Returns `true` if the `Key` is present locally
-    pub fn contains(&self, key: &Key) -> bool {
-        self.records.contains(key)
+    /// Takes the receiver of the outcomes of the writes to storage, which are to be passed back
+    /// to `handle_write_outcome`.
+    pub(crate) fn take_write_outcomes(&mut self) -> Option<mpsc::UnboundedReceiver<WriteOutcome>> {
+        self.write_outcomes.take()
     }
 
-    // Converts a Key into a Hex string.
-    fn key_to_hex(key: &Key) -> String {
-        let key_bytes = key.as_ref();
-        let mut hex_string = String::with_capacity(key_bytes.len() * 2);
-        for byte in key_bytes {
-            hex_string.push_str(&format!("{:02x}", byte));
+    /// Stops serving a written record from memory, or drops it from the index if it could not be
+    /// written. Outcomes of writes which were since replaced, or removed, are ignored.
+    pub(crate) fn handle_write_outcome(&mut self, outcome: WriteOutcome) {
+        let WriteOutcome {
+            key,
+            write_id,
+            result,
+        } = outcome;
+        if !matches!(self.unwritten.get(&key), Some((id, _)) if *id == write_id) {
+            return;
         }
-        hex_string
+        let _ = self.unwritten.remove(&key);
+
+        if let Err(err) = result {
+            error!(
+                "Dropping record {:?} which could not be written to storage: {err:?}",
+                PrettyPrintRecordKey::from(key.clone())
+            );
+            if let Some(size) = self.records.remove(&key) {
+                self.used_bytes -= size as u64;
+            }
+        }
+    }
+
+    fn queue_storage_op(&self, op: StorageOp) {
+        let sent = self
+            .storage_ops
+            .as_ref()
+            .map_or(false, |ops| ops.send(op).is_ok());
+        if !sent {
+            error!("The record store writer has stopped, the change to storage is lost");
+        }
+    }
+
+    /// Returns `true` if the `Key` is present locally
+    pub fn contains(&self, key: &Key) -> bool {
+        self.records.contains_key(key)
     }
 
     pub fn record_addresses(&self) -> HashSet<NetworkAddress> {
         self.records
-            .iter()
+            .keys()
             .map(|record_key| NetworkAddress::from_record_key(record_key.clone()))
             .collect()
     }
 
     #[allow(clippy::mutable_key_type)]
-    pub fn record_addresses_ref(&self) -> &HashSet<Key> {
+    pub fn record_addresses_ref(&self) -> &HashMap<Key, usize> {
         &self.records
     }
 
-    pub fn read_from_disk<'a>(key: &Key, storage_dir: &Path) -> Option<Cow<'a, Record>> {
-        let filename = Self::key_to_hex(key);
-        let file_path = storage_dir.join(&filename);
+    // Adds the record to the index, replacing any previous entry for the key.
+    fn index_record(&mut self, key: Key, size: usize) {
+        if let Some(replaced_size) = self.records.insert(key, size) {
+            self.used_bytes -= replaced_size as u64;
+        }
+        self.used_bytes += size as u64;
+    }
 
-        match fs::read(file_path) {
-            Ok(value) => {
-                trace!("Retrieved record from disk! filename: {filename}");
+    fn read_from_storage<'a>(storage: &dyn RecordStorage, key: &Key) -> Option<Cow<'a, Record>> {
+        let record_key = PrettyPrintRecordKey::from(key.clone());
+
+        match storage.read(key) {
+            Ok(Some(value)) => {
+                trace!("Retrieved record {record_key:?} from storage");
                 let record = Record {
                     key: key.clone(),
                     value,
@@ -142,66 +280,265 @@ impl DiskBackedRecordStore {
                 };
                 Some(Cow::Owned(record))
             }
+            Ok(None) => {
+                error!("Record {record_key:?} is indexed but missing from storage");
+                None
+            }
             Err(err) => {
-                error!("Error while reading file. filename: {filename}, error: {err:?}");
+                error!("Error while reading record {record_key:?} from storage: {err:?}");
                 None
             }
         }
     }
 
-    pub fn put_verified(&mut self, r: Record) -> Result<()> {
-        let content_hash = XorName::from_content(&r.value);
-        let record_key = PrettyPrintRecordKey::from(r.key.clone());
-        trace!("PUT a verified Record: {record_key:?} (content_hash {content_hash:?})");
+    /// Rebuilds the index from the records found in storage, e.g. after a restart.
+    ///
+    /// Records which are not well formed, or not stored under the address of their
+    /// content, are quarantined. The outcome is reported with a `NetworkEvent::RecordsRecovered`.
+    pub fn restore_records(&mut self) -> io::Result<()> {
+        let mut quarantined = 0;
+        for key in self.storage.keys()? {
+            let valid_size = match self.storage.read(&key) {
+                Ok(Some(value)) => {
+                    let record = Record {
+                        key: key.clone(),
+                        value,
+                        publisher: None,
+                        expires: None,
+                    };
+                    Self::content_address(&record)
+                        .filter(|address| address.to_record_key() == key)
+                        .map(|_| record.value.len())
+                }
+                Ok(None) => None,
+                Err(err) => {
+                    error!(
+                        "Error while reading record {:?} from storage: {err:?}",
+                        PrettyPrintRecordKey::from(key.clone())
+                    );
+                    None
+                }
+            };
+
+            if let Some(size) = valid_size {
+                self.index_record(key, size);
+                continue;
+            }
+
+            let record_key = PrettyPrintRecordKey::from(key.clone());
+            warn!("Quarantining corrupt record {record_key:?}");
+            if let Err(err) = self.storage.quarantine(&key) {
+                error!("Error while quarantining record {record_key:?}: {err:?}");
+            }
+            quarantined += 1;
+        }
+
+        let recovered = self.records.len();
+        info!(
+            "Recovered {recovered} records ({} bytes) from storage, quarantined {quarantined} corrupt ones",
+            self.used_bytes
+        );
+
+        if let Some(event_sender) = &self.event_sender {
+            if let Err(error) = event_sender.try_send(NetworkEvent::RecordsRecovered {
+                recovered,
+                quarantined,
+            }) {
+                error!("Failed to report the recovered records: {error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the address derived from the content of a stored record, if it is well formed.
+    pub fn content_address(record: &Record) -> Option<NetworkAddress> {
+        // `RecordHeader::from_record` expects at least one byte of content after the header
+        if record.value.len() <= RecordHeader::SIZE {
+            return None;
+        }
+
+        let address = match RecordHeader::from_record(record).ok()?.kind {
+            RecordKind::Chunk => {
+                let chunk_with_payment = try_deserialize_record::<ChunkWithPayment>(record).ok()?;
+                NetworkAddress::from_chunk_address(*chunk_with_payment.chunk.address())
+            }
+            RecordKind::DbcSpend => {
+                let spends = try_deserialize_record::<Vec<SignedSpend>>(record).ok()?;
+                let spend = spends.first()?;
+                NetworkAddress::from_dbc_address(DbcAddress::from_dbc_id(spend.dbc_id()))
+            }
+            RecordKind::Register => {
+                let register_with_payment =
+                    try_deserialize_record::<RegisterWithPayment>(record).ok()?;
+                NetworkAddress::from_register_address(*register_with_payment.register.address())
+            }
+        };
+        Some(address)
+    }
 
-        self.prune_storage_if_needed_for_record(&r.key)?;
+    /// Returns true if the record is well formed, stored under the address of its content,
+    /// and its signatures are valid.
+    pub fn is_record_intact(record: &Record) -> bool {
+        let pretty_key = PrettyPrintRecordKey::from(record.key.clone());
 
-        let filename = Self::key_to_hex(&r.key);
-        let file_path = self.config.storage_dir.join(&filename);
-        let _ = self.records.insert(r.key);
+        let Some(content_address) = Self::content_address(record) else {
+            warn!("Record {pretty_key:?} is not well formed");
+            return false;
+        };
+        if content_address.to_record_key() != record.key {
+            warn!("Record {pretty_key:?} is not stored under the address of its content: {content_address:?}");
+            return false;
+        }
 
-        // TODO: How could we clean up records if we fail to insert?
-        tokio::spawn(async move {
-            match fs::write(file_path, r.value) {
-                Ok(_) => {
-                    trace!("Wrote record {record_key:?} to disk! filename: {filename}");
+        match RecordHeader::from_record(record).map(|header| header.kind) {
+            Ok(RecordKind::Chunk) => true,
+            Ok(RecordKind::Register) => match try_deserialize_record::<RegisterWithPayment>(record)
+            {
+                Ok(register_with_payment) => match register_with_payment.register.verify() {
+                    Ok(()) => true,
+                    Err(err) => {
+                        warn!("Register {pretty_key:?} failed signature verification: {err:?}");
+                        false
+                    }
+                },
+                Err(err) => {
+                    warn!("Register {pretty_key:?} could not be deserialised: {err:?}");
+                    false
+                }
+            },
+            Ok(RecordKind::DbcSpend) => match try_deserialize_record::<Vec<SignedSpend>>(record) {
+                Ok(spends) => {
+                    if spends.iter().any(|spend| {
+                        NetworkAddress::from_dbc_address(DbcAddress::from_dbc_id(spend.dbc_id()))
+                            != content_address
+                    }) {
+                        warn!("Spend {pretty_key:?} holds spends of different DBCs");
+                        return false;
+                    }
+                    if let Some(err) = spends
+                        .iter()
+                        .find_map(|spend| spend.verify(spend.spent_tx_hash()).err())
+                    {
+                        warn!("Spend {pretty_key:?} failed signature verification: {err:?}");
+                        return false;
+                    }
+                    true
                 }
                 Err(err) => {
-                    error!(
-                        "Error writing record {record_key:?} filename: {filename}, error: {err:?}"
-                    );
+                    warn!("Spend {pretty_key:?} could not be deserialised: {err:?}");
+                    false
                 }
+            },
+            Err(err) => {
+                warn!("Record {pretty_key:?} has an invalid header: {err:?}");
+                false
             }
+        }
+    }
+
+    /// Sends a record received from the network, from the provided peer if known,
+    /// to be validated before it is stored.
+    pub(crate) fn put_unverified(&mut self, record: Record, source: Option<PeerId>) -> Result<()> {
+        if record.value.len() >= self.config.max_value_bytes {
+            warn!(
+                "Record not stored. Value too large: {} bytes",
+                record.value.len()
+            );
+            return Err(Error::ValueTooLarge);
+        }
+
+        if self.records.contains_key(&record.key) {
+            trace!(
+                "Unverified Record {:?} already exists.",
+                PrettyPrintRecordKey::from(record.key.clone())
+            );
+            // Blindly sent to validation to allow double spend can be detected.
+            // TODO: consider avoid throw duplicated chunk to validation.
+        }
+        trace!(
+            "Unverified Record {:?} try to validate and store",
+            PrettyPrintRecordKey::from(record.key.clone())
+        );
+        if let Some(event_sender) = self.event_sender.clone() {
+            // push the event off thread so as to be non-blocking
+            let _handle = tokio::spawn(async move {
+                if let Err(error) = event_sender
+                    .send(NetworkEvent::UnverifiedRecord { record, source })
+                    .await
+                {
+                    error!("SwarmDriver failed to send event: {}", error);
+                }
+            });
+        } else {
+            error!("Record store doesn't have event_sender setup");
+        }
+        Ok(())
+    }
+
+    /// Stores a record which has been validated.
+    /// It is written to storage off the event loop, being served from memory until it is durably
+    /// written, and dropped from the index if the write fails.
+    pub fn put_verified(&mut self, r: Record) -> NetworkResult<()> {
+        let content_hash = XorName::from_content(&r.value);
+        let record_key = PrettyPrintRecordKey::from(r.key.clone());
+        trace!("PUT a verified Record: {record_key:?} (content_hash {content_hash:?})");
+
+        self.prune_storage_if_needed_for_record(&r.key, r.value.len())?;
+
+        let write_id = self.next_write_id;
+        self.next_write_id += 1;
+        self.index_record(r.key.clone(), r.value.len());
+        let _ = self
+            .unwritten
+            .insert(r.key.clone(), (write_id, r.value.clone()));
+        self.queue_storage_op(StorageOp::Write {
+            key: r.key,
+            value: r.value,
+            write_id,
         });
 
         Ok(())
     }
 
     /// Prune the records in the store to ensure that we free up space
-    /// for the incoming record.
+    /// for the incoming record, both in number of records and in bytes.
     ///
     /// An error is returned if we are full and the new record is not closer than
-    /// the furthest record
-    fn prune_storage_if_needed_for_record(&mut self, r: &Key) -> Result<()> {
-        let num_records = self.records.len();
-
-        // we're not full, so we don't need to prune
-        if num_records < self.config.max_records {
-            return Ok(());
+    /// the furthest record, or if the record is larger than the whole storage.
+    fn prune_storage_if_needed_for_record(&mut self, r: &Key, size: usize) -> Result<()> {
+        if size as u64 > self.config.max_storage_bytes {
+            warn!("Record not stored. Value of {size} bytes exceeds the storage capacity");
+            return Err(Error::ValueTooLarge);
         }
 
-        // sort records by distance to our local key
-        let mut records = self.records.iter().cloned().collect::<Vec<_>>();
-        records.sort_unstable_by_key(|k| {
-            let kbucket_key = KBucketKey::from(k.to_vec());
-            self.local_key.distance(&kbucket_key)
-        });
+        // An existing copy of the record is replaced, thus freeing its space
+        let replaced_size = self.records.get(r).copied();
+        let incoming_record_key = KBucketKey::from(r.to_vec());
+
+        loop {
+            let num_records = self.records.len() + usize::from(replaced_size.is_none());
+            let used_bytes = self.used_bytes - replaced_size.unwrap_or_default() as u64;
 
-        // now check if the incoming record is closer than our furthest
-        // if it is, we can prune
-        if let Some(furthest_record) = records.last() {
+            // we're not full, so we don't need to prune
+            if num_records <= self.config.max_records
+                && used_bytes + size as u64 <= self.config.max_storage_bytes
+            {
+                return Ok(());
+            }
+
+            // now check if the incoming record is closer than our furthest
+            // if it is, we can prune
+            let furthest_record = self
+                .records
+                .keys()
+                .filter(|key| *key != r)
+                .max_by_key(|key| self.local_key.distance(&KBucketKey::from(key.to_vec())))
+                .cloned();
+            let Some(furthest_record) = furthest_record else {
+                return Err(Error::MaxRecords);
+            };
             let furthest_record_key = KBucketKey::from(furthest_record.to_vec());
-            let incoming_record_key = KBucketKey::from(r.to_vec());
 
             if incoming_record_key.distance(&self.local_key)
                 < furthest_record_key.distance(&self.local_key)
@@ -212,7 +549,7 @@ impl DiskBackedRecordStore {
                     PrettyPrintRecordKey::from(r.clone())
                 );
                 // we should prune and make space
-                self.remove(furthest_record);
+                self.remove(&furthest_record);
 
                 // Warn if the furthest record was within our distance range
                 if let Some(distance_range) = self.distance_range {
@@ -222,46 +559,64 @@ impl DiskBackedRecordStore {
                 }
             } else {
                 // we should not prune, but warn as we're at max capcaity
-                warn!("Record not stored. Maximum number of records reached. Current num_records: {num_records}");
+                warn!("Record not stored. Maximum capacity reached. Current num_records: {}, used_bytes: {}", self.records.len(), self.used_bytes);
                 return Err(Error::MaxRecords);
             }
         }
-
-        Ok(())
     }
 
     #[allow(dead_code)]
     /// Calculate the cost to store data for our current store state
     pub fn store_cost(&self) -> Token {
-        // Calculate the factor to increase the cost for every PUTS_PER_PRICE_STEP records
+        let network_max_records = self.config.network_max_records as f64;
+        let puts_per_price_step = self.config.puts_per_price_step.max(1);
+
+        // Calculate the factor to increase the cost for every `puts_per_price_step` records
         let factor =
-            10.0f64.powf(MAX_RECORDS_COUNT as f64 / PUTS_PER_PRICE_STEP as f64 - 2.0_f64) as u64;
+            10.0f64.powf(network_max_records / puts_per_price_step as f64 - 2.0_f64) as u64;
 
-        // Calculate the starting cost
-        let mut cost = TOTAL_SUPPLY / factor;
+        // Calculate the starting cost, small networks having a factor rounded down to zero
+        let mut cost = TOTAL_SUPPLY / factor.max(1);
 
         trace!("Starting cost is {:?}", cost);
 
-        trace!("Record count is {:?}", self.records.len());
-        let relevant_records_len = if let Some(distance_range) = self.distance_range {
-            self.records
+        trace!(
+            "Record count is {:?}, used bytes are {:?}",
+            self.records.len(),
+            self.used_bytes
+        );
+        let fullness = if let Some(distance_range) = self.distance_range {
+            let (relevant_records_len, relevant_bytes) = self
+                .records
                 .iter()
-                .filter(|key| {
+                .filter(|(key, _)| {
                     let kbucket_key = KBucketKey::from(key.to_vec());
                     distance_range >= self.local_key.distance(&kbucket_key)
                 })
-                .count()
+                .fold((0, 0), |(count, bytes), (_, size)| {
+                    (count + 1, bytes + *size as u64)
+                });
+            trace!("Relevant records len is {relevant_records_len:?}, relevant bytes are {relevant_bytes:?}");
+
+            // We're as full as the most constrained of our limits
+            let records_fullness =
+                relevant_records_len as f64 / self.config.max_records.max(1) as f64;
+            let bytes_fullness =
+                relevant_bytes as f64 / self.config.max_storage_bytes.max(1) as f64;
+            records_fullness.max(bytes_fullness).min(1.0)
         } else {
             // Otherwise we've no distance range set, so we actually don't know enough
-            // so we'll say we have MAX_RECORDS_COUNT and set a high price until we know
+            // so we'll say we're full and set a high price until we know
             // more about our CLOSE_GROUP
-            MAX_RECORDS_COUNT
+            1.0
         };
 
-        trace!("Relevant records len is {:?}", relevant_records_len);
+        // The fullness is expressed in records of the default capacity, so that the price scale
+        // is the same for all nodes, whatever their configured capacity is.
+        let equivalent_records_len = (fullness * network_max_records) as usize;
 
         // Find where we are on the scale
-        let current_step = relevant_records_len / PUTS_PER_PRICE_STEP + 1;
+        let current_step = equivalent_records_len / puts_per_price_step + 1;
 
         trace!("Current step is {:?}", current_step);
 
@@ -292,74 +647,32 @@ impl RecordStore for DiskBackedRecordStore {
             "GET request for Record key: {:?}",
             PrettyPrintRecordKey::from(k.clone())
         );
-        if !self.records.contains(k) {
+        if !self.records.contains_key(k) {
             trace!("Record not found locally");
             return None;
         }
 
-        Self::read_from_disk(k, &self.config.storage_dir)
+        read_record(self.storage.as_ref(), &self.unwritten, k)
     }
 
     fn put(&mut self, record: Record) -> Result<()> {
-        if record.value.len() >= self.config.max_value_bytes {
-            warn!(
-                "Record not stored. Value too large: {} bytes",
-                record.value.len()
-            );
-            return Err(Error::ValueTooLarge);
-        }
-
-        if self.records.contains(&record.key) {
-            trace!(
-                "Unverified Record {:?} already exists.",
-                PrettyPrintRecordKey::from(record.key.clone())
-            );
-            // Blindly sent to validation to allow double spend can be detected.
-            // TODO: consider avoid throw duplicated chunk to validation.
-        }
-        trace!(
-            "Unverified Record {:?} try to validate and store",
-            PrettyPrintRecordKey::from(record.key.clone())
-        );
-        if let Some(event_sender) = self.event_sender.clone() {
-            // push the event off thread so as to be non-blocking
-            let _handle = tokio::spawn(async move {
-                if let Err(error) = event_sender
-                    .send(NetworkEvent::UnverifiedRecord(record))
-                    .await
-                {
-                    error!("SwarmDriver failed to send event: {}", error);
-                }
-            });
-        } else {
-            error!("Record store doesn't have event_sender setup");
-        }
-        Ok(())
+        self.put_unverified(record, None)
     }
 
     fn remove(&mut self, k: &Key) {
-        let _ = self.records.remove(k);
-
-        let filename = Self::key_to_hex(k);
-        let file_path = self.config.storage_dir.join(&filename);
-
-        let _handle = tokio::spawn(async move {
-            match fs::remove_file(file_path) {
-                Ok(_) => {
-                    trace!("Removed record from disk! filename: {filename}");
-                }
-                Err(err) => {
-                    error!("Error while removing file. filename: {filename}, error: {err:?}");
-                }
-            }
-        });
+        if let Some(size) = self.records.remove(k) {
+            self.used_bytes -= size as u64;
+        }
+        let _ = self.unwritten.remove(k);
+        self.queue_storage_op(StorageOp::Remove(k.clone()));
     }
 
     // A backstop replication shall only trigger within pre-defined interval
     fn records(&self) -> Self::RecordsIter<'_> {
         RecordsIterator {
-            keys: self.records.iter(),
-            storage_dir: self.config.storage_dir.clone(),
+            keys: self.records.keys(),
+            storage: self.storage.as_ref(),
+            unwritten: &self.unwritten,
         }
     }
 
@@ -383,11 +696,12 @@ impl RecordStore for DiskBackedRecordStore {
     }
 }
 
-// Since 'Record's need to be read from disk for each individual 'Key', we need this iterator
+// Since 'Record's need to be read from storage for each individual 'Key', we need this iterator
 // which does that operation at the very moment the consumer/user is iterating each item.
 pub struct RecordsIterator<'a> {
-    keys: hash_set::Iter<'a, Key>,
-    storage_dir: PathBuf,
+    keys: hash_map::Keys<'a, Key, usize>,
+    storage: &'a dyn RecordStorage,
+    unwritten: &'a HashMap<Key, (u64, Vec<u8>)>,
 }
 
 impl<'a> Iterator for RecordsIterator<'a> {
@@ -395,7 +709,7 @@ impl<'a> Iterator for RecordsIterator<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         for key in self.keys.by_ref() {
-            let record = DiskBackedRecordStore::read_from_disk(key, &self.storage_dir);
+            let record = read_record(self.storage, self.unwritten, key);
             if record.is_some() {
                 return record;
             }
@@ -405,15 +719,90 @@ impl<'a> Iterator for RecordsIterator<'a> {
     }
 }
 
+impl Drop for DiskBackedRecordStore {
+    fn drop(&mut self) {
+        // The writer thread applies the queued changes, then stops once the queue is closed.
+        drop(self.storage_ops.take());
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                error!("The record store writer panicked");
+            }
+        }
+    }
+}
+
+// Reads a record, from memory if it has not been written to storage yet.
+#[allow(clippy::mutable_key_type)]
+fn read_record<'a>(
+    storage: &dyn RecordStorage,
+    unwritten: &HashMap<Key, (u64, Vec<u8>)>,
+    key: &Key,
+) -> Option<Cow<'a, Record>> {
+    match unwritten.get(key) {
+        Some((_, value)) => Some(Cow::Owned(Record {
+            key: key.clone(),
+            value: value.clone(),
+            publisher: None,
+            expires: None,
+        })),
+        None => DiskBackedRecordStore::read_from_storage(storage, key),
+    }
+}
+
+// Applies the changes to storage in the order they were queued, reporting the outcome of writes.
+fn write_to_storage(
+    storage: &dyn RecordStorage,
+    ops: std_mpsc::Receiver<StorageOp>,
+    outcomes: mpsc::UnboundedSender<WriteOutcome>,
+) {
+    for op in ops {
+        match op {
+            StorageOp::Write {
+                key,
+                value,
+                write_id,
+            } => {
+                let record_key = PrettyPrintRecordKey::from(key.clone());
+                let result = storage.write(&key, &value);
+                match &result {
+                    Ok(()) => trace!("Wrote record {record_key:?} to storage"),
+                    Err(err) => error!("Error writing record {record_key:?} to storage: {err:?}"),
+                }
+                // The store may be gone, its outcomes no longer being of interest.
+                let _ = outcomes.send(WriteOutcome {
+                    key,
+                    write_id,
+                    result,
+                });
+            }
+            StorageOp::Remove(key) => {
+                let record_key = PrettyPrintRecordKey::from(key.clone());
+                match storage.remove(&key) {
+                    Ok(()) => trace!("Removed record {record_key:?} from storage"),
+                    Err(err) => {
+                        error!("Error while removing record {record_key:?} from storage: {err:?}")
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[allow(trivial_casts)]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_fs::TempDir;
+    use bytes::Bytes;
     use libp2p::{
         core::multihash::Multihash,
         kad::{KBucketKey, RecordKey},
     };
     use quickcheck::*;
+    use sn_protocol::{
+        messages::PaymentProof,
+        storage::{try_serialize_record, Chunk},
+    };
     use tokio::runtime::Runtime;
 
     const MULITHASH_CODE: u64 = 0x12;
@@ -499,7 +888,8 @@ mod tests {
             PeerId::random(),
             Default::default(),
             Some(network_event_sender),
-        );
+        )
+        .expect("Failed to open the record store");
 
         let store_cost_before = store.store_cost();
         // An initial unverified put should not write to disk
@@ -513,7 +903,7 @@ mod tests {
         );
 
         let returned_record = if let Some(event) = network_event_receiver.recv().await {
-            if let NetworkEvent::UnverifiedRecord(record) = event {
+            if let NetworkEvent::UnverifiedRecord { record, .. } = event {
                 record
             } else {
                 panic!("Unexpected network event {event:?}");
@@ -554,6 +944,83 @@ mod tests {
         assert!(store.get(&r.key).is_none());
     }
 
+    #[tokio::test]
+    async fn records_are_served_from_memory_until_written() -> eyre::Result<()> {
+        let mut store =
+            DiskBackedRecordStore::with_config(PeerId::random(), Default::default(), None)
+                .expect("Failed to open the record store");
+        let mut write_outcomes = store
+            .take_write_outcomes()
+            .expect("The write outcomes were already taken");
+        let record = Record {
+            key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+            value: (0..50).map(|_| rand::random::<u8>()).collect(),
+            publisher: None,
+            expires: None,
+        };
+
+        store.put_verified(record.clone())?;
+        assert_eq!(store.get(&record.key), Some(Cow::Borrowed(&record)));
+
+        let outcome = write_outcomes
+            .recv()
+            .await
+            .expect("The writer stopped before reporting the write");
+        assert!(outcome.result.is_ok());
+        store.handle_write_outcome(outcome);
+        assert!(store.unwritten.is_empty());
+        assert_eq!(store.get(&record.key), Some(Cow::Borrowed(&record)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_records_are_not_intact() -> eyre::Result<()> {
+        let chunk_record = || -> eyre::Result<Record> {
+            let chunk = Chunk::new(Bytes::from(
+                (0..50).map(|_| rand::random::<u8>()).collect::<Vec<_>>(),
+            ));
+            let key = NetworkAddress::from_chunk_address(*chunk.address()).to_record_key();
+            let chunk_with_payment = ChunkWithPayment {
+                chunk,
+                payment: PaymentProof {
+                    spent_ids: vec![],
+                    audit_trail: vec![],
+                    path: vec![],
+                    quotes: vec![],
+                },
+            };
+            Ok(Record {
+                key,
+                value: try_serialize_record(&chunk_with_payment, RecordKind::Chunk)?,
+                publisher: None,
+                expires: None,
+            })
+        };
+        let record = chunk_record()?;
+        assert!(DiskBackedRecordStore::is_record_intact(&record));
+
+        // A flipped bit in the chunk's content no longer matches its address.
+        // The content follows the header and the few bytes of its serialised length.
+        let mut bit_rot = record.clone();
+        bit_rot.value[RecordHeader::SIZE + 10] ^= 1;
+        assert!(!DiskBackedRecordStore::is_record_intact(&bit_rot));
+
+        let mut truncated = record.clone();
+        truncated.value.truncate(truncated.value.len() / 2);
+        assert!(!DiskBackedRecordStore::is_record_intact(&truncated));
+
+        truncated.value.truncate(RecordHeader::SIZE);
+        assert!(!DiskBackedRecordStore::is_record_intact(&truncated));
+
+        // A chunk served under another chunk's address.
+        let mut misplaced = record;
+        misplaced.key = chunk_record()?.key;
+        assert!(!DiskBackedRecordStore::is_record_intact(&misplaced));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn pruning_on_full() -> Result<()> {
         let max_iterations = 10;
@@ -567,7 +1034,8 @@ mod tests {
             ..Default::default()
         };
         let self_id = PeerId::random();
-        let mut store = DiskBackedRecordStore::with_config(self_id, store_config.clone(), None);
+        let mut store = DiskBackedRecordStore::with_config(self_id, store_config, None)
+            .expect("Failed to open the record store");
 
         let mut stored_records: Vec<RecordKey> = vec![];
         let self_address = NetworkAddress::from_peer(self_id);
@@ -599,12 +1067,10 @@ mod tests {
                     (furthest_key, record_key)
                 };
 
-                // Confirm the pruned_key got removed, looping to allow async disk ops to complete.
+                // Confirm the pruned_key got removed, looping as the writer thread removes it from storage.
                 let mut iteration = 0;
                 while iteration < max_iterations {
-                    if DiskBackedRecordStore::read_from_disk(&pruned_key, &store_config.storage_dir)
-                        .is_none()
-                    {
+                    if store.storage.read(&pruned_key).ok().flatten().is_none() {
                         break;
                     }
                     tokio::time::sleep(Duration::from_millis(100)).await;
@@ -640,4 +1106,188 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn restore_records_after_restart() -> eyre::Result<()> {
+        for storage_kind in [RecordStorageKind::FlatFile, RecordStorageKind::Sled] {
+            let tmp_dir = TempDir::new()?;
+            let store_config = DiskBackedRecordStoreConfig {
+                storage_dir: tmp_dir.to_path_buf(),
+                storage_kind,
+                ..Default::default()
+            };
+            let self_id = PeerId::random();
+
+            let chunk = Chunk::new(Bytes::from(
+                (0..50).map(|_| rand::random::<u8>()).collect::<Vec<_>>(),
+            ));
+            let chunk_key = NetworkAddress::from_chunk_address(*chunk.address()).to_record_key();
+            let chunk_with_payment = ChunkWithPayment {
+                chunk,
+                payment: PaymentProof {
+                    spent_ids: vec![],
+                    audit_trail: vec![],
+                    path: vec![],
+                    quotes: vec![],
+                },
+            };
+            let valid = Record {
+                key: chunk_key,
+                value: try_serialize_record(&chunk_with_payment, RecordKind::Chunk)?,
+                publisher: None,
+                expires: None,
+            };
+            // A well formed record stored under a key which isn't the one of its content.
+            let mismatched = Record {
+                key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+                ..valid.clone()
+            };
+            let truncated = Record {
+                key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+                value: valid.value[..RecordHeader::SIZE].to_vec(),
+                publisher: None,
+                expires: None,
+            };
+
+            {
+                let mut store =
+                    DiskBackedRecordStore::with_config(self_id, store_config.clone(), None)?;
+                for record in [&valid, &mismatched, &truncated] {
+                    store.put_verified(record.clone())?;
+                }
+            }
+
+            let (network_event_sender, mut network_event_receiver) = mpsc::channel(1);
+            let mut store = DiskBackedRecordStore::with_config(
+                self_id,
+                store_config,
+                Some(network_event_sender),
+            )?;
+            assert!(!store.contains(&valid.key));
+
+            store.restore_records()?;
+            assert_eq!(store.get(&valid.key), Some(Cow::Borrowed(&valid)));
+            assert!(!store.contains(&mismatched.key));
+            assert!(!store.contains(&truncated.key));
+            assert_eq!(store.storage.keys()?, vec![valid.key.clone()]);
+
+            match network_event_receiver.recv().await {
+                Some(NetworkEvent::RecordsRecovered {
+                    recovered: 1,
+                    quarantined: 2,
+                }) => {}
+                other => panic!("Unexpected network event {other:?}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pruning_on_full_storage_bytes() -> eyre::Result<()> {
+        let record_size = 100;
+        let max_storage_bytes = 10 * record_size as u64;
+        let tmp_dir = TempDir::new()?;
+        let store_config = DiskBackedRecordStoreConfig {
+            storage_dir: tmp_dir.to_path_buf(),
+            max_storage_bytes,
+            ..Default::default()
+        };
+        let self_id = PeerId::random();
+        let self_key = KBucketKey::from(self_id);
+        let mut store = DiskBackedRecordStore::with_config(self_id, store_config, None)?;
+
+        // A record larger than the whole storage is never stored.
+        let too_large = Record {
+            key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+            value: vec![0; max_storage_bytes as usize + 1],
+            publisher: None,
+            expires: None,
+        };
+        assert!(store.put_verified(too_large).is_err());
+
+        for _ in 0..30 {
+            let record = Record {
+                key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+                value: vec![0; record_size],
+                publisher: None,
+                expires: None,
+            };
+            let furthest_distance = store
+                .records
+                .keys()
+                .map(|key| self_key.distance(&KBucketKey::from(key.to_vec())))
+                .max();
+            let is_closer = furthest_distance.map_or(true, |furthest| {
+                self_key.distance(&KBucketKey::from(record.key.to_vec())) < furthest
+            });
+            let has_space = store.records.len() < 10;
+
+            let result = store.put_verified(record.clone());
+            if has_space || is_closer {
+                assert!(result.is_ok());
+                assert!(store.contains(&record.key));
+            } else {
+                assert!(result.is_err());
+                assert!(!store.contains(&record.key));
+            }
+            assert!(store.used_bytes <= max_storage_bytes);
+            assert_eq!(store.used_bytes, (store.records.len() * record_size) as u64);
+        }
+        assert_eq!(store.records.len(), 10);
+
+        // Replacing a record with a larger copy prunes others to make space.
+        let closest_key = store
+            .records
+            .keys()
+            .min_by_key(|key| self_key.distance(&KBucketKey::from(key.to_vec())))
+            .cloned()
+            .expect("The store holds records");
+        let larger = Record {
+            key: closest_key.clone(),
+            value: vec![0; 3 * record_size],
+            publisher: None,
+            expires: None,
+        };
+        store.put_verified(larger)?;
+        assert_eq!(store.records.get(&closest_key), Some(&(3 * record_size)));
+        assert_eq!(store.records.len(), 8);
+        assert_eq!(store.used_bytes, max_storage_bytes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn store_cost_reflects_used_bytes() -> eyre::Result<()> {
+        let max_storage_bytes = 1024 * 1024;
+        let self_id = PeerId::random();
+        let self_key = KBucketKey::from(self_id);
+
+        let mut store_costs = vec![];
+        for record_size in [1, max_storage_bytes / 2, max_storage_bytes] {
+            let tmp_dir = TempDir::new()?;
+            let store_config = DiskBackedRecordStoreConfig {
+                storage_dir: tmp_dir.to_path_buf(),
+                max_storage_bytes: max_storage_bytes as u64,
+                ..Default::default()
+            };
+            let mut store = DiskBackedRecordStore::with_config(self_id, store_config, None)?;
+
+            let record = Record {
+                key: NetworkAddress::from_peer(PeerId::random()).to_record_key(),
+                value: vec![0; record_size],
+                publisher: None,
+                expires: None,
+            };
+            store.set_distance_range(self_key.distance(&KBucketKey::from(record.key.to_vec())));
+            store.put_verified(record)?;
+            store_costs.push(store.store_cost());
+        }
+
+        // A single record costs more the fuller it makes the storage.
+        assert!(store_costs[0] < store_costs[1]);
+        assert!(store_costs[1] < store_costs[2]);
+
+        Ok(())
+    }
 }
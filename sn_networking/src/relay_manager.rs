@@ -0,0 +1,143 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::event::NodeBehaviour;
+use libp2p::{
+    core::transport::ListenerId, multiaddr::Protocol, relay, swarm::Swarm, Multiaddr, PeerId,
+};
+use rand::{seq::IteratorRandom, thread_rng};
+use std::{collections::HashMap, time::Duration};
+
+// Max number of private peers a node holds relay slots for.
+const MAX_RELAY_RESERVATIONS: usize = 32;
+// Max number of connections a node relays at the same time.
+const MAX_RELAY_CIRCUITS: usize = 16;
+// Relayed connections only need to last until hole punching upgrades them to direct ones.
+const MAX_RELAY_CIRCUIT_DURATION: Duration = Duration::from_secs(5 * 60);
+// Max number of packets relayed through a circuit, enough for a few records to go through,
+// should hole punching fail.
+const MAX_RELAY_CIRCUIT_PACKETS: u64 = 8;
+
+/// Config of the relay server, which offers a bounded number of relay slots to private peers.
+pub(crate) fn relay_server_config(max_packet_size: usize) -> relay::Config {
+    relay::Config {
+        max_reservations: MAX_RELAY_RESERVATIONS,
+        max_circuits: MAX_RELAY_CIRCUITS,
+        max_circuit_duration: MAX_RELAY_CIRCUIT_DURATION,
+        max_circuit_bytes: MAX_RELAY_CIRCUIT_PACKETS * max_packet_size as u64,
+        ..Default::default()
+    }
+}
+
+/// Keeps track of the peers offering relay slots, and reserves one of them
+/// when AutoNAT finds the node to be private, so that it can be reached
+/// through a `/p2p-circuit` address.
+#[derive(Default, Debug)]
+pub(crate) struct RelayManager {
+    // Reachable peers offering relay slots, with the address they were dialed at.
+    candidates: HashMap<PeerId, Multiaddr>,
+    // The relay currently reserved, along with the listener for its circuit address.
+    reservation: Option<(PeerId, ListenerId)>,
+    behind_nat: bool,
+}
+
+impl RelayManager {
+    /// Adds a peer which offers relay slots, reserving it if we are in need of a relay.
+    pub(crate) fn add_candidate(
+        &mut self,
+        peer_id: PeerId,
+        addrs: &[Multiaddr],
+        swarm: &mut Swarm<NodeBehaviour>,
+    ) {
+        // A relay can only be reserved through a direct address
+        let Some(addr) = addrs
+            .iter()
+            .find(|addr| !addr.iter().any(|p| matches!(p, Protocol::P2pCircuit)))
+        else {
+            return;
+        };
+        let _ = self.candidates.insert(peer_id, addr.clone());
+        self.try_reserve(swarm);
+    }
+
+    /// Updates whether we are behind a NAT. Once private, a relay is reserved,
+    /// and once public again, the reservation is dropped.
+    pub(crate) fn set_behind_nat(&mut self, behind_nat: bool, swarm: &mut Swarm<NodeBehaviour>) {
+        self.behind_nat = behind_nat;
+        if behind_nat {
+            self.try_reserve(swarm);
+        } else if let Some((relay_peer_id, listener_id)) = self.reservation.take() {
+            info!("No longer behind a NAT, dropping the reservation with relay {relay_peer_id:?}");
+            let _ = swarm.remove_listener(listener_id);
+        }
+    }
+
+    /// The relay refused or failed to hold our reservation, so another one is reserved.
+    pub(crate) fn on_reservation_failed(
+        &mut self,
+        relay_peer_id: PeerId,
+        swarm: &mut Swarm<NodeBehaviour>,
+    ) {
+        let _ = self.candidates.remove(&relay_peer_id);
+        if let Some((reserved_peer_id, listener_id)) = self.reservation {
+            if reserved_peer_id == relay_peer_id {
+                self.reservation = None;
+                let _ = swarm.remove_listener(listener_id);
+            }
+        }
+        self.try_reserve(swarm);
+    }
+
+    /// The listener of the circuit address closed, e.g. on the connection to the relay
+    /// being lost, so another relay is reserved.
+    pub(crate) fn on_listener_closed(
+        &mut self,
+        listener_id: ListenerId,
+        swarm: &mut Swarm<NodeBehaviour>,
+    ) {
+        if let Some((relay_peer_id, reserved_listener_id)) = self.reservation {
+            if reserved_listener_id == listener_id {
+                self.reservation = None;
+                let _ = self.candidates.remove(&relay_peer_id);
+                self.try_reserve(swarm);
+            }
+        }
+    }
+
+    // Listens on the circuit address of a random candidate, which makes the relay client
+    // request a reservation from it.
+    fn try_reserve(&mut self, swarm: &mut Swarm<NodeBehaviour>) {
+        if !self.behind_nat || self.reservation.is_some() {
+            return;
+        }
+
+        while let Some((relay_peer_id, relay_addr)) = self
+            .candidates
+            .iter()
+            .choose(&mut thread_rng())
+            .map(|(peer_id, addr)| (*peer_id, addr.clone()))
+        {
+            let circuit_addr = relay_addr
+                .with(Protocol::P2p(relay_peer_id))
+                .with(Protocol::P2pCircuit);
+            match swarm.listen_on(circuit_addr.clone()) {
+                Ok(listener_id) => {
+                    info!("Reserving a slot on relay {relay_peer_id:?} at {circuit_addr:?}");
+                    self.reservation = Some((relay_peer_id, listener_id));
+                    return;
+                }
+                Err(err) => {
+                    warn!("Failed to listen on relay circuit {circuit_addr:?}: {err:?}");
+                    let _ = self.candidates.remove(&relay_peer_id);
+                }
+            }
+        }
+
+        warn!("Behind a NAT, but no relay is known yet to reserve a slot on");
+    }
+}
@@ -11,7 +11,7 @@ use libp2p::{kad::RecordKey, PeerId};
 use rand::{seq::SliceRandom, thread_rng};
 use sn_protocol::NetworkAddress;
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, HashMap},
     time::{Duration, Instant},
 };
 
@@ -49,6 +49,8 @@ pub(crate) struct ReplicationFetcher {
         BTreeMap<PeerId, (ReplicationRequestSentTime, HolderStatus, FailedAttempts)>,
     >,
     on_going_fetches: usize,
+    // The holders which failed to send a record in time, since last taken.
+    timed_out_holders: Vec<PeerId>,
 }
 
 impl ReplicationFetcher {
@@ -58,7 +60,7 @@ impl ReplicationFetcher {
         &mut self,
         peer_id: PeerId,
         incoming_keys: Vec<NetworkAddress>,
-        locally_stored_keys: &HashSet<RecordKey>,
+        locally_stored_keys: &HashMap<RecordKey, usize>,
     ) -> Vec<(RecordKey, Option<PeerId>)> {
         self.retain_keys(locally_stored_keys);
 
@@ -66,7 +68,7 @@ impl ReplicationFetcher {
         incoming_keys
             .into_iter()
             .filter_map(|incoming| incoming.as_record_key())
-            .filter(|incoming| !locally_stored_keys.contains(incoming))
+            .filter(|incoming| !locally_stored_keys.contains_key(incoming))
             .for_each(|incoming| self.add_holder_pey_key(incoming, peer_id));
 
         self.next_keys_to_fetch()
@@ -154,6 +156,7 @@ impl ReplicationFetcher {
                     HolderStatus::OnGoing => {
                         if Instant::now() > *replication_req_time + FETCH_TIMEOUT {
                             *failed_attempts += 1;
+                            self.timed_out_holders.push(*peer_id);
                             // allows it to be re-queued
                             *holder_status = HolderStatus::Pending;
                             self.on_going_fetches = self.on_going_fetches.saturating_sub(1);
@@ -190,10 +193,15 @@ impl ReplicationFetcher {
             .collect::<Vec<_>>()
     }
 
+    /// Returns the holders which failed to send a record in time, since last called.
+    pub(crate) fn take_timed_out_holders(&mut self) -> Vec<PeerId> {
+        std::mem::take(&mut self.timed_out_holders)
+    }
+
     /// Remove keys that we hold already and no longer need to be replicated.
-    fn retain_keys(&mut self, existing_keys: &HashSet<RecordKey>) {
+    fn retain_keys(&mut self, existing_keys: &HashMap<RecordKey, usize>) {
         self.to_be_fetched
-            .retain(|key, _| !existing_keys.contains(key));
+            .retain(|key, _| !existing_keys.contains_key(key));
     }
 
     /// Add the holder for the following key
@@ -211,12 +219,15 @@ mod tests {
     use eyre::Result;
     use libp2p::{kad::RecordKey, PeerId};
     use sn_protocol::NetworkAddress;
-    use std::{collections::HashSet, time::Duration};
+    use std::{
+        collections::{HashMap, HashSet},
+        time::Duration,
+    };
 
     #[tokio::test]
     async fn fetch_from_the_network_if_we_cannot_fetch_from_peer() -> Result<()> {
         let mut replication_fetcher = ReplicationFetcher::default();
-        let locally_stored_keys = HashSet::new();
+        let locally_stored_keys = HashMap::new();
 
         let random_data: Vec<u8> = (0..50).map(|_| rand::random::<u8>()).collect();
         let key = NetworkAddress::from_record_key(RecordKey::from(random_data));
@@ -233,6 +244,7 @@ mod tests {
         // should not return key as it is being fetched
         let keys_to_fetch = replication_fetcher.next_keys_to_fetch();
         assert_eq!(keys_to_fetch.len(), 0);
+        assert!(replication_fetcher.take_timed_out_holders().is_empty());
 
         tokio::time::sleep(FETCH_TIMEOUT).await;
 
@@ -244,13 +256,17 @@ mod tests {
         assert!(key.as_record_key().is_some_and(|k| k == fetch_key));
         assert!(fetch_peer.is_none());
 
+        // and the peer reported for having timed out
+        assert_eq!(replication_fetcher.take_timed_out_holders(), vec![peer]);
+        assert!(replication_fetcher.take_timed_out_holders().is_empty());
+
         Ok(())
     }
 
     #[tokio::test]
     async fn try_with_multiple_peers_before_fetching_from_network() -> Result<()> {
         let mut replication_fetcher = ReplicationFetcher::default();
-        let locally_stored_keys = HashSet::new();
+        let locally_stored_keys = HashMap::new();
         let mut already_fetched_from = HashSet::new();
 
         let random_data: Vec<u8> = (0..50).map(|_| rand::random::<u8>()).collect();
@@ -316,7 +332,7 @@ mod tests {
     #[tokio::test]
     async fn verify_max_parallel_fetches() -> Result<()> {
         let mut replication_fetcher = ReplicationFetcher::default();
-        let locally_stored_keys = HashSet::new();
+        let locally_stored_keys = HashMap::new();
 
         let peer = PeerId::random();
         let mut incoming_keys = Vec::new();
@@ -0,0 +1,204 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use libp2p::{request_response::RequestId, PeerId};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Instant,
+};
+
+/// The limits applied to each peer, so that a single one cannot flood the node
+/// and crowd out the others. The peers of our close group are exempt from them.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// The maximum number of connections established with a peer at the same time.
+    pub max_connections_per_peer: u32,
+    /// The maximum number of requests of a peer being handled at the same time.
+    pub max_in_flight_requests_per_peer: usize,
+    /// The number of requests and PUTs per second a peer is allowed on average.
+    pub max_requests_per_sec_per_peer: u32,
+    /// The number of requests and PUTs a peer is allowed in a burst, above its average rate.
+    pub request_burst_per_peer: u32,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_connections_per_peer: 4,
+            max_in_flight_requests_per_peer: 32,
+            max_requests_per_sec_per_peer: 20,
+            request_burst_per_peer: 100,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PeerRequests<Id> {
+    // Requests the peer is allowed right now, refilled at the allowed rate, up to the burst.
+    tokens: f64,
+    refilled_at: Instant,
+    in_flight: HashSet<Id>,
+}
+
+/// Keeps track of the requests of each peer, rejecting the ones beyond its limits.
+#[derive(Debug)]
+pub(crate) struct RequestLimiter<Id = RequestId> {
+    limits: RequestLimits,
+    peers: HashMap<PeerId, PeerRequests<Id>>,
+}
+
+impl<Id: Eq + Hash> RequestLimiter<Id> {
+    pub(crate) fn new(limits: RequestLimits) -> Self {
+        Self {
+            limits,
+            peers: Default::default(),
+        }
+    }
+
+    /// Admits a request from the peer, unless it is beyond its rate or in-flight limits.
+    /// Admitted requests are in flight until `on_request_completed` is called.
+    pub(crate) fn try_admit_request(
+        &mut self,
+        peer_id: PeerId,
+        request_id: Id,
+        is_close_group: bool,
+    ) -> bool {
+        self.try_admit_request_at(peer_id, request_id, is_close_group, Instant::now())
+    }
+
+    /// The response to the request has been sent, or failed to be.
+    pub(crate) fn on_request_completed(&mut self, peer_id: &PeerId, request_id: &Id) {
+        if let Some(requests) = self.peers.get_mut(peer_id) {
+            let _ = requests.in_flight.remove(request_id);
+        }
+    }
+
+    /// Stops tracking the peers with no request in flight and a full allowance.
+    pub(crate) fn prune_idle_peers(&mut self) {
+        let now = Instant::now();
+        let limits = self.limits;
+        self.peers.retain(|_, requests| {
+            refill(requests, &limits, now);
+            !requests.in_flight.is_empty() || requests.tokens < limits.request_burst_per_peer as f64
+        });
+    }
+
+    fn try_admit_request_at(
+        &mut self,
+        peer_id: PeerId,
+        request_id: Id,
+        is_close_group: bool,
+        now: Instant,
+    ) -> bool {
+        if is_close_group {
+            return true;
+        }
+
+        let max_in_flight = self.limits.max_in_flight_requests_per_peer;
+        if self
+            .peers
+            .get(&peer_id)
+            .map_or(false, |requests| requests.in_flight.len() >= max_in_flight)
+        {
+            return false;
+        }
+        if !self.take_token(peer_id, now) {
+            return false;
+        }
+
+        if let Some(requests) = self.peers.get_mut(&peer_id) {
+            let _ = requests.in_flight.insert(request_id);
+        }
+        true
+    }
+
+    fn take_token(&mut self, peer_id: PeerId, now: Instant) -> bool {
+        let limits = self.limits;
+        let requests = self.peers.entry(peer_id).or_insert_with(|| PeerRequests {
+            tokens: limits.request_burst_per_peer as f64,
+            refilled_at: now,
+            in_flight: Default::default(),
+        });
+        refill(requests, &limits, now);
+
+        if requests.tokens < 1.0 {
+            return false;
+        }
+        requests.tokens -= 1.0;
+        true
+    }
+}
+
+fn refill<Id>(requests: &mut PeerRequests<Id>, limits: &RequestLimits, now: Instant) {
+    let elapsed = now.saturating_duration_since(requests.refilled_at);
+    requests.tokens = (requests.tokens
+        + elapsed.as_secs_f64() * limits.max_requests_per_sec_per_peer as f64)
+        .min(limits.request_burst_per_peer as f64);
+    requests.refilled_at = now;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limits() -> RequestLimits {
+        RequestLimits {
+            max_connections_per_peer: 1,
+            max_in_flight_requests_per_peer: 2,
+            max_requests_per_sec_per_peer: 1,
+            request_burst_per_peer: 3,
+        }
+    }
+
+    #[test]
+    fn requests_beyond_the_limits_are_rejected() {
+        let mut limiter = RequestLimiter::<u64>::new(limits());
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        // Only so many requests can be in flight at once.
+        assert!(limiter.try_admit_request_at(peer_id, 1, false, now));
+        assert!(limiter.try_admit_request_at(peer_id, 2, false, now));
+        assert!(!limiter.try_admit_request_at(peer_id, 3, false, now));
+
+        // Once one completes, another is admitted, until the burst is used up.
+        limiter.on_request_completed(&peer_id, &1);
+        assert!(limiter.try_admit_request_at(peer_id, 3, false, now));
+        limiter.on_request_completed(&peer_id, &2);
+        assert!(!limiter.try_admit_request_at(peer_id, 4, false, now));
+
+        // The allowance is refilled over time.
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.try_admit_request_at(peer_id, 4, false, later));
+        limiter.on_request_completed(&peer_id, &3);
+        assert!(!limiter.try_admit_request_at(peer_id, 5, false, later));
+
+        // Other peers are not affected, and the close group is exempt.
+        assert!(limiter.try_admit_request_at(PeerId::random(), 6, false, later));
+        assert!(limiter.try_admit_request_at(peer_id, 7, true, later));
+    }
+
+    #[test]
+    fn idle_peers_are_pruned() {
+        let mut limiter = RequestLimiter::<u64>::new(limits());
+        let peer_id = PeerId::random();
+
+        assert!(limiter.try_admit_request(peer_id, 1, false));
+        limiter.prune_idle_peers();
+        assert!(limiter.peers.contains_key(&peer_id));
+
+        limiter.on_request_completed(&peer_id, &1);
+        let _ = limiter.peers.get_mut(&peer_id).map(|requests| {
+            requests.refilled_at -= Duration::from_secs(5);
+        });
+        limiter.prune_idle_peers();
+        assert!(limiter.peers.is_empty());
+    }
+}
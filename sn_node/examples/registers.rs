@@ -6,13 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use sn_client::{Client, Error};
+use sn_client::{Client, Error, NetworkConfig, WalletClient};
 
 use bls::SecretKey;
 use clap::Parser;
 use eyre::Result;
 use sn_registers::RegisterAddress;
-use std::{io, time::Duration};
+use sn_transfers::wallet::LocalWallet;
+use std::{io, path::PathBuf, time::Duration};
 use tokio::time::sleep;
 use xor_name::XorName;
 
@@ -27,6 +28,10 @@ struct Opt {
 
     #[clap(long, default_value_t = 2000)]
     delay_millis: u64,
+
+    /// The dir of the wallet to pay for the Register with, in case it needs to be created.
+    #[clap(long)]
+    wallet_dir: PathBuf,
 }
 
 #[tokio::main]
@@ -40,7 +45,7 @@ async fn main() -> Result<()> {
     let signer = SecretKey::random();
 
     println!("Starting SAFE client...");
-    let client = Client::new(signer, None, None).await?;
+    let client = Client::new(signer, None, None, None, NetworkConfig::default()).await?;
     println!("SAFE client signer public key: {:?}", client.signer_pk());
 
     // we'll retrieve (or create if not found) a Register, and write on it
@@ -58,7 +63,13 @@ async fn main() -> Result<()> {
         }
         Err(_) => {
             println!("Register '{reg_nickname}' not found, creating it at {address}");
-            client.create_register(meta, true).await?
+            let wallet = LocalWallet::load_from(&opt.wallet_dir).await?;
+            let mut wallet_client = WalletClient::new(client.clone(), wallet).await;
+            let register = client
+                .create_register(meta, &mut wallet_client, true)
+                .await?;
+            wallet_client.into_wallet().store().await?;
+            register
         }
     };
     println!("Register owned by: {:?}", reg_replica.owner());
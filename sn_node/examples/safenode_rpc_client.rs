@@ -11,13 +11,16 @@ use eyre::Result;
 use libp2p::{Multiaddr, PeerId};
 use safenode_proto::safe_node_client::SafeNodeClient;
 use safenode_proto::{
-    NetworkInfoRequest, NodeEventsRequest, NodeInfoRequest, RecordAddressesRequest, RestartRequest,
-    StopRequest, UpdateRequest,
+    NetworkInfoRequest, NodeEventsRequest, NodeInfoRequest, PeerScoresRequest,
+    RecordAddressesRequest, RestartRequest, ScrubStatsRequest, StopRequest, UpdateRequest,
 };
 use sn_logging::{init_logging, LogFormat, LogOutputDest};
 use sn_node::NodeEvent;
 use std::str::FromStr;
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    net::SocketAddr,
+    time::{Duration, UNIX_EPOCH},
+};
 use tokio_stream::StreamExt;
 use tonic::Request;
 use tracing_core::Level;
@@ -49,6 +52,12 @@ enum Cmd {
     /// Note this blocks the app and it will print events as they are broadcasted by the node
     #[clap(name = "events")]
     Events,
+    /// Retrieve the statistics of the integrity checks of the records held by the node
+    #[clap(name = "scrubstats")]
+    ScrubStats,
+    /// Retrieve the scores of the peers penalised by the node for misbehaving
+    #[clap(name = "peerscores")]
+    PeerScores,
     /// Restart the node after the specified delay
     #[clap(name = "restart")]
     Restart {
@@ -91,6 +100,8 @@ async fn main() -> Result<()> {
         Cmd::Info => node_info(addr).await,
         Cmd::Netinfo => network_info(addr).await,
         Cmd::Events => node_events(addr).await,
+        Cmd::ScrubStats => scrub_stats(addr).await,
+        Cmd::PeerScores => peer_scores(addr).await,
         Cmd::Restart { delay_millis } => node_restart(addr, delay_millis).await,
         Cmd::Stop { delay_millis } => node_stop(addr, delay_millis).await,
         Cmd::Update { delay_millis } => node_update(addr, delay_millis).await,
@@ -111,6 +122,7 @@ pub async fn node_info(addr: SocketAddr) -> Result<()> {
     println!("Logs dir: {}", node_info.log_dir);
     println!("PID: {}", node_info.pid);
     println!("Binary version: {}", node_info.bin_version);
+    println!("Network id: {}", node_info.network_id);
     println!(
         "Time since last restart: {:?}",
         Duration::from_secs(node_info.uptime_secs)
@@ -184,6 +196,45 @@ pub async fn record_addresses(addr: SocketAddr) -> Result<()> {
     Ok(())
 }
 
+pub async fn scrub_stats(addr: SocketAddr) -> Result<()> {
+    let endpoint = format!("https://{addr}");
+    let mut client = SafeNodeClient::connect(endpoint).await?;
+    let response = client
+        .scrub_stats(Request::new(ScrubStatsRequest {}))
+        .await?;
+    let stats = response.get_ref();
+
+    println!("Integrity checks of the records held by the node:");
+    println!("Scrubs completed: {}", stats.runs);
+    println!("Records checked: {}", stats.records_checked);
+    println!("Corrupt records found: {}", stats.corrupt_records);
+    if stats.last_run_secs == 0 {
+        println!("Last scrub: never");
+    } else {
+        let last_run = UNIX_EPOCH + Duration::from_secs(stats.last_run_secs);
+        println!("Last scrub: {last_run:?}");
+    }
+
+    Ok(())
+}
+
+pub async fn peer_scores(addr: SocketAddr) -> Result<()> {
+    let endpoint = format!("https://{addr}");
+    let mut client = SafeNodeClient::connect(endpoint).await?;
+    let response = client
+        .peer_scores(Request::new(PeerScoresRequest {}))
+        .await?;
+
+    println!("Peers penalised by the node, the worst first:");
+    for score in response.get_ref().scores.iter() {
+        let peer_id = PeerId::from_bytes(&score.peer_id)?;
+        let blocked = if score.blocked { " (blocked)" } else { "" };
+        println!("Peer {peer_id}: score {}{blocked}", score.score);
+    }
+
+    Ok(())
+}
+
 pub async fn node_restart(addr: SocketAddr, delay_millis: u64) -> Result<()> {
     let endpoint = format!("https://{addr}");
     let mut client = SafeNodeClient::connect(endpoint).await?;
@@ -6,18 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{error::Result, event::NodeEventsChannel, Marker, Network, Node, NodeEvent};
+use super::{
+    error::Result, event::NodeEventsChannel, put_validation::is_invalid_record_error,
+    scrub::SCRUB_INTERVAL, Marker, Network, Node, NodeEvent, ScrubStats,
+};
 use libp2p::{autonat::NatStatus, identity::Keypair, Multiaddr, PeerId};
 use rand::{rngs::StdRng, Rng, SeedableRng};
-use sn_networking::{MsgResponder, NetworkEvent, SwarmDriver, SwarmLocalState};
+use sn_networking::{
+    MsgResponder, NetworkConfig, NetworkEvent, PeerMisbehaviour, PeerScore, RecordStoreOptions,
+    RequestLimits, SwarmDriver, SwarmLocalState,
+};
+use sn_peers_acquisition::PeerCacheUpdater;
 use sn_protocol::{
     error::Error as ProtocolError,
     messages::{Cmd, CmdResponse, Query, QueryResponse, ReplicatedData, Request, Response},
     storage::DbcAddress,
     NetworkAddress, PrettyPrintRecordKey,
 };
-use std::{collections::HashSet, net::SocketAddr, path::PathBuf, time::Duration};
-use tokio::task::spawn;
+use std::{collections::HashSet, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, task::spawn};
 
 /// Once a node is started and running, the user obtains
 /// a `NodeRunning` object which can be used to interact with it.
@@ -25,6 +32,7 @@ use tokio::task::spawn;
 pub struct RunningNode {
     network: Network,
     node_events_channel: NodeEventsChannel,
+    scrub_stats: Arc<RwLock<ScrubStats>>,
 }
 
 impl RunningNode {
@@ -45,6 +53,11 @@ impl RunningNode {
         self.network.root_dir_path.clone()
     }
 
+    /// Returns the id of the network the node is part of.
+    pub fn network_id(&self) -> String {
+        self.network.network_config.network_id.clone()
+    }
+
     /// Returns a `SwarmLocalState` with some information obtained from swarm's local state.
     pub async fn get_swarm_local_state(&self) -> Result<SwarmLocalState> {
         let state = self.network.get_swarm_local_state().await?;
@@ -61,6 +74,17 @@ impl RunningNode {
         let addresses = self.network.get_all_local_record_addresses().await?;
         Ok(addresses)
     }
+
+    /// Returns the statistics of the integrity checks of the records held by the node
+    pub async fn scrub_stats(&self) -> ScrubStats {
+        self.scrub_stats.read().await.clone()
+    }
+
+    /// Returns the scores of the peers penalised for misbehaving, the worst first
+    pub async fn peer_scores(&self) -> Result<Vec<PeerScore>> {
+        let scores = self.network.get_peer_scores().await?;
+        Ok(scores)
+    }
 }
 
 impl Node {
@@ -76,21 +100,36 @@ impl Node {
     /// # Errors
     ///
     /// Returns an error if there is a problem initializing the `SwarmDriver`.
+    #[allow(clippy::too_many_arguments)]
     pub async fn run(
         keypair: Keypair,
         addr: SocketAddr,
         initial_peers: Vec<Multiaddr>,
         local: bool,
         root_dir: PathBuf,
+        network_config: NetworkConfig,
+        store_options: RecordStoreOptions,
+        request_limits: RequestLimits,
     ) -> Result<RunningNode> {
-        let (network, mut network_event_receiver, swarm_driver) =
-            SwarmDriver::new(keypair, addr, local, root_dir)?;
+        let peer_cache = PeerCacheUpdater::spawn(&root_dir);
+        let (network, mut network_event_receiver, swarm_driver) = SwarmDriver::new(
+            keypair,
+            addr,
+            local,
+            root_dir,
+            network_config,
+            store_options,
+            request_limits,
+        )?;
         let node_events_channel = NodeEventsChannel::default();
 
+        let scrub_stats = Arc::new(RwLock::new(ScrubStats::default()));
         let node = Self {
             network: network.clone(),
             events_channel: node_events_channel.clone(),
             initial_peers,
+            scrub_stats: scrub_stats.clone(),
+            peer_cache,
         };
 
         let network_clone = network.clone();
@@ -98,6 +137,16 @@ impl Node {
         let mut rng = StdRng::from_entropy();
 
         let _handle = spawn(swarm_driver.run());
+
+        let scrubbing_node = node.clone();
+        let _handle = spawn(async move {
+            loop {
+                tokio::time::sleep(SCRUB_INTERVAL).await;
+                if let Err(err) = scrubbing_node.scrub_records().await {
+                    error!("Error while scrubbing the locally stored records: {err:?}");
+                }
+            }
+        });
         let _handle = spawn(async move {
             // use a random inactivity timeout to ensure that the nodes do not sync when messages
             // are being transmitted.
@@ -147,6 +196,7 @@ impl Node {
         Ok(RunningNode {
             network,
             node_events_channel,
+            scrub_stats,
         })
     }
 
@@ -164,8 +214,9 @@ impl Node {
                     error!("Error while handling NetworkEvent::ResponseReceived {err:?}");
                 }
             }
-            NetworkEvent::PeerAdded(peer_id) => {
+            NetworkEvent::PeerAdded(peer_id, addrs) => {
                 Marker::PeerAddedToRoutingTable(peer_id).log();
+                self.peer_cache.peer_added(peer_id, addrs);
 
                 if let Err(err) = self.try_trigger_replication(peer_id, false).await {
                     error!("During CloseGroupUpdate, error while triggering replication {err:?}");
@@ -173,6 +224,7 @@ impl Node {
             }
             NetworkEvent::PeerRemoved(peer_id) => {
                 Marker::PeerRemovedFromRoutingTable(peer_id).log();
+                self.peer_cache.peer_removed(peer_id);
                 // During a node restart, the new node got added before the old one got removed.
                 // If the old one is `pushed out of close_group by the new one`, then the records
                 // that being close to the old one won't got replicated during the CloseGroupUpdate
@@ -208,61 +260,56 @@ impl Node {
                     self.events_channel.broadcast(NodeEvent::BehindNat);
                 }
             }
-            NetworkEvent::UnverifiedRecord(record) => {
+            NetworkEvent::UnverifiedRecord { record, source } => {
                 let key = PrettyPrintRecordKey::from(record.key.clone());
                 match self.validate_and_store_record(record, true).await {
                     Ok(cmdok) => trace!("UnverifiedRecord {key:?} stored with {cmdok:?}."),
                     Err(err) => {
-                        trace!("UnverifiedRecord {key:?} failed to be stored with error {err:?}.")
+                        trace!("UnverifiedRecord {key:?} failed to be stored with error {err:?}.");
+                        if let Some(peer_id) = source {
+                            self.report_if_invalid_record(peer_id, &err);
+                        }
                     }
                 }
             }
+            NetworkEvent::RecordsRecovered {
+                recovered,
+                quarantined,
+            } => {
+                info!(
+                    "Recovered {recovered} records from local storage, quarantined {quarantined}"
+                );
+                self.events_channel.broadcast(NodeEvent::RecordsRecovered {
+                    recovered,
+                    quarantined,
+                });
+            }
+        }
+    }
+
+    // Penalises the peer which sent a record, if it failed validation.
+    fn report_if_invalid_record(&self, peer_id: PeerId, err: &ProtocolError) {
+        if !is_invalid_record_error(err) {
+            return;
+        }
+        warn!("Peer {peer_id:?} sent an invalid record: {err:?}");
+        if let Err(err) = self
+            .network
+            .report_peer(peer_id, PeerMisbehaviour::InvalidRecord)
+        {
+            warn!("Failed to report peer {peer_id:?}: {err:?}");
         }
     }
 
     // Handle the response that was not awaited at the call site
     async fn handle_response(&self, response: Response) -> Result<()> {
         match response {
-            Response::Query(QueryResponse::GetReplicatedData(Ok((_holder, replicated_data)))) => {
-                match replicated_data {
-                    ReplicatedData::Chunk(chunk_with_payment) => {
-                        let chunk_addr = *chunk_with_payment.chunk.address();
-                        debug!("Chunk received for replication: {:?}", chunk_addr.xorname());
-
-                        let success = self
-                            .validate_and_store_chunk(chunk_with_payment, false)
-                            .await?;
-                        trace!("ReplicatedData::Chunk with {chunk_addr:?} has been validated and stored. {success:?}");
-                    }
-                    ReplicatedData::DbcSpend(signed_spend) => {
-                        if let Some(spend) = signed_spend.first() {
-                            let dbc_addr = DbcAddress::from_dbc_id(spend.dbc_id());
-                            debug!(
-                                "DbcSpend received for replication: {:?}",
-                                dbc_addr.xorname()
-                            );
-                            let addr = NetworkAddress::from_dbc_address(dbc_addr);
-
-                            let success = self.validate_and_store_spends(signed_spend).await?;
-                            trace!("ReplicatedData::Dbc with {addr:?} has been validated and stored. {success:?}");
-                        } else {
-                            // Put validations make sure that we have >= 1 spends and with the same
-                            // dbc_id
-                            error!("Got ReplicatedData::DbcSpend with zero elements");
-                            return Ok(());
-                        }
-                    }
-                    ReplicatedData::Register(register) => {
-                        let register_addr = *register.address();
-                        debug!(
-                            "Register received for replication: {:?}",
-                            register_addr.xorname()
-                        );
-
-                        let success = self.validate_and_store_register(register).await?;
-                        trace!("ReplicatedData::Register with {register_addr:?} has been validated and stored. {success:?}");
-                    }
+            Response::Query(QueryResponse::GetReplicatedData(Ok((holder, replicated_data)))) => {
+                let result = self.store_replicated_data(replicated_data).await;
+                if let (Err(err), Some(peer_id)) = (&result, holder.as_peer_id()) {
+                    self.report_if_invalid_record(peer_id, err);
                 }
+                result?;
             }
             Response::Query(QueryResponse::GetReplicatedData(Err(
                 ProtocolError::ReplicatedDataNotFound { holder, address },
@@ -283,6 +330,55 @@ impl Node {
         Ok(())
     }
 
+    // Validate and store the data fetched from a holder for replication
+    async fn store_replicated_data(
+        &self,
+        replicated_data: ReplicatedData,
+    ) -> Result<(), ProtocolError> {
+        match replicated_data {
+            ReplicatedData::Chunk(chunk_with_payment) => {
+                let chunk_addr = *chunk_with_payment.chunk.address();
+                debug!("Chunk received for replication: {:?}", chunk_addr.xorname());
+
+                let success = self
+                    .validate_and_store_chunk(chunk_with_payment, false)
+                    .await?;
+                trace!("ReplicatedData::Chunk with {chunk_addr:?} has been validated and stored. {success:?}");
+            }
+            ReplicatedData::DbcSpend(signed_spend) => {
+                if let Some(spend) = signed_spend.first() {
+                    let dbc_addr = DbcAddress::from_dbc_id(spend.dbc_id());
+                    debug!(
+                        "DbcSpend received for replication: {:?}",
+                        dbc_addr.xorname()
+                    );
+                    let addr = NetworkAddress::from_dbc_address(dbc_addr);
+
+                    let success = self.validate_and_store_spends(signed_spend).await?;
+                    trace!("ReplicatedData::Dbc with {addr:?} has been validated and stored. {success:?}");
+                } else {
+                    // Put validations make sure that we have >= 1 spends and with the same
+                    // dbc_id
+                    error!("Got ReplicatedData::DbcSpend with zero elements");
+                    return Ok(());
+                }
+            }
+            ReplicatedData::Register(register_with_payment) => {
+                let register_addr = *register_with_payment.register.address();
+                debug!(
+                    "Register received for replication: {:?}",
+                    register_addr.xorname()
+                );
+
+                let success = self
+                    .validate_and_store_register(register_with_payment, false)
+                    .await?;
+                trace!("ReplicatedData::Register with {register_addr:?} has been validated and stored. {success:?}");
+            }
+        }
+        Ok(())
+    }
+
     async fn handle_request(&self, request: Request, response_channel: MsgResponder) {
         trace!("Handling request: {request:?}");
         let response = match request {
@@ -294,9 +390,9 @@ impl Node {
 
     async fn handle_query(&self, query: Query) -> Response {
         let resp: QueryResponse = match query {
-            Query::GetStoreCost(_address) => {
+            Query::GetStoreCost(address) => {
                 trace!("Got GetStoreCost");
-                let result = self.current_storecost().await;
+                let result = self.current_storecost_quote(address).await;
                 QueryResponse::GetStoreCost(result)
             }
             Query::GetReplicatedData {
@@ -335,6 +431,13 @@ impl Node {
                 // if we do not send a response, we can cause connection failures.
                 CmdResponse::Replicate(Ok(()))
             }
+            Cmd::PutRecord { address, .. } => {
+                // The `SwarmDriver` accepts the records itself, sending them to be validated.
+                warn!("PutRecord cmd for {address:?} unexpectedly passed on to the node");
+                CmdResponse::PutRecord(Err(ProtocolError::RecordNotAccepted(
+                    "not handled by the node".to_string(),
+                )))
+            }
         };
 
         Marker::NodeCmdResponded(&resp).log();
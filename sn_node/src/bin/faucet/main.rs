@@ -11,7 +11,9 @@ mod faucet_server;
 use clap::{Parser, Subcommand};
 use eyre::{eyre, Result};
 use faucet_server::run_faucet_server;
-use sn_client::{get_tokens_from_faucet, load_faucet_wallet_from_genesis_wallet, Client};
+use sn_client::{
+    get_tokens_from_faucet, load_faucet_wallet_from_genesis_wallet, Client, NetworkConfigArgs,
+};
 use sn_dbc::Token;
 use sn_logging::{init_logging, LogFormat, LogOutputDest};
 use sn_peers_acquisition::{parse_peer_addr, PeersArgs};
@@ -58,7 +60,9 @@ async fn main() -> Result<()> {
     info!("Instantiating a SAFE Test Faucet...");
 
     let secret_key = bls::SecretKey::random();
-    let client = Client::new(secret_key, Some(opt.peers.peers), None).await?;
+    let peers = opt.peers.get_peers(None).await;
+    let network_config = opt.network_config.load()?;
+    let client = Client::new(secret_key, Some(peers), None, None, network_config).await?;
 
     faucet_cmds(opt.cmd, &client).await?;
 
@@ -83,6 +87,9 @@ struct Opt {
     #[command(flatten)]
     peers: PeersArgs,
 
+    #[command(flatten)]
+    network_config: NetworkConfigArgs,
+
     /// Available sub commands.
     #[clap(subcommand)]
     pub cmd: SubCmd,
@@ -17,7 +17,10 @@ use libp2p::{identity::Keypair, Multiaddr, PeerId};
 #[cfg(feature = "metrics")]
 use sn_logging::metrics::init_metrics;
 use sn_logging::{parse_log_format, LogFormat, LogOutputDest};
-use sn_node::{Marker, Node, NodeEvent, NodeEventsReceiver};
+use sn_node::{
+    Marker, NetworkConfig, NetworkConfigArgs, Node, NodeEvent, NodeEventsReceiver,
+    RecordStorageKind, RecordStoreOptions, RequestLimits,
+};
 use sn_peers_acquisition::{parse_peer_addr, PeersArgs};
 use std::{
     env,
@@ -114,6 +117,9 @@ struct Opt {
     #[command(flatten)]
     peers: PeersArgs,
 
+    #[command(flatten)]
+    network_config: NetworkConfigArgs,
+
     /// Enable the admin/control RPC service by providing an IP and port for it to listen on.
     ///
     /// The RPC service can be used for querying information about the running node.
@@ -125,6 +131,53 @@ struct Opt {
     /// When this flag is set, we will not filter out local addresses that we observe.
     #[clap(long)]
     local: bool,
+
+    /// Specify the backend used to store records on disk.
+    ///
+    /// Valid values are "flat-file", storing one file per record, or "sled", an embedded database.
+    ///
+    /// Switching backends does not migrate the records already stored by the node.
+    #[clap(long, default_value_t = RecordStorageKind::FlatFile, verbatim_doc_comment)]
+    storage_backend: RecordStorageKind,
+
+    /// Specify the maximum number of records the node stores.
+    ///
+    /// If the argument is not used, the default capacity will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    max_records: Option<usize>,
+
+    /// Specify the maximum number of bytes the node stores, summing the size of all its records.
+    ///
+    /// If the argument is not used, the default capacity will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    max_storage_bytes: Option<u64>,
+
+    /// Specify the maximum number of connections a single peer can establish with the node.
+    ///
+    /// If the argument is not used, the default limit will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    max_connections_per_peer: Option<u32>,
+
+    /// Specify the maximum number of requests of a single peer the node handles at the same time.
+    ///
+    /// Requests beyond it are rejected. The peers of the node's close group are exempt.
+    /// If the argument is not used, the default limit will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    max_in_flight_requests_per_peer: Option<usize>,
+
+    /// Specify the number of requests per second a single peer is allowed on average.
+    ///
+    /// Requests beyond it are rejected. The peers of the node's close group are exempt.
+    /// If the argument is not used, the default rate will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    max_requests_per_sec_per_peer: Option<u32>,
+
+    /// Specify the number of requests a single peer is allowed in a burst, above its average rate.
+    ///
+    /// Requests beyond it are rejected. The peers of the node's close group are exempt.
+    /// If the argument is not used, the default burst will be applied.
+    #[clap(long, verbatim_doc_comment)]
+    request_burst_per_peer: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -175,6 +228,13 @@ fn main() -> Result<()> {
         }
     }
 
+    // Create a tokio runtime per `start_node` attempt, this ensures
+    // any spawned tasks are closed before we would attempt to run
+    // another process with these args.
+    let rt = Runtime::new()?;
+
+    // Fall back to the peers file, the network contacts, or the peers cached during earlier runs of the node.
+    opt.peers.peers = rt.block_on(opt.peers.get_peers(Some(&root_dir)));
     if opt.peers.peers.is_empty() {
         if !cfg!(feature = "local-discovery") {
             warn!("No peers given. As `local-discovery` feature is disabled, we will not be able to connect to the network.");
@@ -184,6 +244,33 @@ fn main() -> Result<()> {
     }
     let initial_peers = opt.peers.peers.clone();
 
+    let network_config = opt.network_config.load()?;
+
+    let default_store_options = RecordStoreOptions::with_network_config(&network_config);
+    let store_options = RecordStoreOptions {
+        storage_kind: opt.storage_backend,
+        max_records: opt.max_records.unwrap_or(default_store_options.max_records),
+        max_storage_bytes: opt
+            .max_storage_bytes
+            .unwrap_or(default_store_options.max_storage_bytes),
+    };
+
+    let default_request_limits = RequestLimits::default();
+    let request_limits = RequestLimits {
+        max_connections_per_peer: opt
+            .max_connections_per_peer
+            .unwrap_or(default_request_limits.max_connections_per_peer),
+        max_in_flight_requests_per_peer: opt
+            .max_in_flight_requests_per_peer
+            .unwrap_or(default_request_limits.max_in_flight_requests_per_peer),
+        max_requests_per_sec_per_peer: opt
+            .max_requests_per_sec_per_peer
+            .unwrap_or(default_request_limits.max_requests_per_sec_per_peer),
+        request_burst_per_peer: opt
+            .request_burst_per_peer
+            .unwrap_or(default_request_limits.request_burst_per_peer),
+    };
+
     let msg = format!(
         "Running {} v{}",
         env!("CARGO_BIN_NAME"),
@@ -194,10 +281,6 @@ fn main() -> Result<()> {
 
     info!("Node started with initial_peers {initial_peers:?}");
 
-    // Create a tokio runtime per `start_node` attempt, this ensures
-    // any spawned tasks are closed before we would attempt to run
-    // another process with these args.
-    let rt = Runtime::new()?;
     #[cfg(feature = "metrics")]
     rt.spawn(init_metrics(std::process::id()));
     rt.block_on(start_node(
@@ -208,6 +291,9 @@ fn main() -> Result<()> {
         opt.local,
         &log_output_dest,
         root_dir,
+        network_config,
+        store_options,
+        request_limits,
     ))?;
 
     // actively shut down the runtime
@@ -222,6 +308,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 /// Start a node with the given configuration.
 async fn start_node(
     keypair: Keypair,
@@ -231,11 +318,24 @@ async fn start_node(
     local: bool,
     log_output_dest: &str,
     root_dir: PathBuf,
+    network_config: NetworkConfig,
+    store_options: RecordStoreOptions,
+    request_limits: RequestLimits,
 ) -> Result<()> {
     let started_instant = std::time::Instant::now();
 
     info!("Starting node ...");
-    let running_node = Node::run(keypair, node_socket_addr, peers, local, root_dir).await?;
+    let running_node = Node::run(
+        keypair,
+        node_socket_addr,
+        peers,
+        local,
+        root_dir,
+        network_config,
+        store_options,
+        request_limits,
+    )
+    .await?;
 
     // write the PID to the root dir
     let pid = std::process::id();
@@ -312,18 +412,7 @@ fn monitor_node_events(mut node_events_rx: NodeEventsReceiver, ctrl_tx: mpsc::Se
                     }
                 }
                 Ok(NodeEvent::BehindNat) => {
-                    if let Err(err) = ctrl_tx
-                        .send(NodeCtrl::Stop {
-                            delay: Duration::from_secs(1),
-                            cause: eyre!("We have been determined to be behind a NAT. This means we are not reachable externally by other nodes. In the future, the network will implement relays that allow us to still join the network."),
-                        })
-                        .await
-                    {
-                        error!(
-                            "Failed to send node control msg to safenode bin main thread: {err}"
-                        );
-                        break;
-                    }
+                    warn!("We have been determined to be behind a NAT. We are not reachable directly by other nodes, so a relay will be reserved to reach us through.");
                 }
                 Ok(event) => {
                     /* we ignore other events */
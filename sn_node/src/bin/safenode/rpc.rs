@@ -15,7 +15,7 @@ use std::{
     env,
     net::SocketAddr,
     process,
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 use tokio::sync::mpsc::{self, Sender};
 use tokio_stream::wrappers::ReceiverStream;
@@ -25,8 +25,9 @@ use tracing::{debug, info, trace};
 use safenode_proto::safe_node_server::{SafeNode, SafeNodeServer};
 use safenode_proto::{
     NetworkInfoRequest, NetworkInfoResponse, NodeEvent, NodeEventsRequest, NodeInfoRequest,
-    NodeInfoResponse, RecordAddressesRequest, RecordAddressesResponse, RestartRequest,
-    RestartResponse, StopRequest, StopResponse, UpdateRequest, UpdateResponse,
+    NodeInfoResponse, PeerScore, PeerScoresRequest, PeerScoresResponse, RecordAddressesRequest,
+    RecordAddressesResponse, RestartRequest, RestartResponse, ScrubStatsRequest,
+    ScrubStatsResponse, StopRequest, StopResponse, UpdateRequest, UpdateResponse,
 };
 
 // this includes code generated from .proto files
@@ -64,6 +65,7 @@ impl SafeNode for SafeNodeRpcService {
             pid: process::id(),
             bin_version: env!("CARGO_PKG_VERSION").to_string(),
             uptime_secs: self.started_instant.elapsed().as_secs(),
+            network_id: self.running_node.network_id(),
         });
 
         Ok(resp)
@@ -153,6 +155,60 @@ impl SafeNode for SafeNodeRpcService {
         Ok(Response::new(RecordAddressesResponse { addresses }))
     }
 
+    async fn scrub_stats(
+        &self,
+        request: Request<ScrubStatsRequest>,
+    ) -> Result<Response<ScrubStatsResponse>, Status> {
+        trace!(
+            "RPC request received at {}: {:?}",
+            self.addr,
+            request.get_ref()
+        );
+
+        let stats = self.running_node.scrub_stats().await;
+        let last_run_secs = stats
+            .last_run
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs());
+
+        Ok(Response::new(ScrubStatsResponse {
+            runs: stats.runs,
+            records_checked: stats.records_checked,
+            corrupt_records: stats.corrupt_records,
+            last_run_secs,
+        }))
+    }
+
+    async fn peer_scores(
+        &self,
+        request: Request<PeerScoresRequest>,
+    ) -> Result<Response<PeerScoresResponse>, Status> {
+        trace!(
+            "RPC request received at {}: {:?}",
+            self.addr,
+            request.get_ref()
+        );
+
+        let scores = match self.running_node.peer_scores().await {
+            Ok(scores) => scores
+                .into_iter()
+                .map(|score| PeerScore {
+                    peer_id: score.peer_id.to_bytes(),
+                    score: score.score,
+                    blocked: score.blocked,
+                })
+                .collect(),
+            Err(err) => {
+                return Err(Status::new(
+                    Code::Internal,
+                    format!("Failed to get the peer scores: {err}"),
+                ))
+            }
+        };
+
+        Ok(Response::new(PeerScoresResponse { scores }))
+    }
+
     async fn stop(&self, request: Request<StopRequest>) -> Result<Response<StopResponse>, Status> {
         trace!(
             "RPC request received at {}: {:?}",
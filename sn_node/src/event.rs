@@ -58,7 +58,15 @@ pub enum NodeEvent {
     /// One of the sub event channel closed and unrecoverable.
     ChannelClosed,
     /// AutoNAT discovered we are behind a NAT, thus private.
+    /// The node is then reached through a relay.
     BehindNat,
+    /// The records held in local storage before starting have been recovered.
+    RecordsRecovered {
+        /// Number of valid records recovered
+        recovered: usize,
+        /// Number of corrupt records quarantined
+        quarantined: usize,
+    },
 }
 
 impl NodeEvent {
@@ -7,26 +7,45 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::Node;
-use sn_dbc::{SignedSpend, Token};
+use sn_dbc::SignedSpend;
 use sn_protocol::{
     error::{Error, Result},
-    messages::ReplicatedData,
-    storage::{try_deserialize_record, ChunkWithPayment, DbcAddress, RecordHeader, RecordKind},
+    messages::{ReplicatedData, StoreCostQuote, STORE_COST_QUOTE_VALIDITY},
+    storage::{
+        try_deserialize_record, ChunkWithPayment, DbcAddress, RecordHeader, RecordKind,
+        RegisterWithPayment,
+    },
     NetworkAddress, PrettyPrintRecordKey,
 };
-use sn_registers::SignedRegister;
+use std::time::SystemTime;
 
 impl Node {
     /// Get the current storecost in nanos from our local kademlia store
-    /// Returns cost and our node's signature over that cost
-    pub(crate) async fn current_storecost(&self) -> Result<Token> {
+    /// Returns a quote of the cost for the given address, signed by our node
+    pub(crate) async fn current_storecost_quote(
+        &self,
+        address: NetworkAddress,
+    ) -> Result<StoreCostQuote> {
         let cost = self
             .network
             .get_local_storecost()
             .await
             .map_err(|_| Error::GetStoreCostFailed)?;
 
-        Ok(cost)
+        let expiry = SystemTime::now() + STORE_COST_QUOTE_VALIDITY;
+        let signature = self
+            .network
+            .sign(&StoreCostQuote::bytes_for_signing(&address, cost, expiry))
+            .map_err(|_| Error::SignStoreCostFailed)?;
+
+        Ok(StoreCostQuote {
+            address,
+            cost,
+            expiry,
+            peer: NetworkAddress::from_peer(self.network.peer_id),
+            public_key: self.network.encoded_public_key(),
+            signature,
+        })
     }
 
     pub(crate) async fn get_spend_from_network(
@@ -109,9 +128,9 @@ impl Node {
                 Ok(ReplicatedData::DbcSpend(spends))
             }
             RecordKind::Register => {
-                let register =
-                    try_deserialize_record::<SignedRegister>(&record).map_err(|_| error)?;
-                Ok(ReplicatedData::Register(register))
+                let register_with_payment =
+                    try_deserialize_record::<RegisterWithPayment>(&record).map_err(|_| error)?;
+                Ok(ReplicatedData::Register(register_with_payment))
             }
         }
     }
@@ -50,16 +50,24 @@ mod get_validation;
 mod log_markers;
 mod put_validation;
 mod replication;
+mod scrub;
 mod spends;
 
 pub use self::{
     api::RunningNode,
     event::{NodeEvent, NodeEventsChannel, NodeEventsReceiver},
     log_markers::Marker,
+    scrub::ScrubStats,
+};
+pub use sn_networking::{
+    NetworkConfig, NetworkConfigArgs, RecordStorageKind, RecordStoreOptions, RequestLimits,
 };
 
 use libp2p::Multiaddr;
 use sn_networking::Network;
+use sn_peers_acquisition::PeerCacheUpdater;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 /// `Node` represents a single node in the distributed network. It handles
 /// network events, processes incoming requests, interacts with the data
@@ -70,4 +78,8 @@ pub struct Node {
     events_channel: NodeEventsChannel,
     /// Peers that are dialed at startup of node.
     initial_peers: Vec<Multiaddr>,
+    /// Statistics of the integrity checks of the locally stored records.
+    scrub_stats: Arc<RwLock<ScrubStats>>,
+    /// Peers recently added to the routing table, persisted for the node to bootstrap from on restart.
+    peer_cache: PeerCacheUpdater,
 }
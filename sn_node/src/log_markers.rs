@@ -7,7 +7,10 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use libp2p::{kad::RecordKey, PeerId};
-use sn_protocol::messages::{Cmd, CmdResponse};
+use sn_protocol::{
+    messages::{Cmd, CmdResponse},
+    PrettyPrintRecordKey,
+};
 use std::time::Duration;
 // this gets us to_string easily enough
 use strum::Display;
@@ -44,6 +47,9 @@ pub enum Marker<'a> {
     /// Replication trigger was fired
     ReplicationTriggered,
 
+    /// A locally stored Record failed its integrity check
+    CorruptRecordDetected(&'a PrettyPrintRecordKey),
+
     /// Keys of Records we are fetching to replicate locally
     FetchingKeysForReplication {
         /// fetching_keys_len: number of keys we are fetching
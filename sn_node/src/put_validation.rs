@@ -14,10 +14,10 @@ use libp2p::kad::Record;
 use sn_dbc::{DbcId, DbcTransaction, Hash, SignedSpend, Token};
 use sn_protocol::{
     error::Error as ProtocolError,
-    messages::{CmdOk, MerkleTreeNodesType, PaymentProof},
+    messages::{CmdOk, MerkleTreeNodesType, PaymentProof, StoreCostQuote},
     storage::{
         try_deserialize_record, try_serialize_record, ChunkWithPayment, DbcAddress, RecordHeader,
-        RecordKind,
+        RecordKind, RegisterWithPayment,
     },
     NetworkAddress, PrettyPrintRecordKey,
 };
@@ -26,10 +26,32 @@ use sn_transfers::{
     dbc_genesis::{is_genesis_parent_tx, GENESIS_DBC},
     payment_proof::validate_payment_proof,
 };
-use std::collections::{BTreeSet, HashSet};
+use std::{
+    collections::{BTreeSet, HashSet},
+    time::SystemTime,
+};
 use tokio::task::JoinSet;
 use xor_name::XorName;
 
+/// Whether the validation error is down to the record itself being invalid,
+/// rather than to the node failing to check or store it.
+pub(crate) fn is_invalid_record_error(err: &ProtocolError) -> bool {
+    matches!(
+        err,
+        ProtocolError::RecordHeaderParsingFailed
+            | ProtocolError::RecordParsingFailed
+            | ProtocolError::RecordKeyMismatch
+            | ProtocolError::RecordKindMismatch(_)
+            | ProtocolError::RegisterInvalid(_)
+            | ProtocolError::SpendSignatureInvalid(_)
+            | ProtocolError::SpendIsEmpty
+            | ProtocolError::PaymentProofTxMismatch(_)
+            | ProtocolError::PaymentProofWithoutInputs(_)
+            | ProtocolError::PaymentProofInvalidFeeOutput(_)
+            | ProtocolError::InvalidPaymentProof { .. }
+    )
+}
+
 impl Node {
     /// Validate and store a record to the RecordStore
     pub(crate) async fn validate_and_store_record(
@@ -71,18 +93,21 @@ impl Node {
                 self.validate_and_store_spends(signed_spends).await
             }
             RecordKind::Register => {
-                let register = try_deserialize_record::<SignedRegister>(&record)?;
+                let register_with_payment = try_deserialize_record::<RegisterWithPayment>(&record)?;
 
                 // check if the deserialized value's RegisterAddress matches the record's key
-                let key =
-                    NetworkAddress::from_register_address(*register.address()).to_record_key();
+                let key = NetworkAddress::from_register_address(
+                    *register_with_payment.register.address(),
+                )
+                .to_record_key();
                 if record.key != key {
                     warn!(
                         "Record's key does not match with the value's RegisterAddress, ignoring PUT."
                     );
                     return Err(ProtocolError::RecordKeyMismatch);
                 }
-                self.validate_and_store_register(register).await
+                self.validate_and_store_register(register_with_payment, validate_payment)
+                    .await
             }
         }
     }
@@ -119,8 +144,13 @@ impl Node {
             return Ok(CmdOk::DataAlreadyPresent);
         }
 
-        self.chunk_payment_validation(&chunk_with_payment, validate_payment_amount)
-            .await?;
+        self.payment_validation(
+            chunk_name,
+            NetworkAddress::from_chunk_address(chunk_addr),
+            &chunk_with_payment.payment,
+            validate_payment_amount,
+        )
+        .await?;
 
         let record = Record {
             key,
@@ -141,11 +171,13 @@ impl Node {
         Ok(CmdOk::StoredSuccessfully)
     }
 
-    /// Validate and store a `Register` to the RecordStore
+    /// Validate and store a `RegisterWithPayment` to the RecordStore
     pub(crate) async fn validate_and_store_register(
         &self,
-        register: SignedRegister,
+        register_with_payment: RegisterWithPayment,
+        validate_payment_amount: bool,
     ) -> Result<CmdOk, ProtocolError> {
+        let RegisterWithPayment { register, payment } = register_with_payment;
         let reg_addr = register.address();
         debug!("Validating and storing register {reg_addr:?}");
 
@@ -161,13 +193,29 @@ impl Node {
             })?;
 
         // check register and merge if needed
-        let updated_register = match self.register_validation(&register, present_locally).await? {
+        let updated_register = match self
+            .register_validation(&register, payment, present_locally)
+            .await?
+        {
             Some(reg) => reg,
             None => {
                 return Ok(CmdOk::DataAlreadyPresent);
             }
         };
 
+        // The payment for the Register is only checked the first time we store it, later edits
+        // being merged with the Register we already hold, along with its validated payment.
+        // Whatever ops it comes with, its amount is checked unless it is being replicated to us.
+        if !present_locally {
+            self.payment_validation(
+                reg_addr.xorname(),
+                NetworkAddress::from_register_address(*reg_addr),
+                &updated_register.payment,
+                validate_payment_amount,
+            )
+            .await?;
+        }
+
         // store in kad
         let record = Record {
             key,
@@ -272,19 +320,21 @@ impl Node {
         Ok(CmdOk::StoredSuccessfully)
     }
 
-    /// Perform validations on the provided `ChunkWithPayment`.
-    async fn chunk_payment_validation(
+    /// Perform validations on the `PaymentProof` provided for storing the content of the given name,
+    /// at the given address.
+    async fn payment_validation(
         &self,
-        chunk_with_payment: &ChunkWithPayment,
+        addr_name: XorName,
+        address: NetworkAddress,
+        payment: &PaymentProof,
         validate_payment_amount: bool,
     ) -> Result<(), ProtocolError> {
         let PaymentProof {
             spent_ids,
             audit_trail,
             path,
-        } = &chunk_with_payment.payment;
-
-        let addr_name = *chunk_with_payment.chunk.name();
+            quotes,
+        } = payment;
 
         // We need to fetch the inputs of the DBC tx in order to obtain the root-hash and
         // other info for verifications of valid payment.
@@ -317,30 +367,19 @@ impl Node {
         }
 
         if let Some(tx) = payment_tx {
-            let acceptable_fee = self
-                .network
-                .get_local_storecost()
-                .await
-                .map_err(|_| ProtocolError::ChunkNotStored(addr_name))?;
+            // The payment is checked against the price quoted to the client before paying,
+            // unless the content is being replicated to us, when only the proof is checked.
+            let acceptable_fee = if validate_payment_amount {
+                self.lowest_acceptable_quote(addr_name, address, quotes)
+                    .await
+                    .ok_or(ProtocolError::PaymentProofWithoutAcceptableQuote(addr_name))?
+            } else {
+                Token::zero()
+            };
             // Check if the fee output id and amount are correct, as well as verify
             // the payment proof corresponds to the fee output and that
             // the fee is sufficient for this chunk.
-            match verify_fee_output_and_proof(addr_name, acceptable_fee, &tx, audit_trail, path) {
-                Ok(tx) => tx,
-                Err(ProtocolError::PaymentProofInsufficientAmount { paid, expected }) => {
-                    if !validate_payment_amount {
-                        return Ok(());
-                    } else {
-                        return Err(ProtocolError::PaymentProofInsufficientAmount {
-                            paid,
-                            expected,
-                        });
-                    }
-                }
-                Err(error) => {
-                    return Err(error);
-                }
-            }
+            verify_fee_output_and_proof(addr_name, acceptable_fee, &tx, audit_trail, path)?;
         } else {
             return Err(ProtocolError::PaymentProofWithoutInputs(addr_name));
         }
@@ -348,11 +387,53 @@ impl Node {
         Ok(())
     }
 
+    /// Returns the lowest cost among the quotes for the content that are validly signed, unexpired,
+    /// and issued by nodes within close group distance of its address, i.e. no farther from it than
+    /// the farthest member of its close group we know of.
+    /// The quotes may have been requested for other content nearby, sharing its close group.
+    async fn lowest_acceptable_quote(
+        &self,
+        addr_name: XorName,
+        address: NetworkAddress,
+        quotes: &[StoreCostQuote],
+    ) -> Option<Token> {
+        if quotes.is_empty() {
+            return None;
+        }
+
+        let close_group_distance = match self.network.get_closest_local_peers(&address).await {
+            Ok(peers) if peers.len() >= self.network.network_config.close_group_size => peers
+                .last()
+                .map(|peer| address.distance(&NetworkAddress::from_peer(*peer))),
+            // Knowing too few peers to tell the close group apart, any of them is close.
+            Ok(_) => None,
+            Err(err) => {
+                warn!("Could not get the close group of {addr_name:?} to check store cost quotes: {err:?}");
+                return None;
+            }
+        };
+
+        let now = SystemTime::now();
+        quotes
+            .iter()
+            .filter(|quote| {
+                let is_close = quote.peer_id().map_or(false, |peer| {
+                    peer == self.network.peer_id
+                        || close_group_distance
+                            .map_or(true, |distance| address.distance(&quote.peer) <= distance)
+                });
+                !quote.has_expired(now) && is_close && quote.verify().is_ok()
+            })
+            .map(|quote| quote.cost)
+            .min()
+    }
+
     async fn register_validation(
         &self,
         register: &SignedRegister,
+        payment: PaymentProof,
         present_locally: bool,
-    ) -> Result<Option<SignedRegister>, ProtocolError> {
+    ) -> Result<Option<RegisterWithPayment>, ProtocolError> {
         // check if register is valid
         let reg_addr = register.address();
         if let Err(e) = register.verify() {
@@ -363,7 +444,10 @@ impl Node {
         // if we don't have it locally return it
         if !present_locally {
             debug!("Register with addr {reg_addr:?} is valid and doesn't exist locally");
-            return Ok(Some(register.to_owned()));
+            return Ok(Some(RegisterWithPayment {
+                register: register.to_owned(),
+                payment,
+            }));
         }
         debug!("Register with addr {reg_addr:?} exists locally, comparing with local version");
 
@@ -381,17 +465,20 @@ impl Node {
                 return Err(ProtocolError::RegisterNotStored(Box::new(*reg_addr)));
             }
         };
-        let local_register: SignedRegister = try_deserialize_record(&record)?;
+        let local: RegisterWithPayment = try_deserialize_record(&record)?;
 
         // merge the two registers
-        let mut merged_register = local_register.clone();
+        let mut merged_register = local.register.clone();
         merged_register.verified_merge(register.to_owned())?;
-        if merged_register == local_register {
+        if merged_register == local.register {
             debug!("Register with addr {reg_addr:?} is the same as the local version");
             Ok(None)
         } else {
             debug!("Register with addr {reg_addr:?} is different from the local version");
-            Ok(Some(merged_register))
+            Ok(Some(RegisterWithPayment {
+                register: merged_register,
+                payment: local.payment,
+            }))
         }
     }
 
@@ -12,7 +12,7 @@ use libp2p::{
     kad::{RecordKey, K_VALUE},
     PeerId,
 };
-use sn_networking::{sort_peers_by_address, CLOSE_GROUP_SIZE};
+use sn_networking::sort_peers_by_address;
 use sn_protocol::{
     messages::{Cmd, Query, Request},
     NetworkAddress,
@@ -59,9 +59,17 @@ impl Node {
             all_peers.push(peer_id);
         }
 
+        // One more than the close group, to find the peer taking over from a removed one,
+        // when there are that many.
+        let close_group_size = self.network.network_config.close_group_size;
+        let expected_entries = if all_peers.len() > close_group_size {
+            close_group_size + 1
+        } else {
+            close_group_size
+        };
         for key in all_records {
             let sorted_based_on_key =
-                sort_peers_by_address(all_peers.clone(), &key, CLOSE_GROUP_SIZE + 1)?;
+                sort_peers_by_address(all_peers.clone(), &key, expected_entries)?;
             trace!("replication: close for {key:?} are: {sorted_based_on_key:?}");
 
             if sorted_based_on_key.contains(&peer_id) {
@@ -0,0 +1,90 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{error::Result, log_markers::Marker, Node};
+use serde::{Deserialize, Serialize};
+use sn_networking::DiskBackedRecordStore;
+use sn_protocol::{NetworkAddress, PrettyPrintRecordKey};
+use std::time::{Duration, SystemTime};
+
+/// Interval between two scrubs of the locally stored records.
+pub(crate) const SCRUB_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Statistics of the integrity checks carried out on the locally stored records.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScrubStats {
+    /// Number of scrubs completed since the node started.
+    pub runs: u64,
+    /// Number of records checked since the node started.
+    pub records_checked: u64,
+    /// Number of corrupt or unreadable records found, deleted and sent to be re-fetched.
+    pub corrupt_records: u64,
+    /// The time the last scrub completed at, if any.
+    pub last_run: Option<SystemTime>,
+}
+
+impl Node {
+    /// Re-verifies every locally stored record. Corrupt copies are deleted,
+    /// and re-fetched from the record's close group through the replication fetcher.
+    pub(crate) async fn scrub_records(&self) -> Result<()> {
+        let addresses = self.network.get_all_local_record_addresses().await?;
+        trace!("Scrubbing {} records", addresses.len());
+
+        let mut records_checked = 0;
+        let mut corrupt_records = 0;
+        for address in addresses {
+            let Some(key) = address.as_record_key() else {
+                continue;
+            };
+            let record = self.network.get_local_record(&key).await?;
+            // The record may have been pruned since the addresses were listed,
+            // otherwise it is still indexed but could not be read from storage.
+            if record.is_none() && !self.network.is_key_present_locally(&key).await? {
+                continue;
+            }
+            records_checked += 1;
+
+            match record {
+                Some(record) if DiskBackedRecordStore::is_record_intact(&record) => continue,
+                Some(_) => {}
+                None => warn!(
+                    "Record {:?} could not be read from storage",
+                    PrettyPrintRecordKey::from(key.clone())
+                ),
+            }
+
+            Marker::CorruptRecordDetected(&PrettyPrintRecordKey::from(key.clone())).log();
+            corrupt_records += 1;
+            self.network.remove_local_record(key)?;
+            self.refetch_from_close_group(address).await?;
+        }
+
+        let mut stats = self.scrub_stats.write().await;
+        stats.runs += 1;
+        stats.records_checked += records_checked;
+        stats.corrupt_records += corrupt_records;
+        stats.last_run = Some(SystemTime::now());
+        debug!("Scrubbed {records_checked} records, {corrupt_records} were corrupt: {stats:?}");
+
+        Ok(())
+    }
+
+    // Adds the key to the replication fetcher for each of the peers of its close group,
+    // which fetches the record from them, or from the network if they fail to provide it.
+    async fn refetch_from_close_group(&self, address: NetworkAddress) -> Result<()> {
+        let close_group = self.network.get_closest_local_peers(&address).await?;
+        for peer in close_group
+            .into_iter()
+            .filter(|peer| *peer != self.network.peer_id)
+        {
+            self.network
+                .add_keys_to_replication_fetcher(peer, vec![address.clone()])?;
+        }
+        Ok(())
+    }
+}
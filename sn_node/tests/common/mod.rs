@@ -14,7 +14,7 @@ pub mod safenode_proto {
 }
 
 use safenode_proto::{safe_node_client::SafeNodeClient, NodeInfoRequest, RestartRequest};
-use sn_client::{load_faucet_wallet_from_genesis_wallet, send, Client};
+use sn_client::{load_faucet_wallet_from_genesis_wallet, send, Client, NetworkConfig};
 use sn_peers_acquisition::parse_peer_addr;
 use sn_transfers::wallet::LocalWallet;
 
@@ -67,9 +67,15 @@ pub async fn get_client() -> Client {
         None
     };
     println!("Client bootstrap with peer {bootstrap_peers:?}");
-    Client::new(secret_key, bootstrap_peers, None)
-        .await
-        .expect("Client shall be successfully created.")
+    Client::new(
+        secret_key,
+        bootstrap_peers,
+        None,
+        None,
+        NetworkConfig::default(),
+    )
+    .await
+    .expect("Client shall be successfully created.")
 }
 
 pub async fn get_wallet(root_dir: &Path) -> LocalWallet {
@@ -54,6 +54,7 @@ const MAX_NUM_OF_QUERY_ATTEMPTS: u8 = 5;
 const TEST_DURATION: Duration = Duration::from_secs(60 * 60); // 1hr
 
 const TRANSFERS_WALLET_INITIAL_BALANCE: u64 = 200_000_000_000;
+const REGISTERS_WALLET_INITIAL_BALANCE: u64 = 200_000_000_000;
 
 type ContentList = Arc<RwLock<VecDeque<NetworkAddress>>>;
 type DbcMap = Arc<RwLock<BTreeMap<DbcAddress, Dbc>>>;
@@ -153,7 +154,23 @@ async fn data_availability_during_churn() -> Result<()> {
         .await?;
         println!("Transfer wallet created");
 
-        create_registers_task(client.clone(), content.clone(), churn_period);
+        println!("Creating registers wallet taking balance from the payment wallet");
+        let registers_wallet_dir = TempDir::new()?;
+        let registers_wallet = get_funded_wallet(
+            &client,
+            get_wallet(paying_wallet_dir.path()).await,
+            registers_wallet_dir.path(),
+            REGISTERS_WALLET_INITIAL_BALANCE,
+        )
+        .await?;
+        println!("Registers wallet created");
+
+        create_registers_task(
+            client.clone(),
+            registers_wallet,
+            content.clone(),
+            churn_period,
+        );
 
         create_dbc_task(
             client.clone(),
@@ -275,7 +292,7 @@ fn create_dbc_task(
         // Create Dbc at a higher frequency than the churning events
         let delay = churn_period / DBC_CREATION_RATIO_TO_CHURN;
 
-        let mut wallet_client = WalletClient::new(client.clone(), transfers_wallet);
+        let mut wallet_client = WalletClient::new(client.clone(), transfers_wallet).await;
 
         loop {
             sleep(delay).await;
@@ -296,10 +313,16 @@ fn create_dbc_task(
 }
 
 // Spawns a task which periodically creates Registers at random locations.
-fn create_registers_task(client: Client, content: ContentList, churn_period: Duration) {
+fn create_registers_task(
+    client: Client,
+    paying_wallet: LocalWallet,
+    content: ContentList,
+    churn_period: Duration,
+) {
     let _handle = tokio::spawn(async move {
         // Create Registers at a higher frequency than the churning events
         let delay = churn_period / REGISTER_CREATION_RATIO_TO_CHURN;
+        let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
         loop {
             let meta = XorName(rand::random());
@@ -309,7 +332,10 @@ fn create_registers_task(client: Client, content: ContentList, churn_period: Dur
             println!("Creating Register at {addr:?} in {delay:?}");
             sleep(delay).await;
 
-            match client.create_register(meta, false).await {
+            match client
+                .create_register(meta, &mut wallet_client, false)
+                .await
+            {
                 Ok(_) => content
                     .write()
                     .await
@@ -330,7 +356,7 @@ fn store_chunks_task(
     let _handle = tokio::spawn(async move {
         // Store Chunks at a higher frequency than the churning events
         let delay = churn_period / CHUNK_CREATION_RATIO_TO_CHURN;
-        let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+        let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
         let file_api = Files::new(client);
         let mut rng = OsRng;
@@ -11,7 +11,7 @@ mod common;
 use common::{get_client_and_wallet, init_logging};
 
 use self_encryption::MIN_ENCRYPTABLE_BYTES;
-use sn_client::{Client, Error as ClientError, Files, WalletClient};
+use sn_client::{Client, ClientRegister, Error as ClientError, Files, WalletClient};
 use sn_dbc::{Hash, Token};
 use sn_networking::Error as NetworkError;
 use sn_protocol::storage::{Chunk, ChunkAddress};
@@ -58,7 +58,7 @@ async fn storage_payment_succeeds() -> Result<()> {
         get_client_and_wallet(paying_wallet_dir.path(), paying_wallet_balance).await?;
 
     let balance_before = paying_wallet.balance();
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
     // generate a random number (between 50 and 100) of random addresses
     let mut rng = rand::thread_rng();
@@ -97,7 +97,12 @@ async fn storage_payment_fails() -> Result<()> {
     let storage_cost = Token::from_nano(random_num_of_addrs);
 
     let mut transfer = wallet_client
-        .local_send_storage_payment(storage_cost, Hash::default(), None)
+        .local_send_storage_payment(
+            storage_cost,
+            Hash::default(),
+            random_num_of_addrs as usize,
+            None,
+        )
         .await?;
     assert!(transfer.created_dbcs.is_empty());
 
@@ -124,7 +129,7 @@ async fn storage_payment_proofs_cached_in_wallet() -> Result<()> {
 
     let (client, paying_wallet) =
         get_client_and_wallet(paying_wallet_dir.path(), wallet_original_balance).await?;
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
     // generate a random number (between 50 and 100) of random addresses
     let mut rng = rand::thread_rng();
@@ -156,7 +161,7 @@ async fn storage_payment_proofs_cached_in_wallet() -> Result<()> {
         .all(|name| paying_wallet.get_payment_proof(name) == proofs.get(name)));
 
     // now let's request to pay for all addresses, even that we've already paid for a subset of them
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
     let (proofs, storage_cost) = wallet_client
         .pay_for_storage(random_content_addrs.iter(), false)
         .await?;
@@ -187,7 +192,7 @@ async fn storage_payment_chunk_upload_succeeds() -> Result<()> {
 
     let (client, paying_wallet) =
         get_client_and_wallet(paying_wallet_dir.path(), paying_wallet_balance).await?;
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
     let (files_api, content_bytes, content_addr, chunks) = random_content(&client)?;
 
@@ -213,7 +218,7 @@ async fn storage_payment_chunk_upload_fails() -> Result<()> {
 
     let (client, paying_wallet) =
         get_client_and_wallet(paying_wallet_dir.path(), paying_wallet_balance).await?;
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
 
     let (files_api, content_bytes, content_addr, chunks) = random_content(&client)?;
 
@@ -284,7 +289,7 @@ async fn storage_payment_chunk_upload_fails() -> Result<()> {
         sn_transfers::payment_proof::build_payment_proofs(chunks.iter().map(|c| c.name()))?;
     let transfer = wallet_client
         .into_wallet()
-        .local_send_storage_payment(Token::from_nano(1), root_hash, None)
+        .local_send_storage_payment(Token::from_nano(1), root_hash, chunks.len(), None)
         .await?;
     client.send(transfer.clone(), false).await?;
     let spent_ids: Vec<_> = transfer.tx.inputs.iter().map(|i| i.dbc_id()).collect();
@@ -312,3 +317,41 @@ async fn storage_payment_chunk_upload_fails() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_payment_register_creation_succeeds() -> Result<()> {
+    let paying_wallet_balance = 50_000_000_000_005;
+    let paying_wallet_dir = TempDir::new()?;
+
+    let (client, paying_wallet) =
+        get_client_and_wallet(paying_wallet_dir.path(), paying_wallet_balance).await?;
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
+
+    let meta = XorName(rand::random());
+    let register = client
+        .create_register(meta, &mut wallet_client, true)
+        .await?;
+
+    assert!(wallet_client.into_wallet().balance() < Token::from_nano(paying_wallet_balance));
+    client.get_register(*register.address()).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_payment_register_creation_fails_without_payment() -> Result<()> {
+    let paying_wallet_dir = TempDir::new()?;
+
+    let (client, _paying_wallet) =
+        get_client_and_wallet(paying_wallet_dir.path(), 50_000_000_000_006).await?;
+
+    let meta = XorName(rand::random());
+    let mut register = ClientRegister::create(client.clone(), meta)?;
+    assert!(matches!(
+        register.sync(true).await,
+        Err(ClientError::MissingRegisterPaymentProof(_))
+    ));
+    assert!(client.get_register(*register.address()).await.is_err());
+
+    Ok(())
+}
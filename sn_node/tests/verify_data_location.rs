@@ -24,7 +24,7 @@ use libp2p::{
 use rand::{rngs::OsRng, Rng};
 use sn_client::{Client, Files, WalletClient};
 use sn_logging::{init_logging, LogFormat, LogOutputDest};
-use sn_networking::{sort_peers_by_key, CLOSE_GROUP_SIZE};
+use sn_networking::{sort_peers_by_key, NetworkConfig};
 use sn_protocol::{storage::ChunkAddress, NetworkAddress, PrettyPrintRecordKey};
 use sn_transfers::wallet::LocalWallet;
 use std::{
@@ -156,8 +156,12 @@ fn print_node_close_groups(all_peers: &[PeerId]) {
     for (node_index, peer) in all_peers.iter().enumerate() {
         let node_index = node_index + 1;
         let key = NetworkAddress::from_peer(*peer).as_kbucket_key();
-        let closest_peers = sort_peers_by_key(all_peers.clone(), &key, CLOSE_GROUP_SIZE)
-            .expect("failed to sort peer");
+        let closest_peers = sort_peers_by_key(
+            all_peers.clone(),
+            &key,
+            NetworkConfig::default().close_group_size,
+        )
+        .expect("failed to sort peer");
         let closest_peers_idx = closest_peers
             .iter()
             .map(|peer| all_peers.iter().position(|p| p == peer).unwrap() + 1)
@@ -222,10 +226,13 @@ async fn verify_location(record_holders: &RecordHolders, all_peers: &[PeerId]) -
         for (key, actual_closest_idx) in record_holders.iter() {
             println!("Verifying {:?}", PrettyPrintRecordKey::from(key.clone()));
             let record_key = KBucketKey::from(key.to_vec());
-            let expected_closest_peers =
-                sort_peers_by_key(all_peers.to_vec(), &record_key, CLOSE_GROUP_SIZE)?
-                    .into_iter()
-                    .collect::<BTreeSet<_>>();
+            let expected_closest_peers = sort_peers_by_key(
+                all_peers.to_vec(),
+                &record_key,
+                NetworkConfig::default().close_group_size,
+            )?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
 
             let actual_closest = actual_closest_idx
                 .iter()
@@ -308,7 +315,7 @@ async fn get_all_peer_ids() -> Result<Vec<PeerId>> {
 // Generate a random Chunk and store it to the Network
 async fn store_chunk(client: Client, paying_wallet: LocalWallet, chunk_count: usize) -> Result<()> {
     let mut rng = OsRng;
-    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet);
+    let mut wallet_client = WalletClient::new(client.clone(), paying_wallet).await;
     let file_api = Files::new(client);
 
     let mut uploaded_chunks_count = 0;
@@ -6,9 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod network_contacts;
+mod peer_cache;
+
+pub use peer_cache::{PeerCache, PeerCacheUpdater, PEER_CACHE_FILE_NAME};
+
 use clap::Args;
 use eyre::{eyre, Result};
 use libp2p::{multiaddr::Protocol, Multiaddr};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 
 /// The name of the environment variable that can be used to pass peers to the node.
 pub const SAFE_PEERS_ENV: &str = "SAFE_PEERS";
@@ -19,9 +26,14 @@ pub struct PeersArgs {
     ///
     /// A multiaddr looks like '/ip4/1.2.3.4/tcp/1200/tcp/p2p/12D3KooWRi6wF7yxWLuPSNskXc6kQ5cJ6eaymeMbCRdTnMesPgFx'
     /// where `1.2.3.4` is the IP, `1200` is the port and the (optional) last part is the peer ID.
+    /// Peers listening for QUIC connections can be reached with a '/ip4/1.2.3.4/udp/1200/quic-v1/p2p/<peer_id>'
+    /// multiaddr, when the executable is built with the `quic` feature.
     ///
     /// This argument can be provided multiple times to connect to multiple peers.
     ///
+    /// If no peer is provided, the peers listed in the `--peers-file`, then the ones fetched from
+    /// the `--network-contacts-url`, then the ones cached in the data dir during earlier sessions are used.
+    ///
     /// Note: `SAFE_PEERS` env_var only got picked up
     ///       when `--peers` argument is undefined via the safe/safenode executable.
     ///       OR, during the restart of a node, `SAFE_PEERS` contains new peers not presented
@@ -31,9 +43,68 @@ pub struct PeersArgs {
     ///            it will still be the old value got picked up during node restarting.
     #[clap(long = "peer", value_name = "multiaddr", env = SAFE_PEERS_ENV, value_delimiter = ',', value_parser = parse_peer_addr)]
     pub peers: Vec<Multiaddr>,
+
+    /// A file listing the peers to use for bootstrap, if none are provided through `--peer`.
+    ///
+    /// The file holds one multiaddr per line, or a JSON array of multiaddrs.
+    #[clap(long = "peers-file", value_name = "path")]
+    pub peers_file: Option<PathBuf>,
+
+    /// A URL to fetch the peers to use for bootstrap from, if none are provided through `--peer`
+    /// or `--peers-file`.
+    ///
+    /// The URL serves one multiaddr per line, or a JSON array of multiaddrs.
+    #[clap(long = "network-contacts-url", value_name = "url")]
+    pub network_contacts_url: Option<String>,
+}
+
+impl PeersArgs {
+    /// Returns the peers to bootstrap from, out of the first source providing any of:
+    /// the `--peer` multiaddrs, the `--peers-file`, the `--network-contacts-url`,
+    /// and the peers cached in the `cache_dir` during earlier sessions.
+    ///
+    /// A failing source is logged and skipped. No peers are returned if all of them fail.
+    pub async fn get_peers(&self, cache_dir: Option<&Path>) -> Vec<Multiaddr> {
+        if !self.peers.is_empty() {
+            return self.peers.clone();
+        }
+
+        if let Some(path) = &self.peers_file {
+            match network_contacts::read_peers_file(path) {
+                Ok(peers) => {
+                    info!("Using {} peers listed in {path:?}", peers.len());
+                    return peers;
+                }
+                Err(err) => warn!("Failed to get peers from the peers file: {err}"),
+            }
+        }
+
+        if let Some(url) = &self.network_contacts_url {
+            match network_contacts::fetch_network_contacts(url).await {
+                Ok(peers) => {
+                    info!("Using {} peers fetched from {url}", peers.len());
+                    return peers;
+                }
+                Err(err) => warn!("Failed to get peers from the network contacts: {err}"),
+            }
+        }
+
+        let Some(cache_dir) = cache_dir else {
+            return vec![];
+        };
+        let cached_peers = PeerCache::load(cache_dir).peers();
+        if !cached_peers.is_empty() {
+            info!(
+                "No peers given, using {} peers cached in {cache_dir:?}",
+                cached_peers.len()
+            );
+        }
+        cached_peers
+    }
 }
 
-/// Parse strings like `1.2.3.4:1234` and `/ip4/1.2.3.4/tcp/1234` into a (TCP) multiaddr.
+/// Parse strings like `1.2.3.4:1234` and `/ip4/1.2.3.4/tcp/1234` into a (TCP) multiaddr,
+/// or `/ip4/1.2.3.4/udp/1234/quic-v1` into a QUIC multiaddr.
 pub fn parse_peer_addr(addr: &str) -> Result<Multiaddr> {
     // Parse valid IPv4 socket address, e.g. `1.2.3.4:1234`.
     if let Ok(addr) = addr.parse::<std::net::SocketAddrV4>() {
@@ -42,10 +113,80 @@ pub fn parse_peer_addr(addr: &str) -> Result<Multiaddr> {
         return Ok(multiaddr);
     }
 
-    // Parse any valid multiaddr string, e.g. `/ip4/1.2.3.4/tcp/1234/p2p/<peer_id>`.
+    // Parse any valid multiaddr string, e.g. `/ip4/1.2.3.4/tcp/1234/p2p/<peer_id>`
+    // or `/ip4/1.2.3.4/udp/1234/quic-v1/p2p/<peer_id>`.
     if let Ok(addr) = addr.parse::<Multiaddr>() {
         return Ok(addr);
     }
 
     Err(eyre!("invalid multiaddr or socket address"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use libp2p::PeerId;
+
+    #[tokio::test]
+    async fn get_peers_fallback_order() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let cached_peer = PeerId::random();
+        let mut peer_cache = PeerCache::load(tmp_dir.path());
+        peer_cache.peer_added(cached_peer, &[parse_peer_addr("1.1.1.1:1")?]);
+        peer_cache.save()?;
+
+        let peers_file = tmp_dir.path().join("peers");
+        std::fs::write(&peers_file, "2.2.2.2:2\n")?;
+
+        let mut args = PeersArgs {
+            peers: vec![parse_peer_addr("3.3.3.3:3")?],
+            peers_file: Some(peers_file),
+            network_contacts_url: None,
+        };
+        assert_eq!(
+            args.get_peers(Some(tmp_dir.path())).await,
+            vec![parse_peer_addr("3.3.3.3:3")?]
+        );
+
+        args.peers.clear();
+        assert_eq!(
+            args.get_peers(Some(tmp_dir.path())).await,
+            vec![parse_peer_addr("2.2.2.2:2")?]
+        );
+
+        // A missing peers file falls back to the cache.
+        args.peers_file = Some(tmp_dir.path().join("missing"));
+        assert_eq!(
+            args.get_peers(Some(tmp_dir.path())).await,
+            vec![parse_peer_addr("1.1.1.1:1")?.with(Protocol::P2p(cached_peer))]
+        );
+        assert!(args.get_peers(None).await.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tcp_and_quic_peer_addrs() -> Result<()> {
+        let tcp_addr =
+            Multiaddr::from(std::net::Ipv4Addr::new(1, 2, 3, 4)).with(Protocol::Tcp(1234));
+        assert_eq!(parse_peer_addr("1.2.3.4:1234")?, tcp_addr);
+        assert_eq!(parse_peer_addr("/ip4/1.2.3.4/tcp/1234")?, tcp_addr);
+
+        let quic_addr = parse_peer_addr(
+            "/ip4/1.2.3.4/udp/1234/quic-v1/p2p/12D3KooWRi6wF7yxWLuPSNskXc6kQ5cJ6eaymeMbCRdTnMesPgFx",
+        )?;
+        let protocols = quic_addr.iter().collect::<Vec<_>>();
+        assert!(matches!(
+            protocols.as_slice(),
+            [
+                Protocol::Ip4(_),
+                Protocol::Udp(1234),
+                Protocol::QuicV1,
+                Protocol::P2p(_)
+            ]
+        ));
+
+        assert!(parse_peer_addr("1.2.3.4").is_err());
+        Ok(())
+    }
+}
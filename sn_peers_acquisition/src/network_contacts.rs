@@ -0,0 +1,168 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::parse_peer_addr;
+use eyre::{eyre, Result};
+use libp2p::Multiaddr;
+use std::{collections::HashSet, path::Path, time::Duration};
+use tracing::{debug, warn};
+
+// Number of attempts to fetch the network contacts, before giving up on the URL.
+const MAX_FETCH_ATTEMPTS: usize = 3;
+// Time to wait between two attempts to fetch the network contacts.
+const FETCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+// Timeout of a single attempt to fetch the network contacts.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reads the peers listed in the file, as one multiaddr per line or a JSON array of multiaddrs.
+pub(crate) fn read_peers_file(path: &Path) -> Result<Vec<Multiaddr>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| eyre!("Failed to read the peers file {path:?}: {err}"))?;
+    parse_peers_list(&contents)
+}
+
+/// Fetches the peers listed at the URL, as one multiaddr per line or a JSON array of multiaddrs.
+/// The fetch is retried a few times, as the contacts are usually needed to start at all.
+pub(crate) async fn fetch_network_contacts(url: &str) -> Result<Vec<Multiaddr>> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+
+    let mut attempt = 1;
+    loop {
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        let result = match response {
+            Ok(response) => response.text().await.map_err(|err| eyre!(err)),
+            Err(err) => Err(eyre!(err)),
+        };
+
+        match result {
+            Ok(contents) => return parse_peers_list(&contents),
+            Err(err) if attempt < MAX_FETCH_ATTEMPTS => {
+                warn!("Attempt {attempt} to fetch the network contacts from {url} failed: {err}");
+                attempt += 1;
+                tokio::time::sleep(FETCH_RETRY_DELAY).await;
+            }
+            Err(err) => {
+                return Err(eyre!(
+                    "Failed to fetch the network contacts from {url} after {attempt} attempts: {err}"
+                ));
+            }
+        }
+    }
+}
+
+/// Parses a list of peers, given as one multiaddr per line or a JSON array of multiaddrs.
+///
+/// Blank lines and lines starting with `#` are skipped, invalid entries are logged and skipped,
+/// and duplicates are removed. An error is only returned if no valid peer is listed.
+pub(crate) fn parse_peers_list(contents: &str) -> Result<Vec<Multiaddr>> {
+    let contents = contents.trim();
+    let entries: Vec<String> = if contents.starts_with('[') {
+        serde_json::from_str(contents).map_err(|err| eyre!("Invalid JSON list of peers: {err}"))?
+    } else {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let mut seen = HashSet::new();
+    let peers: Vec<_> = entries
+        .iter()
+        .filter_map(|entry| match parse_peer_addr(entry) {
+            Ok(addr) => Some(addr),
+            Err(err) => {
+                warn!("Skipping invalid peer {entry:?}: {err}");
+                None
+            }
+        })
+        .filter(|addr| seen.insert(addr.clone()))
+        .collect();
+    debug!(
+        "Parsed {} peers out of {} entries",
+        peers.len(),
+        entries.len()
+    );
+
+    if peers.is_empty() {
+        return Err(eyre!("No valid peer listed"));
+    }
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const PEER: &str =
+        "/ip4/1.2.3.4/tcp/1200/p2p/12D3KooWRi6wF7yxWLuPSNskXc6kQ5cJ6eaymeMbCRdTnMesPgFx";
+
+    #[test]
+    fn parse_lines_and_json_lists() -> Result<()> {
+        let expected = vec![PEER.parse::<Multiaddr>()?, "/ip4/5.6.7.8/tcp/1234".parse()?];
+
+        let lines = format!("# testnet peers\n{PEER}\n\n  5.6.7.8:1234  \nnot-a-peer\n{PEER}\n");
+        assert_eq!(parse_peers_list(&lines)?, expected);
+
+        let json = format!(r#"["{PEER}", "5.6.7.8:1234", "{PEER}"]"#);
+        assert_eq!(parse_peers_list(&json)?, expected);
+
+        assert!(parse_peers_list("# nothing but comments\n").is_err());
+        assert!(parse_peers_list("[\"unterminated").is_err());
+        Ok(())
+    }
+
+    // Serves the responses to consecutive requests, one connection each, as a stand-in for
+    // the HTTP server publishing the network contacts.
+    async fn serve(responses: Vec<(&'static str, String)>) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/contacts", listener.local_addr()?);
+
+        let _handle = tokio::spawn(async move {
+            for (status, body) in responses {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        Ok(url)
+    }
+
+    #[tokio::test]
+    async fn fetch_network_contacts_with_retries() -> Result<()> {
+        let url = serve(vec![
+            ("503 Service Unavailable", String::new()),
+            ("200 OK", format!("{PEER}\n")),
+        ])
+        .await?;
+        assert_eq!(fetch_network_contacts(&url).await?, vec![PEER.parse()?]);
+
+        let failures = (0..MAX_FETCH_ATTEMPTS)
+            .map(|_| ("500 Internal Server Error", String::new()))
+            .collect();
+        let url = serve(failures).await?;
+        assert!(fetch_network_contacts(&url).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use eyre::Result;
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::mpsc, task, time::Instant};
+use tracing::{debug, warn};
+
+/// Name of the file the peer cache is persisted to, within the data dir.
+pub const PEER_CACHE_FILE_NAME: &str = "peer_cache";
+
+// Max number of peers kept in the cache.
+const MAX_CACHED_PEERS: usize = 100;
+// A peer's score is increased by one each time it is added to the routing table, up to this.
+const MAX_SCORE: u32 = 10;
+// A peer's score is decreased by this much each time it is removed from the routing table.
+// It is dropped from the cache once its score reaches zero.
+const REMOVAL_PENALTY: u32 = 3;
+// Updates are saved together, once no save happened for this long.
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct CachedPeer {
+    // The multiaddrs of the peer, including its `/p2p/<peer_id>`.
+    addrs: Vec<String>,
+    score: u32,
+    // Seconds since the unix epoch.
+    last_seen: u64,
+}
+
+/// A bounded cache of the peers recently found healthy, i.e. added to the routing table,
+/// persisted in the data dir so that later sessions can bootstrap from them.
+#[derive(Clone, Debug)]
+pub struct PeerCache {
+    path: PathBuf,
+    peers: BTreeMap<PeerId, CachedPeer>,
+}
+
+impl PeerCache {
+    /// Loads the cache persisted in the provided dir.
+    /// A missing or unreadable cache is treated as an empty one.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(PEER_CACHE_FILE_NAME);
+        let peers = match fs::read(&path) {
+            Ok(bytes) => match rmp_serde::from_slice::<BTreeMap<String, CachedPeer>>(&bytes) {
+                Ok(peers) => peers
+                    .into_iter()
+                    .filter_map(|(peer_id, peer)| Some((peer_id.parse().ok()?, peer)))
+                    .collect(),
+                Err(err) => {
+                    warn!("Ignoring the unreadable peer cache at {path:?}: {err:?}");
+                    BTreeMap::new()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                warn!("Failed to read the peer cache at {path:?}: {err:?}");
+                BTreeMap::new()
+            }
+        };
+        debug!("Loaded {} cached peers from {path:?}", peers.len());
+
+        Self { path, peers }
+    }
+
+    /// Records that the peer was added to the routing table, reachable at the provided addrs.
+    pub fn peer_added(&mut self, peer_id: PeerId, addrs: &[Multiaddr]) {
+        if addrs.is_empty() {
+            return;
+        }
+        let addrs = addrs
+            .iter()
+            .map(|addr| {
+                let mut addr = addr.clone();
+                if !matches!(addr.iter().last(), Some(Protocol::P2p(_))) {
+                    addr.push(Protocol::P2p(peer_id));
+                }
+                addr.to_string()
+            })
+            .collect();
+
+        let peer = self.peers.entry(peer_id).or_insert(CachedPeer {
+            addrs: vec![],
+            score: 0,
+            last_seen: 0,
+        });
+        peer.addrs = addrs;
+        peer.score = (peer.score + 1).min(MAX_SCORE);
+        peer.last_seen = now();
+
+        self.evict();
+    }
+
+    /// Records that the peer was removed from the routing table.
+    pub fn peer_removed(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.score = peer.score.saturating_sub(REMOVAL_PENALTY);
+            if peer.score == 0 {
+                let _ = self.peers.remove(peer_id);
+            }
+        }
+    }
+
+    /// Returns the multiaddrs of the cached peers, the best scored and most recently seen first.
+    pub fn peers(&self) -> Vec<Multiaddr> {
+        let mut peers = self.peers.values().collect::<Vec<_>>();
+        peers.sort_by_key(|peer| std::cmp::Reverse((peer.score, peer.last_seen)));
+        peers
+            .into_iter()
+            .flat_map(|peer| peer.addrs.iter().filter_map(|addr| addr.parse().ok()))
+            .collect()
+    }
+
+    /// Persists the cache, replacing the previous one.
+    pub fn save(&self) -> Result<()> {
+        let peers = self
+            .peers
+            .iter()
+            .map(|(peer_id, peer)| (peer_id.to_string(), peer))
+            .collect::<BTreeMap<_, _>>();
+        let bytes = rmp_serde::to_vec(&peers)?;
+
+        // Write to a temporary file first, so that a crash mid-write does not lose the cache.
+        // It is named after the process, as the data dir may be shared by several of them.
+        let tmp_path = self.path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    // Drops the worst scored, least recently seen peers beyond the cache's capacity.
+    fn evict(&mut self) {
+        while self.peers.len() > MAX_CACHED_PEERS {
+            let Some(worst) = self
+                .peers
+                .iter()
+                .min_by_key(|(_, peer)| (peer.score, peer.last_seen))
+                .map(|(peer_id, _)| *peer_id)
+            else {
+                return;
+            };
+            let _ = self.peers.remove(&worst);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PeerCacheUpdate {
+    Added(PeerId, Vec<Multiaddr>),
+    Removed(PeerId),
+}
+
+/// Updates a `PeerCache` from a background task, which saves it shortly after it changed.
+/// The pending changes are saved once every updater has been dropped.
+#[derive(Clone, Debug)]
+pub struct PeerCacheUpdater {
+    sender: mpsc::UnboundedSender<PeerCacheUpdate>,
+}
+
+impl PeerCacheUpdater {
+    /// Loads the cache persisted in the provided dir, and spawns the task keeping it up to date.
+    /// Must be called from within a tokio runtime.
+    pub fn spawn(dir: &Path) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let _handle = task::spawn(update_peer_cache(
+            PeerCache::load(dir),
+            receiver,
+            SAVE_DEBOUNCE,
+        ));
+        Self { sender }
+    }
+
+    /// Records that the peer was added to the routing table, reachable at the provided addrs.
+    pub fn peer_added(&self, peer_id: PeerId, addrs: Vec<Multiaddr>) {
+        self.send(PeerCacheUpdate::Added(peer_id, addrs));
+    }
+
+    /// Records that the peer was removed from the routing table.
+    pub fn peer_removed(&self, peer_id: PeerId) {
+        self.send(PeerCacheUpdate::Removed(peer_id));
+    }
+
+    fn send(&self, update: PeerCacheUpdate) {
+        if self.sender.send(update).is_err() {
+            warn!("The peer cache task has stopped, the update is lost");
+        }
+    }
+}
+
+// Applies the updates to the cache, saving it once `debounce` has elapsed since the first
+// unsaved update, and a last time once the updates stop.
+async fn update_peer_cache(
+    mut peer_cache: PeerCache,
+    mut updates: mpsc::UnboundedReceiver<PeerCacheUpdate>,
+    debounce: Duration,
+) {
+    let mut save_at: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            update = updates.recv() => {
+                match update {
+                    Some(PeerCacheUpdate::Added(peer_id, addrs)) => {
+                        peer_cache.peer_added(peer_id, &addrs)
+                    }
+                    Some(PeerCacheUpdate::Removed(peer_id)) => peer_cache.peer_removed(&peer_id),
+                    None => break,
+                }
+                let _ = save_at.get_or_insert_with(|| Instant::now() + debounce);
+            }
+            _ = tokio::time::sleep_until(save_at.unwrap_or_else(Instant::now)), if save_at.is_some() => {
+                save_at = None;
+                save_in_background(&peer_cache).await;
+            }
+        }
+    }
+    if save_at.is_some() {
+        save_in_background(&peer_cache).await;
+    }
+}
+
+// Saves a snapshot of the cache on a blocking thread, off the async runtime's workers.
+async fn save_in_background(peer_cache: &PeerCache) {
+    let snapshot = peer_cache.clone();
+    match task::spawn_blocking(move || snapshot.save()).await {
+        Ok(Ok(())) => debug!("Saved the peer cache"),
+        Ok(Err(err)) => warn!("Failed to save the peer cache: {err:?}"),
+        Err(err) => warn!("The peer cache save task failed: {err:?}"),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    fn addr(port: u16) -> Multiaddr {
+        Multiaddr::from(std::net::Ipv4Addr::new(1, 2, 3, 4)).with(Protocol::Tcp(port))
+    }
+
+    #[test]
+    fn peer_cache_persists_scored_peers() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut cache = PeerCache::load(tmp_dir.path());
+        assert!(cache.peers().is_empty());
+
+        let healthy = PeerId::random();
+        let flaky = PeerId::random();
+        cache.peer_added(flaky, &[addr(1)]);
+        cache.peer_added(healthy, &[addr(2)]);
+        cache.peer_added(healthy, &[addr(2)]);
+
+        // The healthiest peer comes first, with its peer id appended.
+        let healthy_addr = addr(2).with(Protocol::P2p(healthy));
+        let flaky_addr = addr(1).with(Protocol::P2p(flaky));
+        assert_eq!(cache.peers(), vec![healthy_addr.clone(), flaky_addr]);
+
+        // Once removed from the routing table, the flaky peer is no longer worth keeping.
+        cache.peer_removed(&flaky);
+        assert_eq!(cache.peers(), vec![healthy_addr.clone()]);
+
+        cache.save()?;
+        let reloaded = PeerCache::load(tmp_dir.path());
+        assert_eq!(reloaded.peers(), vec![healthy_addr]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn peer_cache_is_bounded() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut cache = PeerCache::load(tmp_dir.path());
+
+        let healthy = PeerId::random();
+        cache.peer_added(healthy, &[addr(1)]);
+        cache.peer_added(healthy, &[addr(1)]);
+        for port in 0..MAX_CACHED_PEERS as u16 {
+            cache.peer_added(PeerId::random(), &[addr(port)]);
+        }
+
+        assert_eq!(cache.peers().len(), MAX_CACHED_PEERS);
+        assert_eq!(cache.peers()[0], addr(1).with(Protocol::P2p(healthy)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_cache_updates_are_saved_together() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let updater = PeerCacheUpdater { sender };
+
+        let first = PeerId::random();
+        let second = PeerId::random();
+        updater.peer_added(first, vec![addr(1)]);
+        updater.peer_added(second, vec![addr(2)]);
+        updater.peer_removed(first);
+        // The pending updates are saved once the updater is dropped, well within the debounce.
+        drop(updater);
+        update_peer_cache(
+            PeerCache::load(tmp_dir.path()),
+            receiver,
+            Duration::from_secs(3600),
+        )
+        .await;
+
+        let reloaded = PeerCache::load(tmp_dir.path());
+        assert_eq!(reloaded.peers(), vec![addr(2).with(Protocol::P2p(second))]);
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+}
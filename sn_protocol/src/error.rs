@@ -60,9 +60,20 @@ pub enum Error {
     GetStoreCostFailed,
     #[error("There was an error signing the storecost from kademlia store")]
     SignStoreCostFailed,
+    /// The store cost quote is not signed by the node it claims to be issued by
+    #[error("The store cost quote claimed to be issued by {0:?} has an invalid signature")]
+    StoreCostQuoteInvalid(Box<NetworkAddress>),
+    /// The peer speaks a version of the messages answering with the store cost alone
+    #[error(
+        "The store cost was not quoted, the peer speaking a version of the messages without quotes"
+    )]
+    StoreCostNotQuoted,
     /// The amount paid by payment proof is not the required for the received content
     #[error("The amount paid by payment proof is not the required for the received content, paid {paid}, expected {expected}")]
     PaymentProofInsufficientAmount { paid: Token, expected: Token },
+    /// None of the store cost quotes of the payment proof is acceptable for the content
+    #[error("None of the store cost quotes of the payment proof for {0:?} is signed by a node close to it")]
+    PaymentProofWithoutAcceptableQuote(XorName),
     /// At least one input of payment proof provided has a mismatching spend Tx
     #[error("At least one input of payment proof provided for {0:?} has a mismatching spend Tx")]
     PaymentProofTxMismatch(XorName),
@@ -84,6 +95,8 @@ pub enum Error {
     UtxoSerialisationFailed,
     #[error("UTXO decryption failed")]
     UtxoDecryptionFailed,
+    #[error("Transfer serialisation failed")]
+    TransferSerialisationFailed,
 
     // ---------- replication errors
     /// Replication not found.
@@ -108,4 +121,15 @@ pub enum Error {
     // The RecordKind that was obtained did not match with the expected one
     #[error("The RecordKind obtained from the Record did not match with the expected kind: {0}")]
     RecordKindMismatch(RecordKind),
+
+    // ---------- request limiting errors
+    /// The peer is sending more requests than it is allowed to.
+    #[error("Too many requests from this peer, the request has been rejected")]
+    RequestLimitExceeded,
+    /// The node has too many requests pending to take on more, from any peer.
+    #[error("The node is too busy to handle the request, it has been rejected")]
+    NodeBusy,
+    /// The record sent to be stored was not accepted, before being validated.
+    #[error("The record was not accepted for storage: {0}")]
+    RecordNotAccepted(String),
 }
@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::StoreCostQuote;
 use crate::NetworkAddress;
 use serde::{Deserialize, Serialize};
 // TODO: remove this dependency and define these types herein.
@@ -30,6 +31,14 @@ pub enum Cmd {
         #[debug(skip)]
         keys: Vec<NetworkAddress>,
     },
+    /// Write operation to store a record, which the peer validates before storing it.
+    PutRecord {
+        /// The address the record is stored at.
+        address: NetworkAddress,
+        /// The serialised record.
+        #[debug(skip)]
+        value: Vec<u8>,
+    },
 }
 
 impl Cmd {
@@ -37,6 +46,7 @@ impl Cmd {
     pub fn dst(&self) -> NetworkAddress {
         match self {
             Cmd::Replicate { holder, .. } => holder.clone(),
+            Cmd::PutRecord { address, .. } => address.clone(),
         }
     }
 }
@@ -52,6 +62,9 @@ impl std::fmt::Display for Cmd {
                     keys.len()
                 )
             }
+            Cmd::PutRecord { address, value } => {
+                write!(f, "Cmd::PutRecord({address:?}, {} bytes)", value.len())
+            }
         }
     }
 }
@@ -69,4 +82,7 @@ pub struct PaymentProof {
     pub audit_trail: Vec<MerkleTreeNodesType>,
     // Path of the audit trail
     pub path: Vec<usize>,
+    // Store cost quotes signed by the nodes closest to the content, which the payment was made for.
+    // Nodes validate the amount paid against any of these which is still valid.
+    pub quotes: Vec<StoreCostQuote>,
 }
@@ -12,7 +12,9 @@ mod node_id;
 mod query;
 mod register;
 mod response;
+mod store_cost_quote;
 mod utxo;
+pub mod v1;
 
 pub use self::{
     cmd::{Cmd, Hash, MerkleTreeNodesType, PaymentProof},
@@ -20,19 +22,32 @@ pub use self::{
     query::Query,
     register::RegisterCmd,
     response::{CmdOk, CmdResponse, QueryResponse},
+    store_cost_quote::{StoreCostQuote, STORE_COST_QUOTE_VALIDITY},
     utxo::{Transfer, Utxo},
 };
 
 use super::NetworkAddress;
 use crate::{
     error::{Error, Result},
-    storage::{ChunkWithPayment, DbcAddress},
+    storage::{ChunkWithPayment, DbcAddress, RegisterWithPayment},
 };
 use serde::{Deserialize, Serialize};
 use sn_dbc::SignedSpend;
-use sn_registers::SignedRegister;
+use std::ops::RangeInclusive;
 use xor_name::XorName;
 
+/// The version of the `Request`/`Response` wire protocol, independent of the crate versions so
+/// that patch releases don't split the network. It is bumped on any change the previous version
+/// can't decode. The releases before it, speaking `/safe/node/<crate version>`, aren't supported.
+///  - 1: `Query::GetStoreCost` is answered with the cost alone, see [`v1`].
+///  - 2: `Query::GetStoreCost` is answered with a signed `StoreCostQuote`.
+pub const MESSAGES_VERSION: u32 = 2;
+
+/// The versions of the wire protocol a node speaks, keeping the previous one so that the nodes
+/// of the previous release are still understood while the network upgrades.
+pub const SUPPORTED_MESSAGES_VERSIONS: RangeInclusive<u32> =
+    MESSAGES_VERSION - 1..=MESSAGES_VERSION;
+
 #[allow(clippy::large_enum_variant)]
 /// A request to peers in the network
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -58,8 +73,8 @@ pub enum ReplicatedData {
     Chunk(ChunkWithPayment),
     /// A set of SignedSpends
     DbcSpend(Vec<SignedSpend>),
-    /// A signed register
-    Register(SignedRegister),
+    /// A signed register, along with the payment for its creation
+    Register(RegisterWithPayment),
 }
 
 impl Request {
@@ -70,6 +85,24 @@ impl Request {
             Request::Query(query) => query.dst(),
         }
     }
+
+    /// The response to this request, failed with the provided error.
+    pub fn error_response(&self, error: Error) -> Response {
+        match self {
+            Request::Cmd(Cmd::Replicate { .. }) => {
+                Response::Cmd(CmdResponse::Replicate(Err(error)))
+            }
+            Request::Cmd(Cmd::PutRecord { .. }) => {
+                Response::Cmd(CmdResponse::PutRecord(Err(error)))
+            }
+            Request::Query(Query::GetStoreCost(_)) => {
+                Response::Query(QueryResponse::GetStoreCost(Err(error)))
+            }
+            Request::Query(Query::GetReplicatedData { .. }) => {
+                Response::Query(QueryResponse::GetReplicatedData(Err(error)))
+            }
+        }
+    }
 }
 
 impl ReplicatedData {
@@ -84,7 +117,7 @@ impl ReplicatedData {
                     return Err(Error::SpendIsEmpty);
                 }
             }
-            Self::Register(register) => register.address().xorname(),
+            Self::Register(register) => register.register.address().xorname(),
         };
         Ok(name)
     }
@@ -100,7 +133,9 @@ impl ReplicatedData {
                     return Err(Error::SpendIsEmpty);
                 }
             }
-            Self::Register(register) => NetworkAddress::from_register_address(*register.address()),
+            Self::Register(register) => {
+                NetworkAddress::from_register_address(*register.register.address())
+            }
         };
         Ok(dst)
     }
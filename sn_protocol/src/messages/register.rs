@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::PaymentProof;
 use sn_registers::{Register, RegisterAddress, RegisterOp};
 
 use serde::{Deserialize, Serialize};
@@ -20,6 +21,8 @@ pub enum RegisterCmd {
         register: Register,
         /// The signature of the owner on that register.
         signature: bls::Signature,
+        /// The proof of the payment made for storing the register.
+        payment: PaymentProof,
     },
     /// Edit the register
     Edit(RegisterOp),
@@ -6,9 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{error::Result, messages::ReplicatedData, NetworkAddress};
+use crate::{
+    error::Result,
+    messages::{ReplicatedData, StoreCostQuote},
+    NetworkAddress,
+};
 use serde::{Deserialize, Serialize};
-use sn_dbc::Token;
 use std::fmt::Debug;
 
 /// The response to a query, containing the query result.
@@ -16,7 +19,7 @@ use std::fmt::Debug;
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, custom_debug::Debug)]
 pub enum QueryResponse {
     /// The store cost in nanos for storing the next record, and the node's singature over that cost.
-    GetStoreCost(Result<Token>),
+    GetStoreCost(Result<StoreCostQuote>),
     // ===== ReplicatedData =====
     //
     /// Response to [`GetReplicatedData`]
@@ -33,6 +36,11 @@ pub enum CmdResponse {
     //
     /// Response to replication cmd
     Replicate(Result<()>),
+    //
+    // ===== Record =====
+    //
+    /// Response to a PutRecord cmd, once the record is accepted to be validated and stored
+    PutRecord(Result<()>),
 }
 
 /// The Ok variant of a CmdResponse
@@ -0,0 +1,139 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::{Error, Result},
+    NetworkAddress,
+};
+use libp2p::{identity::PublicKey, PeerId};
+use serde::{Deserialize, Serialize};
+use sn_dbc::Token;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The period of time a store cost quote is valid for, from the moment it was issued.
+pub const STORE_COST_QUOTE_VALIDITY: Duration = Duration::from_secs(60 * 60);
+
+/// The cost of storing a record at an address, as quoted and signed by a node.
+///
+/// It is attached to the payment proofs, so that the payment is validated against the
+/// price quoted before paying, rather than the price of the node at the time of storing.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, custom_debug::Debug)]
+pub struct StoreCostQuote {
+    /// The address the cost was quoted for.
+    pub address: NetworkAddress,
+    /// The cost in nanos of storing a record at the address.
+    pub cost: Token,
+    /// The time after which the quote is no longer valid.
+    pub expiry: SystemTime,
+    /// The PeerId of the node which issued the quote.
+    pub peer: NetworkAddress,
+    /// The public key of the node, from which its PeerId is derived, encoded in protobuf.
+    #[debug(skip)]
+    pub public_key: Vec<u8>,
+    /// The signature of the node over the address, cost and expiry of the quote.
+    #[debug(skip)]
+    pub signature: Vec<u8>,
+}
+
+impl StoreCostQuote {
+    /// Returns the bytes a node signs to issue a quote.
+    pub fn bytes_for_signing(address: &NetworkAddress, cost: Token, expiry: SystemTime) -> Vec<u8> {
+        let expiry_secs = expiry
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut bytes = address.as_bytes();
+        bytes.extend(cost.as_nano().to_be_bytes());
+        bytes.extend(expiry_secs.to_be_bytes());
+        bytes
+    }
+
+    /// Returns the PeerId of the node which issued the quote, if it could be parsed.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        self.peer.as_peer_id()
+    }
+
+    /// Returns true if the quote is no longer valid at the given time.
+    pub fn has_expired(&self, now: SystemTime) -> bool {
+        now > self.expiry
+    }
+
+    /// Verifies that the quote was signed by the node it claims to be issued by.
+    /// This does not check whether the quote has expired.
+    pub fn verify(&self) -> Result<()> {
+        let public_key = PublicKey::try_decode_protobuf(&self.public_key)
+            .map_err(|_| Error::StoreCostQuoteInvalid(Box::new(self.peer.clone())))?;
+
+        if self.peer_id() != Some(PeerId::from_public_key(&public_key)) {
+            return Err(Error::StoreCostQuoteInvalid(Box::new(self.peer.clone())));
+        }
+
+        let bytes = Self::bytes_for_signing(&self.address, self.cost, self.expiry);
+        if !public_key.verify(&bytes, &self.signature) {
+            return Err(Error::StoreCostQuoteInvalid(Box::new(self.peer.clone())));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ChunkAddress;
+    use libp2p::identity::Keypair;
+    use xor_name::XorName;
+
+    fn quote(keypair: &Keypair, cost: Token) -> StoreCostQuote {
+        let address = NetworkAddress::ChunkAddress(ChunkAddress::new(XorName::random(
+            &mut bls::rand::thread_rng(),
+        )));
+        let expiry = SystemTime::now() + STORE_COST_QUOTE_VALIDITY;
+        let signature = keypair
+            .sign(&StoreCostQuote::bytes_for_signing(&address, cost, expiry))
+            .expect("Failed to sign the quote");
+
+        StoreCostQuote {
+            address,
+            cost,
+            expiry,
+            peer: NetworkAddress::from_peer(PeerId::from(keypair.public())),
+            public_key: keypair.public().encode_protobuf(),
+            signature,
+        }
+    }
+
+    #[test]
+    fn store_cost_quote_signature_is_verified() {
+        let keypair = Keypair::generate_ed25519();
+        let valid = quote(&keypair, Token::from_nano(10));
+        assert!(valid.verify().is_ok());
+
+        // The cost cannot be changed after being signed.
+        let mut cheaper = valid.clone();
+        cheaper.cost = Token::from_nano(1);
+        assert!(cheaper.verify().is_err());
+
+        // The quote cannot be claimed to be from another node.
+        let mut other_peer = valid.clone();
+        other_peer.peer = NetworkAddress::from_peer(PeerId::random());
+        assert!(other_peer.verify().is_err());
+
+        let mut other_key = valid.clone();
+        other_key.public_key = Keypair::generate_ed25519().public().encode_protobuf();
+        assert!(other_key.verify().is_err());
+    }
+
+    #[test]
+    fn store_cost_quote_expires() {
+        let valid = quote(&Keypair::generate_ed25519(), Token::from_nano(10));
+        assert!(!valid.has_expired(SystemTime::now()));
+        assert!(valid.has_expired(valid.expiry + Duration::from_secs(1)));
+    }
+}
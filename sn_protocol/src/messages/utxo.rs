@@ -46,6 +46,18 @@ impl Transfer {
         }
         Ok(utxos)
     }
+
+    /// Serialize the Transfer to a hex string, for it to be handed over to the recipient
+    pub fn to_hex(&self) -> Result<String> {
+        let bytes = rmp_serde::to_vec(self).map_err(|_| Error::TransferSerialisationFailed)?;
+        Ok(hex::encode(bytes))
+    }
+
+    /// Deserialize the Transfer from a hex string
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = hex::decode(hex.trim()).map_err(|_| Error::TransferSerialisationFailed)?;
+        rmp_serde::from_slice(&bytes).map_err(|_| Error::TransferSerialisationFailed)
+    }
 }
 
 /// Unspent Transaction (Tx) Output
@@ -74,6 +86,16 @@ impl Utxo {
         }
     }
 
+    /// The derivation index of the dbc, to derive its key from the main key
+    pub fn derivation_index(&self) -> &DerivationIndex {
+        &self.derivation_index
+    }
+
+    /// The address of the spend of one of the inputs of the transaction the dbc was created in
+    pub fn parent_spend(&self) -> &DbcAddress {
+        &self.parent_spend
+    }
+
     /// Serialize the Utxo to bytes
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         rmp_serde::to_vec(self).map_err(|_| Error::UtxoSerialisationFailed)
@@ -131,5 +153,9 @@ mod tests {
         let utxos = payment.utxos(&sk).unwrap();
 
         assert_eq!(utxos, vec![utxo]);
+
+        let hex = payment.to_hex().unwrap();
+        assert_eq!(Transfer::from_hex(&hex).unwrap(), payment);
+        assert!(Transfer::from_hex("not hex").is_err());
     }
 }
@@ -0,0 +1,68 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The messages of version 1, which answer `Query::GetStoreCost` with the cost alone rather than
+//! with a signed `StoreCostQuote`. Its requests and other responses are those of the current version.
+
+use super::{CmdResponse, ReplicatedData};
+use crate::{
+    error::{Error, Result},
+    NetworkAddress,
+};
+use serde::{Deserialize, Serialize};
+use sn_dbc::Token;
+
+/// A response to peers speaking version 1 of the messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Response {
+    /// The response to a cmd.
+    Cmd(CmdResponse),
+    /// The response to a query.
+    Query(QueryResponse),
+}
+
+/// The response to a query of version 1 of the messages.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, custom_debug::Debug)]
+pub enum QueryResponse {
+    /// The store cost in nanos for storing the next record.
+    GetStoreCost(Result<Token>),
+    /// Response to [`GetReplicatedData`]
+    ///
+    /// [`GetReplicatedData`]: crate::messages::Query::GetReplicatedData
+    GetReplicatedData(Result<(NetworkAddress, ReplicatedData)>),
+}
+
+impl From<super::Response> for Response {
+    fn from(response: super::Response) -> Self {
+        match response {
+            super::Response::Cmd(response) => Response::Cmd(response),
+            super::Response::Query(super::QueryResponse::GetStoreCost(quote)) => {
+                Response::Query(QueryResponse::GetStoreCost(quote.map(|quote| quote.cost)))
+            }
+            super::Response::Query(super::QueryResponse::GetReplicatedData(result)) => {
+                Response::Query(QueryResponse::GetReplicatedData(result))
+            }
+        }
+    }
+}
+
+impl From<Response> for super::Response {
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Cmd(response) => super::Response::Cmd(response),
+            // A cost alone is no quote a payment can be checked against.
+            Response::Query(QueryResponse::GetStoreCost(cost)) => super::Response::Query(
+                super::QueryResponse::GetStoreCost(cost.and(Err(Error::StoreCostNotQuoted))),
+            ),
+            Response::Query(QueryResponse::GetReplicatedData(result)) => {
+                super::Response::Query(super::QueryResponse::GetReplicatedData(result))
+            }
+        }
+    }
+}
@@ -9,9 +9,11 @@
 mod address;
 mod chunks;
 mod header;
+mod registers;
 
 pub use self::{
     address::{ChunkAddress, DbcAddress, RegisterAddress},
     chunks::{Chunk, ChunkWithPayment},
     header::{try_deserialize_record, try_serialize_record, RecordHeader, RecordKind},
+    registers::RegisterWithPayment,
 };
@@ -0,0 +1,21 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::messages::PaymentProof;
+use serde::{Deserialize, Serialize};
+use sn_registers::SignedRegister;
+
+/// The Register along with the Payment for its creation is written as Record to kademlia
+///
+/// The payment is made for the name of the Register address, and is kept along with
+/// the Register for its whole life, so that any node can validate it, e.g. upon replication.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RegisterWithPayment {
+    pub register: SignedRegister,
+    pub payment: PaymentProof,
+}
@@ -26,4 +26,7 @@ pub enum Error {
     /// DbcReissueFailed
     #[error("DbcReissueFailed: {0}")]
     DbcReissueFailed(String),
+    /// Failed to create the transfer of the created dbcs to a recipient.
+    #[error("Failed to create the transfer to a recipient: {0}")]
+    RecipientTransferFailed(String),
 }
@@ -31,9 +31,36 @@ mod error;
 mod transfer;
 
 pub(crate) use self::error::{Error, Result};
-pub use self::transfer::{create_storage_payment_transfer, create_transfer};
+pub use self::transfer::{create_storage_payment_transfer, create_transfer, plan_transfer};
 
-use sn_dbc::{Dbc, DbcTransaction, DerivationIndex, DerivedKey, PublicAddress, SignedSpend, Token};
+use sn_dbc::{
+    Dbc, DbcId, DbcTransaction, DerivationIndex, DerivedKey, PublicAddress, SignedSpend, Token,
+};
+use sn_protocol::messages::Transfer;
+
+/// How the dbcs to spend are picked out of the available ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum CoinSelection {
+    /// The largest dbcs first, spending as few of them as possible.
+    #[default]
+    LargestFirst,
+    /// The smallest dbcs first, consolidating the dust into the change.
+    SmallestFirst,
+    /// In random order, so that the inputs don't tell which wallet strategy made the transfer.
+    Random,
+}
+
+/// What a transfer would spend and create, without creating it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferPlan {
+    /// The ids and amounts of the dbcs to spend.
+    pub inputs: Vec<(DbcId, Token)>,
+    /// The amounts sent to each recipient.
+    pub outputs: Vec<(Token, PublicAddress)>,
+    /// Any surplus amount after spending the inputs, returned to the sender.
+    pub change: Token,
+}
 
 /// The input details necessary to
 /// carry out a transfer of tokens.
@@ -63,6 +90,10 @@ pub struct TransferOutputs {
     /// spending the necessary input dbcs.
     #[debug(skip)]
     pub change_dbc: Option<Dbc>,
+    /// The transfers handing the created dbcs over to their recipients,
+    /// one for each recipient, which only that recipient can decrypt.
+    #[debug(skip)]
+    pub recipient_transfers: Vec<(PublicAddress, Transfer)>,
     /// The parameters necessary to send all spend requests to the network.
     pub all_spend_requests: Vec<SpendRequest>,
 }
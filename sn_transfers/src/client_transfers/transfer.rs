@@ -6,13 +6,18 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{Error, Inputs, Result, SpendRequest, TransferOutputs};
+use super::{CoinSelection, Error, Inputs, Result, SpendRequest, TransferOutputs, TransferPlan};
 
 use sn_dbc::{
-    random_derivation_index, rng, Dbc, DerivationIndex, DerivedKey, FeeOutput, Hash, Input,
-    PublicAddress, Token, TransactionBuilder,
+    random_derivation_index, rng, Dbc, DbcTransaction, DerivationIndex, DerivedKey, FeeOutput,
+    Hash, Input, PublicAddress, Token, TransactionBuilder,
+};
+use sn_protocol::{
+    messages::{Transfer, Utxo},
+    storage::DbcAddress,
 };
 
+use rand::seq::SliceRandom;
 use std::collections::BTreeMap;
 
 /// A function for creating an offline transfer of tokens.
@@ -30,20 +35,13 @@ pub fn create_transfer(
     recipients: Vec<(Token, PublicAddress, DerivationIndex)>,
     change_to: PublicAddress,
     reason_hash: Hash,
+    coin_selection: CoinSelection,
 ) -> Result<TransferOutputs> {
-    let total_output_amount = recipients
-        .iter()
-        .fold(Some(Token::zero()), |total, (amount, _, _)| {
-            total.and_then(|t| t.checked_add(*amount))
-        })
-        .ok_or_else(|| {
-            Error::DbcReissueFailed(
-                "Overflow occurred while summing the amounts for the recipients.".to_string(),
-            )
-        })?;
+    let total_output_amount = total_amount(recipients.iter().map(|(amount, _, _)| *amount))?;
 
     // We need to select the necessary number of dbcs from those that we were passed.
-    let (dbcs_to_spend, change_amount) = select_inputs(available_dbcs, total_output_amount)?;
+    let (dbcs_to_spend, change_amount) =
+        select_inputs(available_dbcs, total_output_amount, coin_selection)?;
 
     let selected_inputs = Inputs {
         dbcs_to_spend,
@@ -64,9 +62,11 @@ pub fn create_storage_payment_transfer(
     storage_payment: Token,
     root_hash: Hash,
     reason_hash: Hash,
+    coin_selection: CoinSelection,
 ) -> Result<TransferOutputs> {
     // We need to select the necessary number of dbcs from those that we were passed.
-    let (dbcs_to_spend, change_amount) = select_inputs(available_dbcs, storage_payment)?;
+    let (dbcs_to_spend, change_amount) =
+        select_inputs(available_dbcs, storage_payment, coin_selection)?;
 
     // We build the recipients to contain just a single output which is for the network owned output.
     // This is a special output that spendbook peers validating the signed spends (inputs) will be
@@ -92,11 +92,56 @@ pub fn create_storage_payment_transfer(
     create_transfer_with(selected_inputs, reason_hash, Some(fee))
 }
 
-/// Select the necessary number of dbcs from those that we were passed.
-fn select_inputs(
+/// Selects the dbcs a transfer to the recipients would spend, and the change it would leave,
+/// without creating the transfer. This lets the sender review it before making it.
+pub fn plan_transfer(
     available_dbcs: Vec<(Dbc, DerivedKey)>,
+    recipients: Vec<(Token, PublicAddress)>,
+    coin_selection: CoinSelection,
+) -> Result<TransferPlan> {
+    let total_output_amount = total_amount(recipients.iter().map(|(amount, _)| *amount))?;
+    let (dbcs_to_spend, change) =
+        select_inputs(available_dbcs, total_output_amount, coin_selection)?;
+
+    let inputs = dbcs_to_spend
+        .iter()
+        .filter_map(|(dbc, _)| dbc.token().ok().map(|amount| (dbc.id(), amount)))
+        .collect();
+
+    Ok(TransferPlan {
+        inputs,
+        outputs: recipients,
+        change,
+    })
+}
+
+fn total_amount(mut amounts: impl Iterator<Item = Token>) -> Result<Token> {
+    amounts
+        .try_fold(Token::zero(), |total, amount| total.checked_add(amount))
+        .ok_or_else(|| {
+            Error::DbcReissueFailed(
+                "Overflow occurred while summing the amounts for the recipients.".to_string(),
+            )
+        })
+}
+
+/// Select the necessary number of dbcs from those that we were passed,
+/// in the order of the coin selection.
+fn select_inputs(
+    mut available_dbcs: Vec<(Dbc, DerivedKey)>,
     total_output_amount: Token,
+    coin_selection: CoinSelection,
 ) -> Result<(Vec<(Dbc, DerivedKey)>, Token)> {
+    // Dbcs without an amount are ignored below, so their place in the order doesn't matter.
+    let amount = |dbc: &Dbc| dbc.token().map(|token| token.as_nano()).unwrap_or_default();
+    match coin_selection {
+        CoinSelection::LargestFirst => {
+            available_dbcs.sort_by_key(|(dbc, _)| std::cmp::Reverse(amount(dbc)))
+        }
+        CoinSelection::SmallestFirst => available_dbcs.sort_by_key(|(dbc, _)| amount(dbc)),
+        CoinSelection::Random => available_dbcs.shuffle(&mut rand::thread_rng()),
+    }
+
     let mut dbcs_to_spend = Vec::new();
     let mut total_input_amount = Token::zero();
     let mut change_amount = total_output_amount;
@@ -188,7 +233,7 @@ fn create_transfer_with(
 
     let mut tx_builder = TransactionBuilder::default()
         .add_inputs(inputs)
-        .add_outputs(recipients);
+        .add_outputs(recipients.clone());
 
     if let Some(fee_output) = fee {
         tx_builder = tx_builder.set_fee_output(fee_output);
@@ -260,10 +305,45 @@ fn create_transfer_with(
         }
     });
 
+    let recipient_transfers = create_recipient_transfers(&tx, recipients)?;
+
     Ok(TransferOutputs {
         tx,
         created_dbcs,
         change_dbc,
+        recipient_transfers,
         all_spend_requests,
     })
 }
+
+/// Creates a transfer for each recipient, holding the utxos of the dbcs created to it.
+/// From those, the recipient can rebuild its dbcs out of the spends found in the network.
+fn create_recipient_transfers(
+    tx: &DbcTransaction,
+    recipients: Vec<(Token, PublicAddress, DerivationIndex)>,
+) -> Result<Vec<(PublicAddress, Transfer)>> {
+    // The spend of any of the inputs leads to the tx.
+    let parent_spend = match tx.inputs.first() {
+        Some(input) => DbcAddress::from_dbc_id(&input.dbc_id()),
+        None => return Ok(vec![]),
+    };
+
+    let mut utxos: BTreeMap<PublicAddress, Vec<Utxo>> = BTreeMap::new();
+    for (_, address, derivation_index) in recipients {
+        utxos
+            .entry(address)
+            .or_default()
+            .push(Utxo::new(derivation_index, parent_spend));
+    }
+
+    utxos
+        .into_iter()
+        .map(|(address, utxos)| {
+            let recipient = bls::PublicKey::from_bytes(address.to_bytes())
+                .map_err(|err| Error::RecipientTransferFailed(err.to_string()))?;
+            let transfer = Transfer::create(utxos, recipient)
+                .map_err(|err| Error::RecipientTransferFailed(err.to_string()))?;
+            Ok((address, transfer))
+        })
+        .collect()
+}
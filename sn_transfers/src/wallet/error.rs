@@ -32,6 +32,9 @@ pub enum Error {
     /// A general error when verifying a transfer validity in the network.
     #[error("Failed to verify transfer validity in the network {0}")]
     CouldNotVerifyTransfer(String),
+    /// A general error when receiving a transfer fails.
+    #[error("Failed to receive the transfer: {0}")]
+    CouldNotReceiveTransfer(String),
     /// Failed to parse bytes into a bls key.
     #[error("Unconfirmed transactions still persist even after retries")]
     UnconfirmedTxAfterRetries,
@@ -53,6 +56,9 @@ pub enum Error {
     /// Bincode error.
     #[error("Bincode error:: {0}")]
     Bincode(#[from] bincode::Error),
+    /// Json error.
+    #[error("Json error: {0}")]
+    Json(#[from] serde_json::Error),
     /// I/O error.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
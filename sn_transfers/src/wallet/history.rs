@@ -0,0 +1,235 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::error::Result;
+
+use serde::{Deserialize, Serialize};
+use sn_dbc::{Hash, PublicAddress, Token};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What a record of the wallet history is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    /// Tokens deposited to the wallet.
+    Deposit,
+    /// Tokens sent to another wallet.
+    Send,
+    /// Tokens paid for storing content.
+    StoragePayment,
+    /// The surplus of a send or storage payment, returned to the wallet. It doesn't add to the
+    /// balance, as sends and storage payments only record the tokens which left the wallet.
+    Change,
+    /// Tokens of the wallet found spent in the network by a tx it didn't make,
+    /// e.g. by another copy of the wallet.
+    SpentElsewhere,
+}
+
+/// Whether the spends of the tx of a record have been accepted by the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxState {
+    Pending,
+    Confirmed,
+    /// The tx could not be completed, its inputs having been spent in another one.
+    RolledBack,
+}
+
+/// A record of the wallet history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// When the record was made, in seconds since the unix epoch.
+    pub timestamp: u64,
+    pub kind: HistoryKind,
+    /// The hash of the tx, in hex. For deposits, it is the one of the tx the dbc was created in.
+    pub tx_hash: String,
+    /// The amount, in nanos.
+    pub amount: u64,
+    /// The public address of the recipient of a send, in hex.
+    pub recipient: Option<String>,
+    /// The root hash of the content paid for by a storage payment, in hex.
+    pub root_hash: Option<String>,
+    /// The number of records paid for by a storage payment.
+    pub records_count: Option<usize>,
+    pub state: TxState,
+}
+
+/// The entries appended to the history file, one per line.
+/// The state of the records is never rewritten, the outcomes of their tx are appended instead.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum HistoryEntry {
+    Record(HistoryRecord),
+    Confirmed { timestamp: u64, tx_hash: String },
+    RolledBack { timestamp: u64, tx_hash: String },
+}
+
+impl HistoryRecord {
+    fn new(kind: HistoryKind, tx_hash: Hash, amount: Token, state: TxState) -> Self {
+        Self {
+            timestamp: now(),
+            kind,
+            tx_hash: tx_hash.to_hex(),
+            amount: amount.as_nano(),
+            recipient: None,
+            root_hash: None,
+            records_count: None,
+            state,
+        }
+    }
+
+    pub(super) fn deposit(tx_hash: Hash, amount: Token) -> Self {
+        Self::new(HistoryKind::Deposit, tx_hash, amount, TxState::Confirmed)
+    }
+
+    pub(super) fn send(tx_hash: Hash, amount: Token, recipient: &PublicAddress) -> Self {
+        Self {
+            recipient: Some(hex::encode(recipient.to_bytes())),
+            ..Self::new(HistoryKind::Send, tx_hash, amount, TxState::Pending)
+        }
+    }
+
+    pub(super) fn storage_payment(
+        tx_hash: Hash,
+        amount: Token,
+        root_hash: Hash,
+        records_count: usize,
+    ) -> Self {
+        Self {
+            root_hash: Some(root_hash.to_hex()),
+            records_count: Some(records_count),
+            ..Self::new(
+                HistoryKind::StoragePayment,
+                tx_hash,
+                amount,
+                TxState::Pending,
+            )
+        }
+    }
+
+    pub(super) fn change(tx_hash: Hash, amount: Token) -> Self {
+        Self::new(HistoryKind::Change, tx_hash, amount, TxState::Pending)
+    }
+
+    pub(super) fn spent_elsewhere(tx_hash: Hash, amount: Token) -> Self {
+        Self::new(
+            HistoryKind::SpentElsewhere,
+            tx_hash,
+            amount,
+            TxState::Confirmed,
+        )
+    }
+}
+
+impl HistoryEntry {
+    pub(super) fn confirmed(tx_hash: Hash) -> Self {
+        Self::Confirmed {
+            timestamp: now(),
+            tx_hash: tx_hash.to_hex(),
+        }
+    }
+
+    pub(super) fn rolled_back(tx_hash: Hash) -> Self {
+        Self::RolledBack {
+            timestamp: now(),
+            tx_hash: tx_hash.to_hex(),
+        }
+    }
+}
+
+/// Replays the entries into the records, in the order they were made,
+/// with the state of those whose tx was confirmed or rolled back since.
+pub(super) fn replay(entries: Vec<HistoryEntry>) -> Vec<HistoryRecord> {
+    let mut outcomes = BTreeMap::new();
+    for entry in &entries {
+        match entry {
+            HistoryEntry::Confirmed { tx_hash, .. } => {
+                let _ = outcomes.insert(tx_hash.clone(), TxState::Confirmed);
+            }
+            HistoryEntry::RolledBack { tx_hash, .. } => {
+                let _ = outcomes.insert(tx_hash.clone(), TxState::RolledBack);
+            }
+            HistoryEntry::Record(_) => {}
+        }
+    }
+
+    entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            HistoryEntry::Record(mut record) => {
+                if let Some(state) = outcomes.get(&record.tx_hash) {
+                    record.state = *state;
+                }
+                Some(record)
+            }
+            HistoryEntry::Confirmed { .. } | HistoryEntry::RolledBack { .. } => None,
+        })
+        .collect()
+}
+
+/// Exports the records as CSV, with a header line.
+pub fn history_to_csv(records: &[HistoryRecord]) -> String {
+    let mut csv =
+        String::from("timestamp,kind,tx_hash,amount,recipient,root_hash,records_count,state\n");
+    for record in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            record.timestamp,
+            record.kind,
+            record.tx_hash,
+            record.amount,
+            record.recipient.as_deref().unwrap_or_default(),
+            record.root_hash.as_deref().unwrap_or_default(),
+            record
+                .records_count
+                .map(|count| count.to_string())
+                .unwrap_or_default(),
+            record.state,
+        ));
+    }
+    csv
+}
+
+/// Exports the records as a JSON array.
+pub fn history_to_json(records: &[HistoryRecord]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(records)?)
+}
+
+impl fmt::Display for HistoryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Deposit => "deposit",
+            Self::Send => "send",
+            Self::StoragePayment => "storage_payment",
+            Self::Change => "change",
+            Self::SpentElsewhere => "spent_elsewhere",
+        };
+        f.pad(kind)
+    }
+}
+
+impl fmt::Display for TxState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+            Self::RolledBack => "rolled_back",
+        };
+        f.pad(state)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -52,6 +52,17 @@ pub(super) async fn get_main_key(wallet_dir: &Path) -> Result<Option<MainKey>> {
     Ok(Some(MainKey::new(secret)))
 }
 
+/// The secret key of the main key, to decrypt what was encrypted to our public address.
+pub(super) fn main_secret_key(main_key: &MainKey) -> Result<bls::SecretKey> {
+    let bytes_fixed_len: [u8; bls::SK_SIZE] = main_key
+        .to_bytes()
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::FailedToParseBlsKey)?;
+    let sk = bls::SecretKey::from_bytes(bytes_fixed_len)?;
+    Ok(sk)
+}
+
 /// Construct a BLS secret key from a hex-encoded string.
 pub fn bls_secret_from_hex<T: AsRef<[u8]>>(hex: T) -> Result<bls::SecretKey> {
     let bytes = decode(hex).map_err(|_| Error::FailedToDecodeHexToKey)?;
@@ -7,15 +7,23 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    keys::{get_main_key, store_new_keypair},
+    history::{replay, HistoryEntry, HistoryRecord},
+    keys::{get_main_key, main_secret_key, store_new_keypair},
     wallet_file::{
-        create_received_dbcs_dir, get_wallet, load_received_dbcs, store_created_dbcs, store_wallet,
+        append_history, create_received_dbcs_dir, get_unconfirmed_txs, get_wallet, load_history,
+        load_received_dbcs, store_created_dbcs, store_unconfirmed_txs, store_wallet,
     },
     Error, KeyLessWallet, PaymentProofsMap, Result,
 };
-use crate::client_transfers::{create_storage_payment_transfer, create_transfer, TransferOutputs};
-use sn_dbc::{random_derivation_index, Dbc, DerivedKey, Hash, MainKey, PublicAddress, Token};
-use sn_protocol::messages::PaymentProof;
+use crate::client_transfers::{
+    create_storage_payment_transfer, create_transfer, plan_transfer, CoinSelection,
+    TransferOutputs, TransferPlan,
+};
+use sn_dbc::{
+    random_derivation_index, Dbc, DbcCiphers, DbcId, DbcTransaction, DerivedKey, Hash, MainKey,
+    PublicAddress, SignedSpend, Token,
+};
+use sn_protocol::messages::{PaymentProof, Transfer, Utxo};
 
 use std::{
     collections::{BTreeMap, BTreeSet},
@@ -34,12 +42,117 @@ pub struct LocalWallet {
     wallet: KeyLessWallet,
     /// The dir of the wallet file, main key, public address, and new dbcs.
     wallet_dir: PathBuf,
+    /// The history entries made since the wallet was last stored.
+    unstored_history: Vec<HistoryEntry>,
+    /// The transfers whose spends have not yet been confirmed in the network,
+    /// in the order they were made, as a later one may spend the change of an earlier one.
+    unconfirmed_txs: Vec<TransferOutputs>,
+    /// How the dbcs to spend are picked by the sends and storage payments.
+    coin_selection: CoinSelection,
 }
 
 impl LocalWallet {
-    /// Stores the wallet to disk.
-    pub async fn store(&self) -> Result<()> {
-        store_wallet(&self.wallet_dir, &self.wallet).await
+    /// Stores the wallet to disk, along with its unconfirmed transfers,
+    /// appending the new entries to its history.
+    pub async fn store(&mut self) -> Result<()> {
+        store_wallet(&self.wallet_dir, &self.wallet).await?;
+        store_unconfirmed_txs(&self.wallet_dir, &self.unconfirmed_txs).await?;
+        append_history(&self.wallet_dir, &self.unstored_history).await?;
+        self.unstored_history.clear();
+        Ok(())
+    }
+
+    /// The history of the wallet, in the order it was made,
+    /// including the entries not yet stored.
+    pub async fn history(&self) -> Result<Vec<HistoryRecord>> {
+        let mut entries = load_history(&self.wallet_dir).await?;
+        entries.extend(self.unstored_history.iter().cloned());
+        Ok(replay(entries))
+    }
+
+    /// The transfers whose spends have not yet been confirmed in the network, oldest first.
+    pub fn unconfirmed_txs(&self) -> &[TransferOutputs] {
+        &self.unconfirmed_txs
+    }
+
+    /// Keeps the transfer, for its spends to be sent to the network again later.
+    pub fn add_unconfirmed_tx(&mut self, transfer: TransferOutputs) {
+        self.unconfirmed_txs.push(transfer);
+    }
+
+    /// Records the tx as confirmed, once its spends have been accepted by the network.
+    pub fn confirm_tx(&mut self, tx_hash: Hash) {
+        self.unconfirmed_txs
+            .retain(|transfer| transfer.tx.hash() != tx_hash);
+        self.unstored_history.push(HistoryEntry::confirmed(tx_hash));
+    }
+
+    /// Rolls back an unconfirmed tx which can't be completed, as some of its inputs were spent
+    /// in another tx. Its inputs which are not `still_spent` are available again, while
+    /// its change and the dbcs it created for others are dropped. The later unconfirmed txs
+    /// spending any of its outputs can't be completed either, and are rolled back with it.
+    ///
+    /// Returns the hashes of the txs rolled back.
+    pub fn rollback_tx(&mut self, tx_hash: Hash, still_spent: &BTreeSet<DbcId>) -> Vec<Hash> {
+        let mut rolled_back = vec![];
+        let mut to_roll_back = vec![tx_hash];
+        // The outputs of the txs rolled back, which never came to exist.
+        let mut dropped_dbc_ids = BTreeSet::new();
+        while let Some(tx_hash) = to_roll_back.pop() {
+            let Some(index) = self
+                .unconfirmed_txs
+                .iter()
+                .position(|transfer| transfer.tx.hash() == tx_hash)
+            else {
+                continue;
+            };
+            let transfer = self.unconfirmed_txs.remove(index);
+
+            let output_ids: BTreeSet<_> = transfer
+                .change_dbc
+                .iter()
+                .chain(&transfer.created_dbcs)
+                .map(|dbc| dbc.id())
+                .collect();
+            dropped_dbc_ids.extend(output_ids.iter().copied());
+
+            for input in &transfer.tx.inputs {
+                let dbc_id = input.dbc_id();
+                if still_spent.contains(&dbc_id) {
+                    continue;
+                }
+                if let Some(dbc) = self.wallet.spent_dbcs.remove(&dbc_id) {
+                    if !dropped_dbc_ids.contains(&dbc_id) {
+                        let _ = self.wallet.available_dbcs.insert(dbc_id, dbc);
+                    }
+                }
+            }
+            for dbc_id in &output_ids {
+                let _ = self.wallet.available_dbcs.remove(dbc_id);
+            }
+            self.wallet
+                .dbcs_created_for_others
+                .retain(|dbc| !output_ids.contains(&dbc.id()));
+
+            to_roll_back.extend(
+                self.unconfirmed_txs
+                    .iter()
+                    .filter(|later| {
+                        later
+                            .tx
+                            .inputs
+                            .iter()
+                            .any(|input| output_ids.contains(&input.dbc_id()))
+                    })
+                    .map(|later| later.tx.hash()),
+            );
+            self.unstored_history
+                .push(HistoryEntry::rolled_back(tx_hash));
+            rolled_back.push(tx_hash);
+        }
+
+        self.wallet.update_balance(&self.key);
+        rolled_back
     }
 
     /// Stores the given dbc to the `created dbcs dir` in the wallet dir.
@@ -52,7 +165,7 @@ impl LocalWallet {
     /// Try to load any new dbcs from the `received dbcs dir` in the wallet dir.
     pub async fn try_load_deposits(&mut self) -> Result<()> {
         let deposited = load_received_dbcs(&self.wallet_dir).await?;
-        self.wallet.deposit(deposited, &self.key);
+        self.deposit(deposited);
         Ok(())
     }
 
@@ -62,10 +175,14 @@ impl LocalWallet {
         // This creates the received_dbcs dir if it doesn't exist.
         tokio::fs::create_dir_all(&wallet_dir).await?;
         let (key, wallet) = load_from_path(&wallet_dir).await?;
+        let unconfirmed_txs = get_unconfirmed_txs(&wallet_dir).await?;
         Ok(Self {
             key,
             wallet,
             wallet_dir: wallet_dir.to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs,
+            coin_selection: CoinSelection::default(),
         })
     }
 
@@ -82,7 +199,54 @@ impl LocalWallet {
     }
 
     pub fn deposit(&mut self, dbcs: Vec<Dbc>) {
-        self.wallet.deposit(dbcs, &self.key);
+        let deposited = self.wallet.deposit(dbcs, &self.key);
+        for dbc in deposited {
+            if let Ok(amount) = dbc.token() {
+                self.record(HistoryRecord::deposit(dbc.src_tx.hash(), amount));
+            }
+        }
+    }
+
+    /// Decrypts the utxos of a transfer made to us.
+    pub fn unwrap_transfer(&self, transfer: &Transfer) -> Result<Vec<Utxo>> {
+        let secret_key = main_secret_key(&self.key)?;
+        transfer
+            .utxos(&secret_key)
+            .map_err(|err| Error::CouldNotReceiveTransfer(err.to_string()))
+    }
+
+    /// Rebuilds the dbc of a utxo we received, out of the tx it was created in
+    /// and the spends of all the inputs of that tx, as found in the network.
+    /// The dbc is verified against them before being returned.
+    pub fn rebuild_received_dbc(
+        &self,
+        utxo: &Utxo,
+        src_tx: DbcTransaction,
+        signed_spends: BTreeSet<SignedSpend>,
+    ) -> Result<Dbc> {
+        let derivation_index = utxo.derivation_index();
+        let dbc = Dbc {
+            id: self.key.derive_key(derivation_index).dbc_id(),
+            src_tx,
+            ciphers: DbcCiphers::from((&self.address(), derivation_index)),
+            signed_spends,
+        };
+        dbc.verify(&self.key)?;
+        Ok(dbc)
+    }
+
+    /// Moves the available dbc to the spent ones, once found spent in the network in the tx
+    /// of `tx_hash`, e.g. by another copy of this wallet. Returns false if it wasn't available.
+    pub fn mark_as_spent(&mut self, dbc_id: &DbcId, tx_hash: Hash) -> bool {
+        let Some(dbc) = self.wallet.available_dbcs.remove(dbc_id) else {
+            return false;
+        };
+        if let Ok(amount) = dbc.token() {
+            self.record(HistoryRecord::spent_elsewhere(tx_hash, amount));
+        }
+        let _ = self.wallet.spent_dbcs.insert(*dbc_id, dbc);
+        self.wallet.update_balance(&self.key);
+        true
     }
 
     pub fn available_dbcs(&self) -> Vec<(Dbc, DerivedKey)> {
@@ -141,6 +305,21 @@ impl LocalWallet {
         self.wallet.paymet_proofs.get(name)
     }
 
+    /// Sets how the dbcs to spend are picked by the next sends and storage payments.
+    pub fn set_coin_selection(&mut self, coin_selection: CoinSelection) {
+        self.coin_selection = coin_selection;
+    }
+
+    /// Returns the dbcs a send to the recipients would spend, and the change it would leave,
+    /// without making it.
+    pub fn plan_send(&self, to: Vec<(Token, PublicAddress)>) -> Result<TransferPlan> {
+        Ok(plan_transfer(
+            self.available_dbcs(),
+            to,
+            self.coin_selection,
+        )?)
+    }
+
     pub async fn local_send(
         &mut self,
         to: Vec<(Token, PublicAddress)>,
@@ -159,18 +338,29 @@ impl LocalWallet {
 
         let reason_hash = reason_hash.unwrap_or_default();
 
-        let transfer =
-            create_transfer(available_dbcs, to_unique_keys, self.address(), reason_hash)?;
+        let transfer = create_transfer(
+            available_dbcs,
+            to_unique_keys.clone(),
+            self.address(),
+            reason_hash,
+            self.coin_selection,
+        )?;
 
+        let tx_hash = transfer.tx.hash();
+        for (amount, address, _) in &to_unique_keys {
+            self.record(HistoryRecord::send(tx_hash, *amount, address));
+        }
         self.update_local_wallet(&transfer);
 
         Ok(transfer)
     }
 
+    /// Pays for storing the `records_count` records of the content the `root_hash` is the one of.
     pub async fn local_send_storage_payment(
         &mut self,
         storage_payment: Token,
         root_hash: Hash,
+        records_count: usize,
         reason_hash: Option<Hash>,
     ) -> Result<TransferOutputs> {
         let available_dbcs = self.available_dbcs();
@@ -182,8 +372,17 @@ impl LocalWallet {
             storage_payment,
             root_hash,
             reason_hash.unwrap_or_default(),
+            self.coin_selection,
         )?;
 
+        // The network owned output of the tx, which is what the payment consists of.
+        let fee = &transfer.tx.fee;
+        self.record(HistoryRecord::storage_payment(
+            transfer.tx.hash(),
+            fee.token,
+            fee.root_hash,
+            records_count,
+        ));
         self.update_local_wallet(&transfer);
 
         Ok(transfer)
@@ -205,10 +404,22 @@ impl LocalWallet {
             .filter_map(|id| self.wallet.available_dbcs.remove(&id).map(|dbc| (id, dbc)))
             .collect();
 
-        self.deposit(change_dbc.into_iter().collect());
+        // The change is recorded as such, rather than as a deposit.
+        let change = self
+            .wallet
+            .deposit(change_dbc.into_iter().collect(), &self.key);
+        for dbc in change {
+            if let Ok(amount) = dbc.token() {
+                self.record(HistoryRecord::change(tx.hash(), amount));
+            }
+        }
         self.wallet.spent_dbcs.append(&mut spent_dbcs);
         self.wallet.dbcs_created_for_others.extend(created_dbcs);
     }
+
+    fn record(&mut self, record: HistoryRecord) {
+        self.unstored_history.push(HistoryEntry::Record(record));
+    }
 }
 
 /// Loads a serialized wallet from a path.
@@ -257,12 +468,13 @@ impl KeyLessWallet {
         self.balance
     }
 
-    fn deposit(&mut self, dbcs: Vec<Dbc>, key: &MainKey) {
+    /// Deposits the dbcs we own and haven't spent, returning those that weren't already available.
+    fn deposit(&mut self, dbcs: Vec<Dbc>, key: &MainKey) -> Vec<Dbc> {
         if dbcs.is_empty() {
-            return;
+            return vec![];
         }
 
-        let mut received_dbcs = dbcs
+        let mut received_dbcs: BTreeMap<_, _> = dbcs
             .into_iter()
             .filter_map(|dbc| {
                 let id = dbc.id();
@@ -271,8 +483,19 @@ impl KeyLessWallet {
             .filter_map(|(id, dbc)| dbc.derived_key(key).is_ok().then_some((id, dbc)))
             .collect();
 
+        let new_dbcs = received_dbcs
+            .iter()
+            .filter(|(id, _)| !self.available_dbcs.contains_key(id))
+            .map(|(_, dbc)| dbc.clone())
+            .collect();
+
         self.available_dbcs.append(&mut received_dbcs);
+        self.update_balance(key);
+
+        new_dbcs
+    }
 
+    fn update_balance(&mut self, key: &MainKey) {
         let new_balance = self
             .available_dbcs
             .iter()
@@ -286,16 +509,20 @@ impl KeyLessWallet {
 
 #[cfg(test)]
 mod tests {
-    use super::{get_wallet, store_wallet, LocalWallet};
+    use super::{get_wallet, store_wallet, HistoryEntry, LocalWallet};
 
     use crate::{
-        client_transfers::TransferOutputs,
+        client_transfers::{CoinSelection, TransferOutputs, TransferPlan},
         dbc_genesis::{create_first_dbc_from_key, GENESIS_DBC_AMOUNT},
-        wallet::{local_store::WALLET_DIR_NAME, public_address_name, KeyLessWallet},
+        wallet::{
+            history_to_csv, local_store::WALLET_DIR_NAME, public_address_name, HistoryKind,
+            KeyLessWallet, TxState,
+        },
     };
 
-    use sn_dbc::{MainKey, Token};
+    use sn_dbc::{Hash, MainKey, Token};
     use sn_protocol::storage::DbcAddress;
+    use std::collections::BTreeSet;
 
     use assert_fs::TempDir;
     use eyre::Result;
@@ -344,6 +571,9 @@ mod tests {
             key,
             wallet: KeyLessWallet::new(),
             wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
         };
 
         assert_eq!(public_address, deposit_only.address());
@@ -368,6 +598,9 @@ mod tests {
             key: MainKey::random(),
             wallet: KeyLessWallet::new(),
             wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
         };
 
         deposit_only.deposit(vec![]);
@@ -391,6 +624,9 @@ mod tests {
             key,
             wallet: KeyLessWallet::new(),
             wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
         };
 
         deposit_only.deposit(vec![genesis]);
@@ -410,6 +646,9 @@ mod tests {
             key: MainKey::random(),
             wallet: KeyLessWallet::new(),
             wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
         };
 
         local_wallet.deposit(vec![genesis]);
@@ -430,6 +669,9 @@ mod tests {
             key,
             wallet: KeyLessWallet::new(),
             wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
         };
 
         deposit_only.deposit(vec![genesis_0.clone()]);
@@ -657,6 +899,374 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn received_transfers_rebuild_the_created_dbcs() -> Result<()> {
+        let sender_root_dir = create_temp_dir();
+        let mut sender = LocalWallet::load_from(sender_root_dir.path()).await?;
+        let sender_dbc =
+            create_first_dbc_from_key(&sender.key).expect("Genesis creation to succeed.");
+        sender.deposit(vec![sender_dbc]);
+
+        let recipient_root_dir = create_temp_dir();
+        let mut recipient = LocalWallet::load_from(recipient_root_dir.path()).await?;
+
+        let send_amount = 100;
+        let to = vec![(Token::from_nano(send_amount), recipient.address())];
+        let transfer = sender.local_send(to, None).await?;
+        let [(address, recipient_transfer)] = &transfer.recipient_transfers[..] else {
+            panic!("There should be a transfer to the recipient.");
+        };
+        assert_eq!(address, &recipient.address());
+
+        // Nobody else can decrypt the transfer.
+        assert!(sender.unwrap_transfer(recipient_transfer).is_err());
+        let utxos = recipient.unwrap_transfer(recipient_transfer)?;
+        assert_eq!(1, utxos.len());
+
+        // The spends the sender uploads, as the recipient would fetch them from the network.
+        let signed_spends: BTreeSet<_> = transfer
+            .all_spend_requests
+            .iter()
+            .map(|request| request.signed_spend.clone())
+            .collect();
+        let dbc = recipient.rebuild_received_dbc(
+            &utxos[0],
+            transfer.tx.clone(),
+            signed_spends.clone(),
+        )?;
+        // The ciphers are encrypted anew, with the same derivation index.
+        assert_eq!(dbc.id(), transfer.created_dbcs[0].id());
+        assert_eq!(Token::from_nano(send_amount), dbc.token()?);
+
+        // A dbc can't be rebuilt out of another tx.
+        let other_tx = sn_dbc::DbcTransaction {
+            inputs: vec![],
+            outputs: vec![],
+            fee: Default::default(),
+        };
+        assert!(recipient
+            .rebuild_received_dbc(&utxos[0], other_tx, signed_spends)
+            .is_err());
+
+        recipient.deposit(vec![dbc]);
+        assert_eq!(send_amount, recipient.balance().as_nano());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn history_skips_only_a_partially_written_last_line() -> Result<()> {
+        let dir = create_temp_dir();
+        let root_dir = dir.path().to_path_buf();
+        let history_path = root_dir.join(WALLET_DIR_NAME).join("history");
+
+        let mut wallet = LocalWallet::load_from(&root_dir).await?;
+        let genesis = create_first_dbc_from_key(&wallet.key).expect("Genesis creation to succeed.");
+        wallet.deposit(vec![genesis]);
+        wallet.store().await?;
+
+        // An append that was interrupted leaves a partial last line.
+        let mut content = std::fs::read_to_string(&history_path)?;
+        content.push_str("{\"Confirmed\":{\"timest");
+        std::fs::write(&history_path, &content)?;
+        assert_eq!(wallet.history().await?.len(), 1);
+
+        // The next append replaces the partial line.
+        let recipient = MainKey::random().public_address();
+        let send = wallet
+            .local_send(vec![(Token::from_nano(100), recipient)], None)
+            .await?;
+        wallet.confirm_tx(send.tx.hash());
+        wallet.store().await?;
+        assert_eq!(wallet.history().await?.len(), 3);
+
+        // A bad line anywhere else is corruption.
+        let content = std::fs::read_to_string(&history_path)?;
+        std::fs::write(&history_path, format!("not an entry\n{content}"))?;
+        assert!(wallet.history().await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn history_records_the_wallet_activity() -> Result<()> {
+        let dir = create_temp_dir();
+        let root_dir = dir.path().to_path_buf();
+
+        let mut wallet = LocalWallet::load_from(&root_dir).await?;
+        let genesis = create_first_dbc_from_key(&wallet.key).expect("Genesis creation to succeed.");
+        wallet.deposit(vec![genesis.clone()]);
+        // Depositing the same dbc again is not recorded.
+        wallet.deposit(vec![genesis]);
+
+        let recipient = MainKey::random().public_address();
+        let send = wallet
+            .local_send(vec![(Token::from_nano(100), recipient)], None)
+            .await?;
+        wallet.confirm_tx(send.tx.hash());
+        wallet.store().await?;
+
+        let root_hash = Hash::hash(b"content");
+        let payment = wallet
+            .local_send_storage_payment(Token::from_nano(30), root_hash, 3, None)
+            .await?;
+        wallet.store().await?;
+
+        // The history is read back from the file.
+        let history = LocalWallet::load_from(&root_dir).await?.history().await?;
+        let summary: Vec<_> = history
+            .iter()
+            .map(|record| (record.kind, record.amount, record.state))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (HistoryKind::Deposit, GENESIS_DBC_AMOUNT, TxState::Confirmed),
+                (HistoryKind::Send, 100, TxState::Confirmed),
+                (
+                    HistoryKind::Change,
+                    GENESIS_DBC_AMOUNT - 100,
+                    TxState::Confirmed
+                ),
+                (HistoryKind::StoragePayment, 30, TxState::Pending),
+                (
+                    HistoryKind::Change,
+                    GENESIS_DBC_AMOUNT - 130,
+                    TxState::Pending
+                ),
+            ]
+        );
+        assert_eq!(
+            history[1].recipient,
+            Some(hex::encode(recipient.to_bytes()))
+        );
+        assert_eq!(history[3].tx_hash, payment.tx.hash().to_hex());
+        assert_eq!(history[3].root_hash, Some(root_hash.to_hex()));
+        assert_eq!(history[3].records_count, Some(3));
+
+        let csv = history_to_csv(&history);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("timestamp,kind,tx_hash,amount,recipient,root_hash,records_count,state")
+        );
+        let payment_line = format!(",30,,{},3,pending", root_hash.to_hex());
+        assert!(lines
+            .nth(3)
+            .map_or(false, |line| line.ends_with(&payment_line)));
+
+        // The statement totals reconcile with the balance, the change not adding to it.
+        let mut total = 0;
+        for line in csv.lines().skip(1) {
+            let fields: Vec<_> = line.split(',').collect();
+            let amount: i128 = fields[3].parse().expect("The amount to be a number.");
+            match (fields[1], fields[7]) {
+                (_, "rolled_back") | ("change", _) => {}
+                ("deposit", _) => total += amount,
+                _ => total -= amount,
+            }
+        }
+        assert_eq!(total, (GENESIS_DBC_AMOUNT - 130) as i128);
+        assert_eq!(wallet.balance().as_nano(), GENESIS_DBC_AMOUNT - 130);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_txs_are_stored_and_can_be_rolled_back() -> Result<()> {
+        let dir = create_temp_dir();
+        let root_dir = dir.path().to_path_buf();
+
+        let mut sender = LocalWallet::load_from(&root_dir).await?;
+        let sender_dbc =
+            create_first_dbc_from_key(&sender.key).expect("Genesis creation to succeed.");
+        sender.deposit(vec![sender_dbc]);
+
+        let recipient = MainKey::random().public_address();
+        let transfer = sender
+            .local_send(vec![(Token::from_nano(100), recipient)], None)
+            .await?;
+        let tx_hash = transfer.tx.hash();
+        sender.add_unconfirmed_tx(transfer);
+        sender.store().await?;
+
+        // The unconfirmed tx outlives the process.
+        let mut sender = LocalWallet::load_from(&root_dir).await?;
+        assert_eq!(1, sender.unconfirmed_txs().len());
+        assert_eq!(GENESIS_DBC_AMOUNT - 100, sender.balance().as_nano());
+
+        assert_eq!(sender.rollback_tx(tx_hash, &BTreeSet::new()), vec![tx_hash]);
+        assert!(sender.unconfirmed_txs().is_empty());
+        assert_eq!(GENESIS_DBC_AMOUNT, sender.balance().as_nano());
+        assert_eq!(1, sender.wallet.available_dbcs.len());
+        assert!(sender.wallet.spent_dbcs.is_empty());
+        assert!(sender.wallet.dbcs_created_for_others.is_empty());
+        sender.store().await?;
+
+        let sender = LocalWallet::load_from(&root_dir).await?;
+        assert!(sender.unconfirmed_txs().is_empty());
+        let states: Vec<_> = sender
+            .history()
+            .await?
+            .iter()
+            .map(|record| (record.kind, record.state))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (HistoryKind::Deposit, TxState::Confirmed),
+                (HistoryKind::Send, TxState::RolledBack),
+                (HistoryKind::Change, TxState::RolledBack),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rollbacks_cascade_to_the_txs_spending_their_outputs() -> Result<()> {
+        let dir = create_temp_dir();
+        let mut sender = LocalWallet::load_from(dir.path()).await?;
+        let sender_dbc =
+            create_first_dbc_from_key(&sender.key).expect("Genesis creation to succeed.");
+        sender.deposit(vec![sender_dbc]);
+
+        // The second send can only spend the change of the first one.
+        let recipient = MainKey::random().public_address();
+        let first = sender
+            .local_send(vec![(Token::from_nano(100), recipient)], None)
+            .await?;
+        let second = sender
+            .local_send(vec![(Token::from_nano(50), recipient)], None)
+            .await?;
+        let first_change = first.change_dbc.as_ref().map(|dbc| dbc.id());
+        assert!(second
+            .tx
+            .inputs
+            .iter()
+            .any(|input| Some(input.dbc_id()) == first_change));
+        let (first_hash, second_hash) = (first.tx.hash(), second.tx.hash());
+        sender.add_unconfirmed_tx(first);
+        sender.add_unconfirmed_tx(second);
+        assert_eq!(GENESIS_DBC_AMOUNT - 150, sender.balance().as_nano());
+
+        let rolled_back = sender.rollback_tx(first_hash, &BTreeSet::new());
+        assert_eq!(rolled_back, vec![first_hash, second_hash]);
+        assert!(sender.unconfirmed_txs().is_empty());
+        assert_eq!(GENESIS_DBC_AMOUNT, sender.balance().as_nano());
+        assert_eq!(1, sender.wallet.available_dbcs.len());
+        assert!(sender.wallet.spent_dbcs.is_empty());
+        assert!(sender.wallet.dbcs_created_for_others.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dbcs_marked_as_spent_leave_the_balance() -> Result<()> {
+        let key = MainKey::random();
+        let genesis = create_first_dbc_from_key(&key).expect("Genesis creation to succeed.");
+        let dbc_id = genesis.id();
+        let dir = create_temp_dir();
+
+        let mut wallet = LocalWallet {
+            key,
+            wallet: KeyLessWallet::new(),
+            wallet_dir: dir.path().to_path_buf(),
+            unstored_history: vec![],
+            unconfirmed_txs: vec![],
+            coin_selection: CoinSelection::default(),
+        };
+        wallet.deposit(vec![genesis.clone()]);
+
+        let tx_hash = Hash::hash(b"spent elsewhere");
+        assert!(wallet.mark_as_spent(&dbc_id, tx_hash));
+        assert!(!wallet.mark_as_spent(&dbc_id, tx_hash));
+        assert_eq!(Token::zero(), wallet.balance());
+        assert!(wallet.wallet.available_dbcs.is_empty());
+        assert!(wallet.wallet.spent_dbcs.contains_key(&dbc_id));
+
+        // The drop of the balance is recorded in the history, once.
+        let spent_elsewhere: Vec<_> = wallet
+            .unstored_history
+            .iter()
+            .filter_map(|entry| match entry {
+                HistoryEntry::Record(record) if record.kind == HistoryKind::SpentElsewhere => {
+                    Some((record.amount, record.tx_hash.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            spent_elsewhere,
+            vec![(GENESIS_DBC_AMOUNT, tx_hash.to_hex())]
+        );
+
+        // Nor can it be deposited again.
+        wallet.deposit(vec![genesis]);
+        assert_eq!(Token::zero(), wallet.balance());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn coin_selection_picks_the_dbcs_to_spend() -> Result<()> {
+        let dir = create_temp_dir();
+        let mut wallet = LocalWallet::load_from(dir.path()).await?;
+        let genesis = create_first_dbc_from_key(&wallet.key).expect("Genesis creation to succeed.");
+        wallet.deposit(vec![genesis]);
+
+        // A send to several recipients, two of them being ourselves, leaves us with small dbcs.
+        let other = MainKey::random().public_address();
+        let to = vec![
+            (Token::from_nano(10), wallet.address()),
+            (Token::from_nano(20), wallet.address()),
+            (Token::from_nano(100), other),
+        ];
+        let transfer = wallet.local_send(to, None).await?;
+        assert_eq!(3, transfer.created_dbcs.len());
+        let change = GENESIS_DBC_AMOUNT - 130;
+        assert_eq!(change, wallet.balance().as_nano());
+        let ours: Vec<_> = transfer
+            .created_dbcs
+            .into_iter()
+            .filter(|dbc| dbc.public_address() == &wallet.address())
+            .collect();
+        wallet.deposit(ours);
+        assert_eq!(change + 30, wallet.balance().as_nano());
+
+        let to = vec![(Token::from_nano(15), other)];
+        let input_amounts = |plan: &TransferPlan| {
+            plan.inputs
+                .iter()
+                .map(|(_, amount)| amount.as_nano())
+                .collect::<Vec<_>>()
+        };
+
+        let plan = wallet.plan_send(to.clone())?;
+        assert_eq!(vec![change], input_amounts(&plan));
+        assert_eq!(Token::from_nano(change - 15), plan.change);
+        assert_eq!(to, plan.outputs);
+
+        wallet.set_coin_selection(CoinSelection::SmallestFirst);
+        let plan = wallet.plan_send(to.clone())?;
+        assert_eq!(vec![10, 20], input_amounts(&plan));
+        assert_eq!(Token::from_nano(15), plan.change);
+
+        wallet.set_coin_selection(CoinSelection::Random);
+        let plan = wallet.plan_send(to.clone())?;
+        let spent: u64 = input_amounts(&plan).iter().sum();
+        assert_eq!(spent, 15 + plan.change.as_nano());
+
+        // Planning leaves the wallet untouched, unlike sending.
+        assert_eq!(change + 30, wallet.balance().as_nano());
+        wallet.set_coin_selection(CoinSelection::SmallestFirst);
+        let transfer = wallet.local_send(to, None).await?;
+        assert_eq!(2, transfer.tx.inputs.len());
+        assert_eq!(change + 15, wallet.balance().as_nano());
+
+        Ok(())
+    }
+
     fn create_temp_dir() -> TempDir {
         TempDir::new().expect("Should be able to create a temp dir.")
     }
@@ -53,12 +53,14 @@
 //!
 
 mod error;
+mod history;
 mod keys;
 mod local_store;
 mod wallet_file;
 
 pub use self::{
     error::{Error, Result},
+    history::{history_to_csv, history_to_json, HistoryKind, HistoryRecord, TxState},
     keys::{bls_secret_from_hex, parse_public_address},
     local_store::LocalWallet,
 };
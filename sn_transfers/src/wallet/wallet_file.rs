@@ -8,18 +8,22 @@
 
 use super::{
     error::{Error, Result},
+    history::HistoryEntry,
     public_address_name, KeyLessWallet,
 };
+use crate::client_transfers::TransferOutputs;
 
 use sn_dbc::Dbc;
 use sn_protocol::storage::DbcAddress;
 use std::path::{Path, PathBuf};
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt};
 
 // Filename for storing a wallet.
 const WALLET_FILE_NAME: &str = "wallet";
 const CREATED_DBCS_DIR_NAME: &str = "created_dbcs";
 const RECEIVED_DBCS_DIR_NAME: &str = "received_dbcs";
+const HISTORY_FILE_NAME: &str = "history";
+const UNCONFIRMED_TXS_FILE_NAME: &str = "unconfirmed_txs";
 
 pub(super) async fn create_received_dbcs_dir(wallet_dir: &Path) -> Result<()> {
     let received_dbcs_dir = wallet_dir.join(RECEIVED_DBCS_DIR_NAME);
@@ -48,6 +52,90 @@ pub(super) async fn get_wallet(wallet_dir: &Path) -> Result<Option<KeyLessWallet
     Ok(Some(wallet))
 }
 
+/// Writes the unconfirmed txs to the wallet dir, removing their file once there are none.
+pub(super) async fn store_unconfirmed_txs(
+    wallet_dir: &Path,
+    unconfirmed_txs: &[TransferOutputs],
+) -> Result<()> {
+    let path = wallet_dir.join(UNCONFIRMED_TXS_FILE_NAME);
+    if unconfirmed_txs.is_empty() {
+        if path.is_file() {
+            fs::remove_file(&path).await?;
+        }
+        return Ok(());
+    }
+
+    let bytes = bincode::serialize(unconfirmed_txs)?;
+    fs::write(&path, bytes).await?;
+    Ok(())
+}
+
+/// Returns the unconfirmed txs stored in the wallet dir, if any.
+pub(super) async fn get_unconfirmed_txs(wallet_dir: &Path) -> Result<Vec<TransferOutputs>> {
+    let path = wallet_dir.join(UNCONFIRMED_TXS_FILE_NAME);
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    let bytes = fs::read(&path).await?;
+    let unconfirmed_txs = bincode::deserialize(&bytes)?;
+    Ok(unconfirmed_txs)
+}
+
+/// Appends the entries to the history file, one JSON line each, syncing it to disk.
+pub(super) async fn append_history(wallet_dir: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+
+    let path = wallet_dir.join(HISTORY_FILE_NAME);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await?;
+    // Drop what an interrupted append left after the last complete line,
+    // so the new entries don't get joined onto it.
+    let content = fs::read(&path).await?;
+    let complete_len = content
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |last_newline| last_newline + 1);
+    if complete_len < content.len() {
+        file.set_len(complete_len as u64).await?;
+    }
+    file.write_all(lines.as_bytes()).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Loads the entries of the history file, in the order they were appended.
+pub(super) async fn load_history(wallet_dir: &Path) -> Result<Vec<HistoryEntry>> {
+    let path = wallet_dir.join(HISTORY_FILE_NAME);
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    // Every entry is written with its newline, so only what follows the last one
+    // can be a partially written line, from being interrupted. That is skipped,
+    // while a bad line anywhere else is an error.
+    let content = fs::read_to_string(&path).await?;
+    let complete_len = content
+        .rfind('\n')
+        .map_or(0, |last_newline| last_newline + 1);
+    let mut entries = vec![];
+    for line in content[..complete_len].lines() {
+        entries.push(serde_json::from_str(line)?);
+    }
+    Ok(entries)
+}
+
 /// Hex encode and write each `Dbc` to a separate file in respective
 /// recipient public address dir in the created dbcs dir. Each file is named after the dbc id.
 pub(super) async fn store_created_dbcs(created_dbcs: Vec<Dbc>, wallet_dir: &Path) -> Result<()> {